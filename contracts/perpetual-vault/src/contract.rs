@@ -1,7 +1,7 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
-    MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg, Storage
+    attr, from_binary, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
 use cw20_base::msg::InstantiateMsg as TokenInstantiateMsg;
@@ -10,24 +10,42 @@ use injective_math::scale::Scaled;
 use injective_math::FPDecimal;
 use injective_protobuf::proto::tx;
 use protobuf::Message;
+use std::cmp::min;
 use std::str::FromStr;
 
 use injective_cosmwasm::{
-    cancel_derivative_order_msg, create_derivative_market_order_msg,
-    get_default_subaccount_id_for_checked_address, DerivativeOrder, InjectiveMsgWrapper,
-    InjectiveQuerier, InjectiveQueryWrapper, MarketStatus, OrderType,
+    create_batch_update_orders_msg, create_derivative_market_order_msg,
+    get_default_subaccount_id_for_checked_address, DerivativeOrder, FullDerivativeMarket,
+    InjectiveMsgWrapper, InjectiveQuerier, InjectiveQueryWrapper, MarketStatus, OrderType,
+    Position,
 };
 
-use crate::asset::{addr_opt_validate, format_lp_token_name, Asset, AssetInfo, CoinsExt};
+use crate::asset::{
+    addr_opt_validate, format_lp_token_name, format_lp_token_symbol, Asset, AssetInfo, CoinsExt,
+};
 use crate::error::ContractError;
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::helpers::{cancel_order_msg, is_on_tick, MarketType};
+use crate::msg::{
+    Cw20HookMsg, ExecuteMsg, FeeStateResponse, FillStatsResponse, InstantiateMsg,
+    NextFundingResponse, QueryMsg, RiskParamsResponse, UserPnlResponse,
+};
 use crate::querier::{query_balance, query_supply, query_token_balance};
 use crate::response::MsgInstantiateContractResponse;
-use crate::state::{ContractInfo, CONTRACT_INFO, FEE_COLLECTED};
+use crate::state::{
+    default_max_leverage, ContractInfo, FundingRecord, SwapRecord, SwapResult, CONTRACT_INFO,
+    CUMULATIVE_FUNDING, FEE_COLLECTED, INSTANTIATE_PARAMS, LAST_SWAP_RESULT, PENDING_SWAP_LONG,
+    SWAP_HISTORY, USER_DEPOSITS,
+};
 
 /// A `reply` call code ID used for sub-messages.
 pub const INSTANTIATE_TOKEN_REPLY_ID: u64 = 1u64;
 pub const ORDER_REPLY_ID: u64 = 2u64;
+pub const DEFAULT_LP_DECIMAL: u8 = 12;
+pub const MAX_LP_DECIMAL: u8 = 18;
+pub const DEFAULT_MAX_LEVERAGE: u128 = 10;
+pub const MAX_SWAP_HISTORY: usize = 50;
+pub const MAX_SHARE_CURVE_STEPS: u32 = 100;
+pub const MAX_WITHDRAW_CURVE_STEPS: u32 = 100;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -36,29 +54,65 @@ pub fn instantiate(
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let lp_decimal = msg.lp_decimal.unwrap_or(DEFAULT_LP_DECIMAL);
+    if lp_decimal > MAX_LP_DECIMAL {
+        return Err(ContractError::CustomError {
+            val: format!("LP decimal must not exceed {MAX_LP_DECIMAL}"),
+        });
+    }
+    if msg.quote_decimal == 0 {
+        return Err(ContractError::CustomError {
+            val: "Quote decimal must be positive".to_string(),
+        });
+    }
+    INSTANTIATE_PARAMS.save(deps.storage, &msg)?;
+
     let querier = InjectiveQuerier::new(&deps.querier);
     if let Some(full_market) = querier.query_derivative_market(&msg.market_id)?.market {
         if let Some(market) = full_market.market {
-            if market.status != MarketStatus::Active {
-                return Err(ContractError::CustomError {
-                    val: format!("Market with id: {} not active", msg.market_id.as_str()),
+            if market.status != MarketStatus::Active && !msg.allow_inactive_market {
+                return Err(ContractError::MarketNotActive {
+                    market_id: msg.market_id.as_str().to_string(),
                 });
             }
+            let max_leverage = msg.max_leverage.unwrap_or_else(default_max_leverage);
+            assert_max_leverage(max_leverage)?;
             cw_ownable::initialize_owner(deps.storage, deps.api, Some(msg.owner.as_str()))
                 .expect(format!("Invalid owner: {}", msg.owner).as_str());
+            let liquidity_token = match &msg.existing_lp_token {
+                Some(existing_lp_token) => deps.api.addr_validate(existing_lp_token)?,
+                None => Addr::unchecked(""),
+            };
             let contract_info = ContractInfo {
                 market_id: msg.market_id,
                 quote_denom: market.quote_denom,
                 quote_decimal: msg.quote_decimal,
                 hardcap: msg.hardcap,
-                liquidity_token: Addr::unchecked(""),
+                liquidity_token,
                 contract_subaccount_id: get_default_subaccount_id_for_checked_address(
                     &env.contract.address,
                 ),
+                allow_inactive_market: msg.allow_inactive_market,
+                lp_decimal,
+                min_order_notional: FPDecimal::zero(),
+                paused: false,
+                fee_reinvest: false,
+                max_leverage,
             };
             CONTRACT_INFO.save(deps.storage, &contract_info)?;
             FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
-            let token_name = format_lp_token_name(&contract_info.quote_denom)?;
+            if msg.existing_lp_token.is_some() {
+                return Ok(Response::<InjectiveMsgWrapper>::new()
+                    .add_attribute("method", "instantiate")
+                    .add_attribute("liquidity_token_addr", contract_info.liquidity_token));
+            }
+            let token_name = match msg.lp_token_name {
+                Some(lp_token_name) => lp_token_name,
+                None => format_lp_token_name(&contract_info.quote_denom)?,
+            };
+            let token_symbol = msg
+                .lp_token_symbol
+                .unwrap_or_else(|| format_lp_token_symbol(&market.ticker));
 
             // Create the LP token contract
             let sub_msg: Vec<SubMsg<InjectiveMsgWrapper>> = vec![SubMsg {
@@ -66,8 +120,8 @@ pub fn instantiate(
                     code_id: msg.token_code_id,
                     msg: to_binary(&TokenInstantiateMsg {
                         name: token_name,
-                        symbol: "uLP".to_string(),
-                        decimals: 12,
+                        symbol: token_symbol,
+                        decimals: lp_decimal,
                         initial_balances: vec![],
                         mint: Some(MinterResponse {
                             minter: env.contract.address.to_string(),
@@ -89,13 +143,13 @@ pub fn instantiate(
                 .add_submessages(sub_msg)
                 .add_attribute("method", "instantiate"))
         } else {
-            Err(ContractError::CustomError {
-                val: format!("Market with id: {} not found", msg.market_id.as_str()),
+            Err(ContractError::MarketNotFound {
+                market_id: msg.market_id.as_str().to_string(),
             })
         }
     } else {
-        Err(ContractError::CustomError {
-            val: format!("Market with id: {} not found", msg.market_id.as_str()),
+        Err(ContractError::MarketNotFound {
+            market_id: msg.market_id.as_str().to_string(),
         })
     }
 }
@@ -145,8 +199,8 @@ fn handle_instantiate_token_reply(
 }
 
 fn handle_order_reply(
-    _deps: DepsMut<InjectiveQueryWrapper>,
-    _env: Env,
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
     msg: Reply,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
     let dec_scale_factor: FPDecimal = FPDecimal::from(1000000000000000000_i128);
@@ -167,17 +221,43 @@ fn handle_order_reply(
         err: err.to_string(),
     })?;
 
-    // unwrap results into trade_data
+    // unwrap results into trade_data, an unfilled market order leaves the vault untouched
     let trade_data = match order_response.results.into_option() {
-        Some(trade_data) => Ok(trade_data),
-        None => Err(ContractError::CustomError {
-            val: "No trade data in order response".to_string(),
-        }),
-    }?;
+        Some(trade_data) => trade_data,
+        None => {
+            return Ok(Response::new().add_attributes(vec![
+                attr("action", "swap_unfilled"),
+                attr("order_hash", order_response.order_hash),
+            ]))
+        }
+    };
     let quantity = FPDecimal::from_str(&trade_data.quantity)? / dec_scale_factor;
     let price = FPDecimal::from_str(&trade_data.price)? / dec_scale_factor;
     let fee = FPDecimal::from_str(&trade_data.fee)? / dec_scale_factor;
 
+    let mut swap_history = SWAP_HISTORY.may_load(deps.storage)?.unwrap_or_default();
+    swap_history.push(SwapRecord {
+        order_hash: order_response.order_hash.clone(),
+        long: PENDING_SWAP_LONG.load(deps.storage)?,
+        quantity,
+        price,
+        fee,
+        height: env.block.height,
+    });
+    if swap_history.len() > MAX_SWAP_HISTORY {
+        swap_history.remove(0);
+    }
+    SWAP_HISTORY.save(deps.storage, &swap_history)?;
+    LAST_SWAP_RESULT.save(
+        deps.storage,
+        &SwapResult {
+            order_hash: order_response.order_hash.clone(),
+            quantity,
+            price,
+            fee,
+        },
+    )?;
+
     Ok(Response::new().add_attributes(vec![
         attr("action", "swap"),
         attr("order_hash", order_response.order_hash),
@@ -209,16 +289,49 @@ pub fn execute(
             }
         }
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
-        ExecuteMsg::Deposit { assets, receiver } => deposit(deps, env, info, assets, receiver),
+        ExecuteMsg::Deposit {
+            assets,
+            receiver,
+            min_lp_out,
+            max_share_price,
+        } => deposit(
+            deps,
+            env,
+            info,
+            assets,
+            receiver,
+            min_lp_out,
+            max_share_price,
+        ),
         ExecuteMsg::SwapPerpetual {
             long,
             quantity,
             price,
             margin,
         } => try_swap(deps, env, info, long, quantity, price, margin),
+        ExecuteMsg::SwapPerpetualLeverage {
+            long,
+            quantity,
+            price,
+            leverage,
+        } => try_swap_leverage(deps, env, info, long, quantity, price, leverage),
         ExecuteMsg::CancelOrder { order_hash } => try_cancel_order(deps, env, info, order_hash),
+        ExecuteMsg::CancelAllOrders {} => try_cancel_all_orders(deps, env, info),
         ExecuteMsg::AddFee { fee } => add_fee(deps, env, info, fee),
         ExecuteMsg::WithdrawFee { fee } => withdraw_fee(deps, env, info, fee),
+        ExecuteMsg::SweepToken { denom, recipient } => {
+            sweep_token(deps, env, info, denom, recipient)
+        }
+        ExecuteMsg::UpdateMinOrderNotional { min_order_notional } => {
+            update_min_order_notional(deps, env, info, min_order_notional)
+        }
+        ExecuteMsg::SetPaused { paused } => set_paused(deps, env, info, paused),
+        ExecuteMsg::ToggleFeeReinvest { enabled } => toggle_fee_reinvest(deps, env, info, enabled),
+        ExecuteMsg::UpdateHardcap { hardcap } => update_hardcap(deps, env, info, hardcap),
+        ExecuteMsg::UpdateMaxLeverage { max_leverage } => {
+            update_max_leverage(deps, env, info, max_leverage)
+        }
+        ExecuteMsg::SettleFunding {} => settle_funding(deps, env, info),
     }
 }
 
@@ -257,17 +370,21 @@ fn deposit(
     info: MessageInfo,
     assets: Vec<Asset>,
     receiver: Option<String>,
+    min_lp_out: Option<Uint128>,
+    max_share_price: Option<Uint128>,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
     if assets.len() != 1 {
         return Err(StdError::generic_err("assets must contain exactly one element").into());
     }
-    assets[0].info.check(deps.api)?;
+    assert_valid_denom(deps.api, &assets[0].info)?;
 
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    assert_not_paused(&contract_info)?;
 
-    let supported = vec![AssetInfo {
+    let supported = vec![AssetInfo::NativeToken {
         denom: contract_info.quote_denom.clone(),
     }];
+    assert_exact_quote_denom(&contract_info, &assets, &info.funds)?;
     info.funds.assert_coins_properly_sent(&assets, &supported)?;
 
     let amount = assets
@@ -284,18 +401,21 @@ fn deposit(
 
     let mut messages = vec![];
 
-    let _share = convert_to_shares(
-        deps.as_ref(),
-        env,
-        scaled_amount,
-        contract_info.quote_decimal,
-    )?;
-    let share = Uint128::new(u128::from(_share.scaled(12)));
+    let nav = FPDecimal::from(get_nav(deps.as_ref(), &env)?)
+        .scaled(-(contract_info.quote_decimal as i32));
+    if let Some(max_share_price) = max_share_price {
+        assert_max_share_price(deps.as_ref(), &contract_info, nav, max_share_price)?;
+    }
+    let _share = convert_to_shares(deps.as_ref(), scaled_amount, nav)?;
+    let share = Uint128::new(u128::from(_share.scaled(contract_info.lp_decimal as i32)));
 
     if share.is_zero() {
-        return Err(ContractError::CustomError {
-            val: format!("Zero share amount"),
-        });
+        return Err(ContractError::ZeroShare {});
+    }
+    if let Some(min_lp_out) = min_lp_out {
+        if share < min_lp_out {
+            return Err(ContractError::SlippageExceeded { share, min_lp_out });
+        }
     }
 
     let receiver = addr_opt_validate(deps.api, &receiver)?.unwrap_or_else(|| info.sender.clone());
@@ -306,6 +426,10 @@ fn deposit(
         return Err(ContractError::ExceedHardcap {});
     }
 
+    USER_DEPOSITS.update(deps.storage, &receiver, |deposited| -> StdResult<_> {
+        Ok(deposited.unwrap_or_default() + amount)
+    })?;
+
     // Mint LP tokens for the sender or for the receiver (if set)
     messages.extend(mint_liquidity_token_message(
         &contract_info,
@@ -334,6 +458,71 @@ fn deposit(
     Ok(res)
 }
 
+/// Rejects a deposit when the NAV per LP token, in quote units, is above `max_share_price`. A
+/// vault without LP supply has no share price yet.
+fn assert_max_share_price(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    nav: FPDecimal,
+    max_share_price: Uint128,
+) -> Result<(), ContractError> {
+    let total_share = FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?)
+        .scaled(-(contract_info.lp_decimal as i32));
+    if total_share.is_zero() {
+        return Ok(());
+    }
+
+    let share_price = Uint128::new(u128::from(
+        (nav / total_share).scaled(contract_info.quote_decimal as i32),
+    ));
+    if share_price > max_share_price {
+        return Err(ContractError::SharePriceExceeded {
+            share_price,
+            max_share_price,
+        });
+    }
+
+    Ok(())
+}
+
+fn assert_not_paused(contract_info: &ContractInfo) -> Result<(), ContractError> {
+    if contract_info.paused {
+        return Err(ContractError::Paused {});
+    }
+
+    Ok(())
+}
+
+/// Rejects denoms that only differ from the quote denom by case, as those are distinct bank
+/// denoms that may not share the quote decimals.
+fn assert_exact_quote_denom(
+    contract_info: &ContractInfo,
+    assets: &[Asset],
+    funds: &[Coin],
+) -> Result<(), ContractError> {
+    let denoms = assets
+        .iter()
+        .filter_map(|asset| match &asset.info {
+            AssetInfo::NativeToken { denom } => Some(denom),
+            AssetInfo::Token { .. } => None,
+        })
+        .chain(funds.iter().map(|coin| &coin.denom));
+    for denom in denoms {
+        if *denom != contract_info.quote_denom
+            && denom.eq_ignore_ascii_case(&contract_info.quote_denom)
+        {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Denom {} does not match quote denom {}",
+                    denom, contract_info.quote_denom
+                ),
+            });
+        }
+    }
+
+    Ok(())
+}
+
 fn try_swap(
     deps: DepsMut<InjectiveQueryWrapper>,
     env: Env,
@@ -348,6 +537,10 @@ fn try_swap(
     if !is_owner(deps.storage, &info.sender)? {
         return Err(ContractError::Unauthorized {});
     }
+    assert_not_paused(&contract_info)?;
+    if contract_info.allow_inactive_market {
+        assert_market_active(deps.as_ref(), &contract_info)?;
+    }
 
     let contract = env.contract.address;
     let subaccount_id = contract_info.contract_subaccount_id;
@@ -357,10 +550,40 @@ fn try_swap(
             val: "Do not provide funds!".to_string(),
         });
     }
+    assert_tick_sizes(deps.as_ref(), &contract_info, price, quantity)?;
+    if min_amount < contract_info.min_order_notional {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: notional {min_amount} below min_order_notional: {}",
+                contract_info.min_order_notional
+            ),
+        });
+    }
+    if margin <= FPDecimal::zero() {
+        return Err(ContractError::CustomError {
+            val: "Swap: margin must be positive".to_string(),
+        });
+    }
+    let required_margin = get_required_margin(deps.as_ref(), &contract_info, quantity, price)?;
+    if margin < required_margin {
+        return Err(ContractError::CustomError {
+            val: format!("Swap: margin {margin} below required margin: {required_margin}"),
+        });
+    }
+    let leverage = min_amount / margin;
+    if leverage > contract_info.max_leverage {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: leverage {leverage} above max_leverage: {}",
+                contract_info.max_leverage
+            ),
+        });
+    }
     let denom = contract_info.quote_denom;
     let fee_collected = FEE_COLLECTED.load(deps.storage)?;
-    let balance =
-        FPDecimal::from(query_balance(&deps.querier, contract.to_string(), denom)? - fee_collected);
+    let balance = FPDecimal::from(
+        query_balance(&deps.querier, contract.to_string(), denom)?.saturating_sub(fee_collected),
+    );
     if balance < min_amount {
         return Err(ContractError::CustomError {
             val: format!("Swap: {balance} below min_amount: {min_amount}"),
@@ -381,15 +604,102 @@ fn try_swap(
         Some(contract.to_owned()),
     );
 
+    PENDING_SWAP_LONG.save(deps.storage, &long)?;
+
     let order_message = SubMsg::reply_on_success(
         create_derivative_market_order_msg(contract, order),
         ORDER_REPLY_ID,
     );
-    let response = Response::<InjectiveMsgWrapper>::new().add_submessage(order_message);
+    let mut response = Response::<InjectiveMsgWrapper>::new().add_submessage(order_message);
+    // Allowed since the initial margin ratio may exceed 1, but usually wastes collateral
+    if margin > min_amount {
+        response = response.add_attribute(
+            "warning",
+            format!("margin {margin} exceeds notional {min_amount}"),
+        );
+    }
 
     Ok(response)
 }
 
+/// Places a perpetual order with the margin derived from `leverage`, which must be covered by
+/// the market initial margin ratio.
+fn try_swap_leverage(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    long: bool,
+    quantity: FPDecimal,
+    price: FPDecimal,
+    leverage: FPDecimal,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if leverage <= FPDecimal::zero() {
+        return Err(ContractError::CustomError {
+            val: "Leverage must be positive".to_string(),
+        });
+    }
+
+    let margin = price * quantity / leverage;
+
+    try_swap(deps, env, info, long, quantity, price, margin)
+}
+
+/// Rejects an order the exchange would fail for a price or quantity off the market ticks.
+fn assert_tick_sizes(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    price: FPDecimal,
+    quantity: FPDecimal,
+) -> Result<(), ContractError> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let market = querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .and_then(|full_market| full_market.market)
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?;
+
+    if !is_on_tick(price, market.min_price_tick_size) {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: price {price} is not a multiple of min_price_tick_size: {}",
+                market.min_price_tick_size
+            ),
+        });
+    }
+    if !is_on_tick(quantity, market.min_quantity_tick_size) {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: quantity {quantity} is not a multiple of min_quantity_tick_size: {}",
+                market.min_quantity_tick_size
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks that the vault market is active, used when the vault was deployed ahead of it.
+fn assert_market_active(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> Result<(), ContractError> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let status = querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .and_then(|full_market| full_market.market)
+        .map(|market| market.status);
+    if status != Some(MarketStatus::Active) {
+        return Err(ContractError::MarketNotActive {
+            market_id: contract_info.market_id.as_str().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
 fn try_cancel_order(
     deps: DepsMut<InjectiveQueryWrapper>,
     env: Env,
@@ -405,14 +715,51 @@ fn try_cancel_order(
     let contract = env.contract.address;
     let subaccount_id = contract_info.contract_subaccount_id;
 
-    let cancel_message = cancel_derivative_order_msg(
+    let cancel_message = cancel_order_msg(
+        MarketType::Derivative,
         contract,
         contract_info.market_id.clone(),
         subaccount_id.clone(),
-        order_hash,
-        1,
+        order_hash.clone(),
+    );
+    let response = Response::<InjectiveMsgWrapper>::new()
+        .add_message(cancel_message)
+        .add_attributes(vec![
+            attr("action", "cancel_order"),
+            attr("order_hash", order_hash),
+            attr("market_id", contract_info.market_id.as_str()),
+        ]);
+
+    Ok(response)
+}
+
+fn try_cancel_all_orders(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let cancel_message = create_batch_update_orders_msg(
+        env.contract.address,
+        Some(contract_info.contract_subaccount_id),
+        vec![],
+        vec![contract_info.market_id.clone()],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
     );
-    let response = Response::<InjectiveMsgWrapper>::new().add_message(cancel_message);
+    let response = Response::<InjectiveMsgWrapper>::new()
+        .add_message(cancel_message)
+        .add_attributes(vec![
+            attr("action", "cancel_all_orders"),
+            attr("market_id", contract_info.market_id.as_str()),
+        ]);
 
     Ok(response)
 }
@@ -427,6 +774,11 @@ fn add_fee(
         return Err(ContractError::Unauthorized {});
     }
 
+    // Reinvested fees are left in the vault balance as working capital
+    if CONTRACT_INFO.load(deps.storage)?.fee_reinvest {
+        return Ok(Response::new().add_attribute("fee_reinvested", fee));
+    }
+
     let fee_collected = FEE_COLLECTED.load(deps.storage)?;
 
     FEE_COLLECTED.save(deps.storage, &(fee_collected + fee))?;
@@ -453,9 +805,7 @@ fn withdraw_fee(
 
     let fee_collected = FEE_COLLECTED.load(deps.storage)?;
     if fee_collected < fee {
-        return Err(ContractError::CustomError {
-            val: format!("Insufficient fee accrued"),
-        });
+        return Err(ContractError::InsufficientFeeAccrued {});
     }
 
     FEE_COLLECTED.save(deps.storage, &(fee_collected - fee))?;
@@ -475,7 +825,7 @@ fn withdraw_fee(
             "{}",
             Asset {
                 amount: fee,
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: contract_info.quote_denom
                 },
             }
@@ -483,6 +833,286 @@ fn withdraw_fee(
     ))
 }
 
+/// Sends the whole balance of a denom sent to the vault by mistake to `recipient`. The quote
+/// denom and the INJ fee asset belong to LP holders and can't be swept.
+fn sweep_token(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    recipient: String,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if denom == contract_info.quote_denom || denom == "INJ" {
+        return Err(ContractError::CustomError {
+            val: format!("Can't sweep vault denom {denom}"),
+        });
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let amount = query_balance(&deps.querier, env.contract.address.to_string(), &denom)?;
+    if amount.is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("No {denom} balance to sweep"),
+        });
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin::new(u128::from(amount), denom.clone())],
+        })
+        .add_attributes(vec![
+            attr("action", "sweep_token"),
+            attr("recipient", recipient),
+            attr("swept", format!("{amount}{denom}")),
+        ]))
+}
+
+fn update_min_order_notional(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    min_order_notional: FPDecimal,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.min_order_notional = min_order_notional;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_min_order_notional"),
+        attr("min_order_notional", min_order_notional.to_string()),
+    ]))
+}
+
+fn set_paused(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.paused = paused;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_paused"),
+        attr("paused", paused.to_string()),
+    ]))
+}
+
+fn toggle_fee_reinvest(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.fee_reinvest = enabled;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "toggle_fee_reinvest"),
+        attr("enabled", enabled.to_string()),
+    ]))
+}
+
+fn update_hardcap(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    hardcap: Uint128,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if hardcap < total_share {
+        return Err(ContractError::CustomError {
+            val: format!("Hardcap {hardcap} below current supply: {total_share}"),
+        });
+    }
+    contract_info.hardcap = hardcap;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_hardcap"),
+        attr("hardcap", hardcap),
+    ]))
+}
+
+fn update_max_leverage(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    max_leverage: FPDecimal,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    assert_max_leverage(max_leverage)?;
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.max_leverage = max_leverage;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_max_leverage"),
+        attr("max_leverage", max_leverage.to_string()),
+    ]))
+}
+
+fn assert_max_leverage(max_leverage: FPDecimal) -> Result<(), ContractError> {
+    if max_leverage <= FPDecimal::zero() {
+        return Err(ContractError::CustomError {
+            val: "max_leverage must be positive".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn query_market_info(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FullDerivativeMarket> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| StdError::generic_err("Market not found"))
+}
+
+/// Returns the next funding time of the market and how long until it, zero when already due.
+fn query_next_funding(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+) -> StdResult<NextFundingResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let next_funding_timestamp = querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .and_then(|full_market| full_market.info)
+        .map(|info| info.perpetual_info.market_info.next_funding_timestamp)
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "No funding info for market with id: {}",
+                contract_info.market_id.as_str()
+            ))
+        })?;
+    let seconds_remaining =
+        (next_funding_timestamp - env.block.time.seconds() as i64).max(0) as u64;
+
+    Ok(NextFundingResponse {
+        next_funding_timestamp,
+        seconds_remaining,
+    })
+}
+
+/// Records the funding accrued on the open position since entry, callable by any keeper.
+fn settle_funding(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    _info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    let cumulative_funding = query_cumulative_funding(deps.as_ref(), &contract_info)?;
+    let funding = match get_open_position(deps.as_ref(), &contract_info)? {
+        Some((position, _)) => {
+            let funding = get_position_funding(&position, cumulative_funding);
+            CUMULATIVE_FUNDING.save(
+                deps.storage,
+                &FundingRecord {
+                    cumulative_funding_entry: position.cumulative_funding_entry,
+                    funding,
+                },
+            )?;
+            funding
+        }
+        None => {
+            CUMULATIVE_FUNDING.remove(deps.storage);
+            FPDecimal::zero()
+        }
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "settle_funding"),
+        attr("cumulative_funding", cumulative_funding.to_string()),
+        attr("funding", funding.to_string()),
+    ]))
+}
+
+/// Returns the cumulative funding of the vault market.
+fn query_cumulative_funding(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<FPDecimal> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .and_then(|full_market| full_market.info)
+        .map(|info| info.perpetual_info.funding_info.cumulative_funding)
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "No funding info for market with id: {}",
+                contract_info.market_id.as_str()
+            ))
+        })
+}
+
+/// Returns the funding recorded by the last `SettleFunding`, zero when it was settled for another
+/// position.
+fn get_recorded_funding(
+    deps: Deps<InjectiveQueryWrapper>,
+    position: &Position,
+) -> StdResult<FPDecimal> {
+    Ok(CUMULATIVE_FUNDING
+        .may_load(deps.storage)?
+        .filter(|record| record.cumulative_funding_entry == position.cumulative_funding_entry)
+        .map_or(FPDecimal::zero(), |record| record.funding))
+}
+
+/// Returns the funding accrued on `position` since entry in quote units, negative when paid.
+fn get_position_funding(position: &Position, cumulative_funding: FPDecimal) -> FPDecimal {
+    // Longs pay a positive funding, shorts receive it
+    let payment = position.quantity * (cumulative_funding - position.cumulative_funding_entry);
+    if position.isLong {
+        FPDecimal::zero() - payment
+    } else {
+        payment
+    }
+}
+
+/// Surfaces a malformed deposit denom as [`ContractError::InvalidDenom`].
+fn assert_valid_denom(api: &dyn Api, asset_info: &AssetInfo) -> Result<(), ContractError> {
+    asset_info
+        .check(api)
+        .map_err(|_| ContractError::InvalidDenom {
+            denom: asset_info.to_string(),
+        })
+}
+
 /// Mint LP tokens for a beneficiary and auto stake the tokens in the Generator contract (if auto staking is specified).
 ///
 /// * **recipient** is the LP token recipient.
@@ -510,7 +1140,8 @@ fn mint_liquidity_token_message(
 /// Withdraw liquidity from the pool.
 /// * **sender** is the address that will receive assets back from the pair contract.
 ///
-/// * **amount** is the amount of LP tokens to burn.
+/// * **amount** is the amount of LP tokens to burn. Shares the bank balance can't cover are
+/// returned to the sender.
 fn withdraw(
     deps: DepsMut<InjectiveQueryWrapper>,
     env: Env,
@@ -532,12 +1163,42 @@ fn withdraw(
     }
 
     let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
-    let refund_assets = get_share_in_assets(deps.as_ref(), env, amount, total_share)?;
+
+    // Shares are redeemed at the NAV. When part of it sits on the exchange, only the shares
+    // covered by the bank balance are burned and the rest are returned to the sender.
+    let balance = get_free_balance(deps.as_ref(), &env, &contract_info)?;
+    let value = get_share_value(deps.as_ref(), &env, amount, total_share)?;
+    let burn_amount = if value > balance {
+        amount.multiply_ratio(balance, value)
+    } else {
+        amount
+    };
+    if burn_amount.is_zero() {
+        return Err(ContractError::NoFreeBalance {});
+    }
+    let returned_share = amount - burn_amount;
+    let refund_assets = get_share_in_assets(deps.as_ref(), env, burn_amount, total_share)?;
+
+    // The withdrawn shares were already transferred to the vault
+    if let Some(deposited) = USER_DEPOSITS.may_load(deps.storage, &sender)? {
+        let remaining_share =
+            query_token_balance(&deps.querier, &contract_info.liquidity_token, &sender)?
+                + returned_share;
+        let deposited = deposited.multiply_ratio(remaining_share, remaining_share + burn_amount);
+        USER_DEPOSITS.save(deps.storage, &sender, &deposited)?;
+    }
+
+    // Quote value redeemed per LP token
+    let nav_per_share = FPDecimal::from(refund_assets[0].amount)
+        .scaled(-(contract_info.quote_decimal as i32))
+        / FPDecimal::from(burn_amount).scaled(-(contract_info.lp_decimal as i32));
 
     let mut messages: Vec<CosmosMsg<InjectiveMsgWrapper>> =
         vec![CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: contract_info.liquidity_token.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Burn { amount })?,
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: burn_amount,
+            })?,
             funds: vec![],
         })];
     if !refund_assets[0].amount.is_zero() {
@@ -546,15 +1207,31 @@ fn withdraw(
     if !refund_assets[1].amount.is_zero() {
         messages.push(refund_assets[1].clone().into_msg(sender.clone())?);
     }
+    if !returned_share.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_info.liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: sender.to_string(),
+                amount: returned_share,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    let mut attributes = vec![
+        attr("action", "withdraw"),
+        attr("sender", sender),
+        attr("withdrawn_share", burn_amount),
+        attr("refund_assets", format!("{}", refund_assets[0])),
+        attr("nav_per_share", nav_per_share.to_string()),
+    ];
+    if !returned_share.is_zero() {
+        attributes.push(attr("returned_share", returned_share));
+    }
 
     Ok(Response::<InjectiveMsgWrapper>::new()
         .add_messages(messages)
-        .add_attributes(vec![
-            attr("action", "withdraw"),
-            attr("sender", sender),
-            attr("withdrawn_share", amount),
-            attr("refund_assets", format!("{}", refund_assets[0])),
-        ]))
+        .add_attributes(attributes))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -565,6 +1242,34 @@ pub fn query(deps: Deps<InjectiveQueryWrapper>, env: Env, msg: QueryMsg) -> StdR
         QueryMsg::TotalLiquidity {} => to_binary(&get_total_liquidity(deps, env)?),
         QueryMsg::UserLiquidity { user } => to_binary(&get_user_liquidity(deps, env, user)?),
         QueryMsg::Tokens {} => to_binary(&query_tokens(deps.storage)?),
+        QueryMsg::LockedLiquidity {} => to_binary(&query_locked_liquidity(deps, env)?),
+        QueryMsg::UserPnl { user } => to_binary(&query_user_pnl(deps, env, user)?),
+        QueryMsg::ShareCurve { min, max, steps } => {
+            to_binary(&query_share_curve(deps, env, min, max, steps)?)
+        }
+        QueryMsg::FeeState {} => to_binary(&query_fee_state(deps)?),
+        QueryMsg::RiskParams {} => to_binary(&query_risk_params(deps)?),
+        QueryMsg::OwnerFeeClaimable {} => to_binary(&query_owner_fee_claimable(deps, env)?),
+        QueryMsg::PositionValueQuote {} => to_binary(&query_position_value_quote(deps)?),
+        QueryMsg::HasExposure {} => to_binary(&query_has_exposure(deps)?),
+        QueryMsg::RequiredMargin { quantity, price } => {
+            to_binary(&query_required_margin(deps, quantity, price)?)
+        }
+        QueryMsg::SwapHistory {} => {
+            to_binary(&SWAP_HISTORY.may_load(deps.storage)?.unwrap_or_default())
+        }
+        QueryMsg::LastSwapResult {} => to_binary(&LAST_SWAP_RESULT.may_load(deps.storage)?),
+        QueryMsg::FillStats {} => to_binary(&query_fill_stats(deps)?),
+        QueryMsg::NextFunding {} => to_binary(&query_next_funding(deps, env)?),
+        QueryMsg::MarketInfo {} => to_binary(&query_market_info(deps)?),
+        QueryMsg::WithdrawCurve { min, max, steps } => {
+            to_binary(&query_withdraw_curve(deps, env, min, max, steps)?)
+        }
+        QueryMsg::SimulateWithdraw { share } => {
+            to_binary(&query_simulate_withdraw(deps, env, share)?)
+        }
+        QueryMsg::InstantiateParams {} => to_binary(&INSTANTIATE_PARAMS.load(deps.storage)?),
+        QueryMsg::Config {} => to_binary(&CONTRACT_INFO.load(deps.storage)?),
     }
 }
 
@@ -574,28 +1279,48 @@ fn get_tokens_for_shares(
     share: Uint128,
 ) -> StdResult<Uint128> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let balance = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.quote_denom,
-    )? - FEE_COLLECTED.load(deps.storage)?;
-
     let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
 
-    let asset = balance * share / total_share;
+    get_share_value(deps, &env, share, total_share)
+}
 
-    Ok(asset)
+/// Returns the quote value of `share` LP tokens at the vault NAV, the same valuation deposits
+/// mint against.
+fn get_share_value(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    share: Uint128,
+    total_share: Uint128,
+) -> StdResult<Uint128> {
+    if total_share.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    Ok(get_nav(deps, env)?.multiply_ratio(share, total_share))
 }
 
 fn get_total_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<Uint128> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    get_free_balance(deps, &env, &contract_info)
+}
+
+/// Returns the quote bank balance net of collected fees.
+///
+/// Fees can exceed the bank balance once margin moved to the exchange, the free balance is zero
+/// then.
+fn get_free_balance(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+) -> StdResult<Uint128> {
     let balance = query_balance(
         &deps.querier,
         env.contract.address.to_string(),
         &contract_info.quote_denom,
-    )? - FEE_COLLECTED.load(deps.storage)?;
+    )?;
 
-    Ok(balance)
+    Ok(balance.saturating_sub(FEE_COLLECTED.load(deps.storage)?))
 }
 
 fn get_user_liquidity(
@@ -606,21 +1331,138 @@ fn get_user_liquidity(
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
     let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
     let share = query_token_balance(&deps.querier, &contract_info.liquidity_token, user)?;
+    let liquidity = get_share_value(deps, &env, share, total_share)?;
+
+    Ok([Asset {
+        amount: liquidity,
+        info: AssetInfo::NativeToken {
+            denom: contract_info.quote_denom.clone(),
+        },
+    }])
+}
+
+fn query_user_pnl(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    user: String,
+) -> StdResult<UserPnlResponse> {
+    let user = deps.api.addr_validate(&user)?;
+    let deposited = USER_DEPOSITS
+        .may_load(deps.storage, &user)?
+        .unwrap_or_default();
+    let value = if deposited.is_zero() {
+        Uint128::zero()
+    } else {
+        get_user_liquidity(deps, env, user.to_string())?[0].amount
+    };
+
+    Ok(UserPnlResponse {
+        deposited,
+        value,
+        pnl: FPDecimal::from(value) - FPDecimal::from(deposited),
+    })
+}
+
+/// Returns the stored fee counter as it is, without reconciling it against the balance.
+fn query_fee_state(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FeeStateResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    Ok(FeeStateResponse {
+        fee_collected: Asset {
+            amount: FEE_COLLECTED.load(deps.storage)?,
+            info: AssetInfo::NativeToken {
+                denom: contract_info.quote_denom,
+            },
+        },
+    })
+}
+
+/// Returns every risk limit applied to swaps.
+fn query_risk_params(deps: Deps<InjectiveQueryWrapper>) -> StdResult<RiskParamsResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    Ok(RiskParamsResponse {
+        min_order_notional: contract_info.min_order_notional,
+        max_leverage: contract_info.max_leverage,
+    })
+}
+
+/// Returns the fee the owner can withdraw right now, capped by the vault balance.
+fn query_owner_fee_claimable(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<Asset> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let fee_collected = FEE_COLLECTED.load(deps.storage)?;
     let balance = query_balance(
         &deps.querier,
         env.contract.address.to_string(),
         &contract_info.quote_denom,
-    )? - FEE_COLLECTED.load(deps.storage)?;
-    let liquidity = balance * share / total_share;
+    )?;
 
-    Ok([
-        Asset {
-            amount: liquidity,
-            info: AssetInfo {
-                denom: contract_info.quote_denom.clone(),
-            },
+    Ok(Asset {
+        amount: min(fee_collected, balance),
+        info: AssetInfo::NativeToken {
+            denom: contract_info.quote_denom,
         },
-    ])
+    })
+}
+
+/// Returns the notional value of the vault position at mark price in quote units, zero when flat.
+/// The mark price is already scaled by the market `oracle_scale_factor`.
+fn query_position_value_quote(deps: Deps<InjectiveQueryWrapper>) -> StdResult<Uint128> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    let value = match get_open_position(deps, &contract_info)? {
+        Some((position, mark_price)) => position.quantity * mark_price,
+        None => FPDecimal::zero(),
+    };
+
+    Ok(Uint128::new(u128::from(value)))
+}
+
+fn query_has_exposure(deps: Deps<InjectiveQueryWrapper>) -> StdResult<bool> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let position = querier
+        .query_vanilla_subaccount_position(
+            &contract_info.market_id,
+            &contract_info.contract_subaccount_id,
+        )?
+        .state;
+    Ok(matches!(position, Some(position) if !position.quantity.is_zero()))
+}
+
+/// Returns the minimum margin the market requires for an order of `quantity` at `price`.
+fn query_required_margin(
+    deps: Deps<InjectiveQueryWrapper>,
+    quantity: FPDecimal,
+    price: FPDecimal,
+) -> StdResult<FPDecimal> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    get_required_margin(deps, &contract_info, quantity, price)
+}
+
+/// Aggregates the fills kept in the swap history window.
+fn query_fill_stats(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FillStatsResponse> {
+    let swap_history = SWAP_HISTORY.may_load(deps.storage)?.unwrap_or_default();
+
+    let mut total_notional = FPDecimal::zero();
+    let mut total_quantity = FPDecimal::zero();
+    for record in swap_history.iter() {
+        total_notional = total_notional + record.price * record.quantity;
+        total_quantity = total_quantity + record.quantity;
+    }
+    let average_price = if total_quantity.is_zero() {
+        FPDecimal::zero()
+    } else {
+        total_notional / total_quantity
+    };
+
+    Ok(FillStatsResponse {
+        total_notional,
+        swap_count: swap_history.len() as u64,
+        average_price,
+    })
 }
 
 pub fn query_tokens(storage: &dyn Storage) -> StdResult<[String; 1]> {
@@ -629,33 +1471,204 @@ pub fn query_tokens(storage: &dyn Storage) -> StdResult<[String; 1]> {
     Ok([contract_info.quote_denom])
 }
 
+/// Returns the LP balance held by the vault itself. These shares can't be redeemed by anyone.
+fn query_locked_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<Uint128> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    query_token_balance(
+        &deps.querier,
+        &contract_info.liquidity_token,
+        env.contract.address,
+    )
+}
+
 fn convert_to_shares(
     deps: Deps<InjectiveQueryWrapper>,
-    env: Env,
     amount: FPDecimal,
-    decimal: u8,
+    nav: FPDecimal,
 ) -> StdResult<FPDecimal> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
-    let total_share =
-        FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?).scaled(-12);
+    let total_share = FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?)
+        .scaled(-(contract_info.lp_decimal as i32));
     let share = if total_share.is_zero() {
         amount
+    } else if nav.is_zero() {
+        return Err(StdError::generic_err(
+            "Vault NAV is zero while LP tokens are outstanding",
+        ));
     } else {
-        let balance = FPDecimal::from(
-            query_balance(
-                &deps.querier,
-                env.contract.address.to_string(),
-                contract_info.quote_denom,
-            )? - FEE_COLLECTED.load(deps.storage)?,
-        )
-        .scaled(-(decimal as i32));
-        total_share * amount / balance
+        total_share * amount / nav
     };
 
     Ok(share)
 }
 
+/// Returns the vault net asset value in quote units: the bank balance without fees, the
+/// exchange subaccount deposit and the equity of the open position at mark price, including
+/// the funding recorded by the last `SettleFunding`. The exchange reports margin, prices and
+/// funding in the same quote units as the subaccount deposit.
+fn get_nav(deps: Deps<InjectiveQueryWrapper>, env: &Env) -> StdResult<Uint128> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let balance = FPDecimal::from(get_free_balance(deps, env, &contract_info)?);
+    let subaccount_deposit = querier
+        .query_subaccount_deposit(
+            &contract_info.contract_subaccount_id,
+            &contract_info.quote_denom,
+        )?
+        .deposits
+        .total_balance;
+    let position_equity = match get_open_position(deps, &contract_info)? {
+        Some((position, mark_price)) => {
+            let pnl = if position.isLong {
+                position.quantity * (mark_price - position.entry_price)
+            } else {
+                position.quantity * (position.entry_price - mark_price)
+            };
+            position.margin + pnl + get_recorded_funding(deps, &position)?
+        }
+        None => FPDecimal::zero(),
+    };
+
+    let nav = balance + subaccount_deposit + position_equity;
+    if nav.is_negative() {
+        return Ok(Uint128::zero());
+    }
+
+    Ok(Uint128::new(u128::from(nav)))
+}
+
+/// Computes the initial margin required by the market for an order of `quantity` at `price`.
+fn get_required_margin(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    quantity: FPDecimal,
+    price: FPDecimal,
+) -> StdResult<FPDecimal> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let market = querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .and_then(|full_market| full_market.market)
+        .ok_or_else(|| StdError::generic_err("Market not found"))?;
+
+    Ok(price * quantity * market.initial_margin_ratio)
+}
+
+/// Returns the open vault position with the market mark price, `None` when the vault is flat.
+fn get_open_position(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<Option<(Position, FPDecimal)>> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let position = querier
+        .query_vanilla_subaccount_position(
+            &contract_info.market_id,
+            &contract_info.contract_subaccount_id,
+        )?
+        .state;
+    let position = match position {
+        Some(position) if !position.quantity.is_zero() => position,
+        _ => return Ok(None),
+    };
+
+    let mark_price = querier
+        .query_derivative_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| StdError::generic_err("Market not found"))?
+        .mark_price;
+
+    Ok(Some((position, mark_price)))
+}
+
+/// Returns `(amount, shares)` pairs for `steps + 1` evenly spaced quote amounts between `min` and `max`.
+fn query_share_curve(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    min: Uint128,
+    max: Uint128,
+    steps: u32,
+) -> StdResult<Vec<(Uint128, Uint128)>> {
+    if min > max {
+        return Err(StdError::generic_err("min must not exceed max"));
+    }
+    if steps == 0 || steps > MAX_SHARE_CURVE_STEPS {
+        return Err(StdError::generic_err(format!(
+            "steps must be between 1 and {MAX_SHARE_CURVE_STEPS}"
+        )));
+    }
+
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let nav = FPDecimal::from(get_nav(deps, &env)?).scaled(-(contract_info.quote_decimal as i32));
+    (0..=steps)
+        .map(|step| {
+            let amount = min + (max - min).multiply_ratio(step, steps);
+            let scaled_amount =
+                FPDecimal::from(amount).scaled(-(contract_info.quote_decimal as i32));
+            let share = convert_to_shares(deps, scaled_amount, nav)?;
+            Ok((
+                amount,
+                Uint128::new(u128::from(share.scaled(contract_info.lp_decimal as i32))),
+            ))
+        })
+        .collect()
+}
+
+/// Returns `(share, assets)` pairs for `steps + 1` evenly spaced LP amounts between `min` and
+/// `max`, the assets being what burning the share pays as in [`query_simulate_withdraw`].
+fn query_withdraw_curve(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    min: Uint128,
+    max: Uint128,
+    steps: u32,
+) -> StdResult<Vec<(Uint128, Vec<Asset>)>> {
+    if min > max {
+        return Err(StdError::generic_err("min must not exceed max"));
+    }
+    if steps == 0 || steps > MAX_WITHDRAW_CURVE_STEPS {
+        return Err(StdError::generic_err(format!(
+            "steps must be between 1 and {MAX_WITHDRAW_CURVE_STEPS}"
+        )));
+    }
+
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if total_share.is_zero() || max > total_share {
+        return Err(StdError::generic_err(format!(
+            "max must not exceed total supply {total_share}"
+        )));
+    }
+
+    (0..=steps)
+        .map(|step| {
+            let share = min + (max - min).multiply_ratio(step, steps);
+            let assets = get_share_in_assets(deps, env.clone(), share, total_share)?;
+            Ok((share, assets.to_vec()))
+        })
+        .collect()
+}
+
+/// Returns the assets, including the INJ fee asset, burning `share` LP tokens pays.
+fn query_simulate_withdraw(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    share: Uint128,
+) -> StdResult<[Asset; 2]> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if share.is_zero() || share > total_share {
+        return Err(StdError::generic_err(format!(
+            "Share must be between 1 and total supply {total_share}"
+        )));
+    }
+
+    get_share_in_assets(deps, env, share, total_share)
+}
+
 fn get_share_in_assets(
     deps: Deps<InjectiveQueryWrapper>,
     env: Env,
@@ -663,12 +1676,9 @@ fn get_share_in_assets(
     total_share: Uint128,
 ) -> StdResult<[Asset; 2]> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let balance = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.quote_denom,
-    )? - FEE_COLLECTED.load(deps.storage)?;
-    let refund_amount = balance * share / total_share;
+    let balance = get_free_balance(deps, &env, &contract_info)?;
+    // Only the bank balance can be paid out, the rest of the NAV is on the exchange
+    let refund_amount = min(get_share_value(deps, &env, share, total_share)?, balance);
     let mut fee_amount = Uint128::zero();
     let fee_denom = "INJ".to_string();
     if contract_info.quote_denom != fee_denom {
@@ -679,13 +1689,13 @@ fn get_share_in_assets(
     Ok([
         Asset {
             amount: refund_amount,
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: contract_info.quote_denom.clone(),
             },
         },
         Asset {
             amount: fee_amount,
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: fee_denom.clone(),
             },
         },
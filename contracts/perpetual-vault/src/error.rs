@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -21,12 +21,44 @@ pub enum ContractError {
     #[error("ExceedHardcap")]
     ExceedHardcap {},
 
+    #[error("Insufficient fee accrued")]
+    InsufficientFeeAccrued {},
+
+    #[error("InvalidDenom: {denom}")]
+    InvalidDenom { denom: String },
+
     #[error("InvalidToken")]
     InvalidToken {},
 
     #[error("InvalidZeroAmount")]
     InvalidZeroAmount {},
 
+    #[error("Market with id: {market_id} not active")]
+    MarketNotActive { market_id: String },
+
+    #[error("Market with id: {market_id} not found")]
+    MarketNotFound { market_id: String },
+
+    #[error("No free balance to refund")]
+    NoFreeBalance {},
+
+    #[error("Paused")]
+    Paused {},
+
+    #[error(
+        "SharePriceExceeded: share price {share_price} above max_share_price {max_share_price}"
+    )]
+    SharePriceExceeded {
+        share_price: Uint128,
+        max_share_price: Uint128,
+    },
+
+    #[error("SlippageExceeded: share {share} below min_lp_out {min_lp_out}")]
+    SlippageExceeded { share: Uint128, min_lp_out: Uint128 },
+
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Zero share amount")]
+    ZeroShare {},
 }
@@ -16,6 +16,20 @@ pub struct InstantiateMsg {
     pub quote_decimal: u8,
     pub hardcap: Uint128,
     pub token_code_id: u64,
+    /// Allows pre-deploying against a market that isn't active yet, swaps stay blocked until it is
+    #[serde(default)]
+    pub allow_inactive_market: bool,
+    /// Decimals of the LP token, defaults to 12
+    pub lp_decimal: Option<u8>,
+    /// Name of the LP token, derived from the denoms when not set
+    pub lp_token_name: Option<String>,
+    /// Symbol of the LP token, derived from the market ticker when not set
+    pub lp_token_symbol: Option<String>,
+    /// Reuse an existing CW20 as the LP token instead of instantiating one, the vault must be
+    /// its minter
+    pub existing_lp_token: Option<String>,
+    /// Maximum `price * quantity / margin` accepted for swaps, defaults to 10
+    pub max_leverage: Option<FPDecimal>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -30,6 +44,11 @@ pub enum ExecuteMsg {
         assets: Vec<Asset>,
         /// The receiver of LP tokens
         receiver: Option<String>,
+        /// The minimum LP tokens to receive, guarding against price moves before execution
+        min_lp_out: Option<Uint128>,
+        /// Maximum NAV per LP token in quote units, guarding against depositing right
+        /// after a NAV jump
+        max_share_price: Option<Uint128>,
     },
     /// SwapPerpetual
     SwapPerpetual {
@@ -38,10 +57,19 @@ pub enum ExecuteMsg {
         price: FPDecimal,
         margin: FPDecimal,
     },
+    /// SwapPerpetual with the margin derived from `price * quantity / leverage`
+    SwapPerpetualLeverage {
+        long: bool,
+        quantity: FPDecimal,
+        price: FPDecimal,
+        leverage: FPDecimal,
+    },
     /// Cancel placed order
     CancelOrder {
         order_hash: String,
     },
+    /// Cancel every order of the vault subaccount on the vault market
+    CancelAllOrders {},
     /// Add fee
     AddFee {
         fee: Uint128,
@@ -50,16 +78,126 @@ pub enum ExecuteMsg {
     WithdrawFee {
         fee: Uint128,
     },
+    /// Send the whole balance of a denom that is not a vault asset to `recipient`
+    SweepToken {
+        denom: String,
+        recipient: String,
+    },
+    /// Update the minimum `price * quantity` accepted for swaps
+    UpdateMinOrderNotional {
+        min_order_notional: FPDecimal,
+    },
+    /// Keep fees added from now on in the NAV, compounding them for LPs
+    ToggleFeeReinvest {
+        enabled: bool,
+    },
+    /// Halt deposits and swaps, withdrawals stay open
+    SetPaused {
+        paused: bool,
+    },
+    /// Update the maximum LP token supply
+    UpdateHardcap {
+        hardcap: Uint128,
+    },
+    /// Update the maximum `price * quantity / margin` accepted for swaps
+    UpdateMaxLeverage {
+        max_leverage: FPDecimal,
+    },
+    /// Record the funding accrued on the open position, which the NAV includes until the
+    /// position changes
+    SettleFunding {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Ownership {},
-    TokensForShares { share: Uint128 },
+    TokensForShares {
+        share: Uint128,
+    },
     TotalLiquidity {},
-    UserLiquidity { user: String },
+    UserLiquidity {
+        user: String,
+    },
     Tokens {},
+    LockedLiquidity {},
+    UserPnl {
+        user: String,
+    },
+    SwapHistory {},
+    /// Fill details of the most recently filled swap, `None` before the first fill
+    LastSwapResult {},
+    OwnerFeeClaimable {},
+    PositionValueQuote {},
+    /// Whether the vault holds a non-zero position
+    HasExposure {},
+    /// Quote fee collected so far, withdrawable by the owner with `WithdrawFee`
+    FeeState {},
+    ShareCurve {
+        min: Uint128,
+        max: Uint128,
+        steps: u32,
+    },
+    RequiredMargin {
+        quantity: FPDecimal,
+        price: FPDecimal,
+    },
+    FillStats {},
+    SimulateWithdraw {
+        share: Uint128,
+    },
+    /// Withdrawal proceeds for `steps + 1` evenly spaced LP amounts between `min` and `max`
+    WithdrawCurve {
+        min: Uint128,
+        max: Uint128,
+        steps: u32,
+    },
+    InstantiateParams {},
+    /// The stored vault configuration
+    Config {},
+    RiskParams {},
+    /// When the market pays funding next, to time `SettleFunding`
+    NextFunding {},
+    /// The derivative market the vault trades on, with its mark price and perpetual info
+    MarketInfo {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UserPnlResponse {
+    /// Recorded quote amount deposited by the user
+    pub deposited: Uint128,
+    /// Current quote value of the user's LP tokens
+    pub value: Uint128,
+    /// Difference between `value` and `deposited`
+    pub pnl: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RiskParamsResponse {
+    pub min_order_notional: FPDecimal,
+    pub max_leverage: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeStateResponse {
+    pub fee_collected: Asset,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NextFundingResponse {
+    pub next_funding_timestamp: i64,
+    /// Seconds until `next_funding_timestamp`, zero once it has passed
+    pub seconds_remaining: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FillStatsResponse {
+    /// Sum of `price * quantity` over the recorded fills
+    pub total_notional: FPDecimal,
+    /// Number of recorded fills
+    pub swap_count: u64,
+    /// Quantity weighted average fill price
+    pub average_price: FPDecimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -1,9 +1,13 @@
 use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use injective_cosmwasm::{MarketId, SubaccountId};
+use injective_math::FPDecimal;
+
+use crate::contract::{DEFAULT_LP_DECIMAL, DEFAULT_MAX_LEVERAGE};
+use crate::msg::InstantiateMsg;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ContractInfo {
@@ -11,10 +15,85 @@ pub struct ContractInfo {
     pub quote_denom: String,
     pub quote_decimal: u8,
     pub hardcap: Uint128,
+    /// LP token decimals, vaults deployed before this was configurable use 12
+    #[serde(default = "default_lp_decimal")]
+    pub lp_decimal: u8,
     pub liquidity_token: Addr,
     pub contract_subaccount_id: SubaccountId,
+    #[serde(default)]
+    pub allow_inactive_market: bool,
+    #[serde(default = "default_min_order_notional")]
+    pub min_order_notional: FPDecimal,
+    /// Deposits and swaps are halted while set
+    #[serde(default)]
+    pub paused: bool,
+    /// Vaults deployed before the leverage limit load with the default of 10
+    #[serde(default = "default_max_leverage")]
+    pub max_leverage: FPDecimal,
+    /// Fees added while set stay in the NAV instead of being reserved for the owner
+    #[serde(default)]
+    pub fee_reinvest: bool,
+}
+
+pub fn default_lp_decimal() -> u8 {
+    DEFAULT_LP_DECIMAL
+}
+
+pub fn default_min_order_notional() -> FPDecimal {
+    FPDecimal::zero()
+}
+
+pub fn default_max_leverage() -> FPDecimal {
+    FPDecimal::from(DEFAULT_MAX_LEVERAGE)
 }
 
 pub const CONTRACT_INFO: Item<ContractInfo> = Item::new("vault");
 
+/// The message the vault was instantiated with
+pub const INSTANTIATE_PARAMS: Item<InstantiateMsg> = Item::new("instantiate_params");
+
 pub const FEE_COLLECTED: Item<Uint128> = Item::new("fee_collected");
+
+/// Cumulative quote amount deposited by each LP holder, reduced pro rata on withdrawal
+pub const USER_DEPOSITS: Map<&Addr, Uint128> = Map::new("user_deposits");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapRecord {
+    pub order_hash: String,
+    pub long: bool,
+    pub quantity: FPDecimal,
+    pub price: FPDecimal,
+    pub fee: FPDecimal,
+    pub height: u64,
+}
+
+/// Fill details parsed from a market order reply
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapResult {
+    pub order_hash: String,
+    pub quantity: FPDecimal,
+    pub price: FPDecimal,
+    pub fee: FPDecimal,
+}
+
+/// Result of the most recently filled swap
+pub const LAST_SWAP_RESULT: Item<SwapResult> = Item::new("last_swap_result");
+
+/// Side of the market order awaiting its reply
+pub const PENDING_SWAP_LONG: Item<bool> = Item::new("pending_swap_long");
+
+/// Most recent fills, oldest first
+pub const SWAP_HISTORY: Item<Vec<SwapRecord>> = Item::new("swap_history");
+
+/// Funding recorded by `SettleFunding` for the open position
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FundingRecord {
+    /// Cumulative funding entry of the settled position
+    pub cumulative_funding_entry: FPDecimal,
+    /// Funding accrued since entry in quote units, negative when paid
+    pub funding: FPDecimal,
+}
+
+/// Funding at the last settlement. The NAV applies it only while the settled position is open,
+/// the exchange moves the funding into the margin once the position changes.
+pub const CUMULATIVE_FUNDING: Item<FundingRecord> = Item::new("cumulative_funding");
@@ -9,7 +9,12 @@ use std::panic;
 
 use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
 
-use injective_cosmwasm::{HandlesMarketIdQuery, InjectiveQuery, InjectiveQueryWrapper};
+use injective_cosmwasm::{
+    Deposit, HandlesMarketAndSubaccountQuery, HandlesMarketIdQuery, HandlesSubaccountAndDenomQuery,
+    InjectiveQuery, InjectiveQueryWrapper, SubaccountDepositResponse,
+    SubaccountPositionInMarketResponse,
+};
+use injective_math::FPDecimal;
 
 const TEST_CONTRACT_ADDR: &str = "inj14hj2tavq8fpesdwxxcu44rty3hh90vhujaxlnz";
 
@@ -37,6 +42,8 @@ where
 
 pub struct WasmMockQuerier {
     pub perpetual_market_response_handler: Option<Box<dyn HandlesMarketIdQuery>>,
+    pub subaccount_position_response_handler: Option<Box<dyn HandlesMarketAndSubaccountQuery>>,
+    pub subaccount_deposit_response_handler: Option<Box<dyn HandlesSubaccountAndDenomQuery>>,
     base: MockQuerier<InjectiveQueryWrapper>,
     token_querier: TokenQuerier,
 }
@@ -177,6 +184,31 @@ impl WasmMockQuerier {
                         None => panic!("SpotMarketHandler not set"),
                     }
                 }
+                InjectiveQuery::SubaccountPositionInMarket {
+                    market_id,
+                    subaccount_id,
+                } => match &self.subaccount_position_response_handler {
+                    Some(handler) => handler.handle(market_id, subaccount_id),
+                    // The vault is flat unless a position is mocked
+                    None => SystemResult::Ok(ContractResult::from(to_binary(
+                        &SubaccountPositionInMarketResponse { state: None },
+                    ))),
+                },
+                InjectiveQuery::SubaccountDeposit {
+                    subaccount_id,
+                    denom,
+                } => match &self.subaccount_deposit_response_handler {
+                    Some(handler) => handler.handle(subaccount_id, denom),
+                    // Nothing is deposited on the exchange unless mocked
+                    None => SystemResult::Ok(ContractResult::from(to_binary(
+                        &SubaccountDepositResponse {
+                            deposits: Deposit {
+                                available_balance: FPDecimal::zero(),
+                                total_balance: FPDecimal::zero(),
+                            },
+                        },
+                    ))),
+                },
                 _ => panic!("Unknown query"),
             },
             _ => self.base.handle_query(request),
@@ -188,6 +220,8 @@ impl WasmMockQuerier {
     pub fn new(base: MockQuerier<InjectiveQueryWrapper>) -> Self {
         WasmMockQuerier {
             perpetual_market_response_handler: None,
+            subaccount_position_response_handler: None,
+            subaccount_deposit_response_handler: None,
             base,
             token_querier: TokenQuerier::default(),
         }
@@ -2,34 +2,47 @@ use std::str::FromStr;
 
 use cosmwasm_std::testing::{mock_info, MockApi, MockStorage};
 use cosmwasm_std::{
-    attr, to_binary, BankMsg, Binary, Coin, ContractResult, DepsMut, OwnedDeps, QuerierResult,
-    Reply, ReplyOn, StdError, SubMsg, SubMsgResponse, SubMsgResult, SystemResult, Uint128, WasmMsg,
+    attr, from_binary, from_slice, to_binary, Addr, BankMsg, Binary, Coin, ContractResult,
+    CosmosMsg, Deps, DepsMut, OwnedDeps, QuerierResult, Reply, ReplyOn, StdError, SubMsg,
+    SubMsgResponse, SubMsgResult, SystemResult, Timestamp, Uint128, WasmMsg,
 };
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
 use cw20_base::msg::InstantiateMsg as TokenInstantiateMsg;
-use injective_cosmwasm::InjectiveMsg::CreateDerivativeMarketOrder;
+use injective_cosmwasm::InjectiveMsg::{self, CreateDerivativeMarketOrder};
 use injective_cosmwasm::{
-    inj_mock_env, DerivativeMarket, DerivativeMarketResponse, DerivativeOrder,
-    FullDerivativeMarket, FullDerivativeMarketPerpetualInfo, HandlesMarketIdQuery,
+    get_default_subaccount_id_for_checked_address, inj_mock_env, DerivativeMarket,
+    DerivativeMarketResponse, DerivativeOrder, FullDerivativeMarket,
+    FullDerivativeMarketPerpetualInfo, HandlesMarketAndSubaccountQuery, HandlesMarketIdQuery,
     InjectiveQueryWrapper, InjectiveRoute, MarketId, MarketStatus, OracleType, OrderInfo,
-    OrderType, PerpetualMarketFunding, PerpetualMarketInfo, PerpetualMarketState, SubaccountId,
+    OrderType, PerpetualMarketFunding, PerpetualMarketInfo, PerpetualMarketState, Position,
+    SubaccountId, SubaccountPositionInMarketResponse,
 };
 use injective_math::FPDecimal;
+use injective_protobuf::proto::tx;
 use protobuf::Message;
 
-use crate::asset::{Asset, AssetInfo};
-use crate::contract::{execute, instantiate, reply, ORDER_REPLY_ID};
+use crate::asset::{format_lp_token_name, Asset, AssetInfo};
+use crate::contract::{execute, instantiate, query, reply, ORDER_REPLY_ID};
 use crate::error::ContractError;
-use crate::helpers::{get_message_data, i32_to_dec};
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg};
+use crate::helpers::{cancel_order_msg, get_message_data, i32_to_dec, MarketType, ORDER_MASK_ANY};
+use crate::msg::{
+    Cw20HookMsg, ExecuteMsg, FeeStateResponse, FillStatsResponse, InstantiateMsg,
+    NextFundingResponse, QueryMsg, RiskParamsResponse, UserPnlResponse,
+};
 use crate::response::MsgInstantiateContractResponse;
-use crate::state::CONTRACT_INFO;
+use crate::state::{
+    ContractInfo, FundingRecord, SwapRecord, SwapResult, CONTRACT_INFO, CUMULATIVE_FUNDING,
+    FEE_COLLECTED, PENDING_SWAP_LONG,
+};
 use crate::test::mock_querier::{mock_dependencies, WasmMockQuerier};
 
 const TEST_CONTRACT_ADDR: &str = "inj14hj2tavq8fpesdwxxcu44rty3hh90vhujaxlnz";
 
 const TEST_MARKET_ID: &str = "0x78c2d3af98c517b164070a739681d4bd4d293101e7ffc3a30968945329b47ec6";
 
+const TEST_INACTIVE_MARKET_ID: &str =
+    "0x1c79dac019f73e4060494ab1b4fcba734350656d6fc4d474f6a238c13c6f9ced";
+
 fn test_deps<'a>() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier, InjectiveQueryWrapper> {
     mock_dependencies(&[], |querier| {
         querier.perpetual_market_response_handler =
@@ -58,6 +71,38 @@ fn store_liquidity_token(deps: DepsMut<InjectiveQueryWrapper>, msg_id: u64, cont
     let _res = reply(deps, inj_mock_env(), reply_msg.clone()).expect("failed to reply");
 }
 
+fn test_instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        quote_decimal: 6,
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: None,
+    }
+}
+
+/// Instantiates the vault as `addr0000` with `overrides` applied and stores its LP token.
+fn setup_vault(
+    mut deps: DepsMut<InjectiveQueryWrapper>,
+    overrides: impl FnOnce(&mut InstantiateMsg),
+) {
+    let mut msg = test_instantiate_msg();
+    overrides(&mut msg);
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        instantiate(deps.branch(), inj_mock_env(), info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps, 1, "liquidity0000".to_string());
+}
+
 #[test]
 fn proper_initialization() {
     let mut deps = test_deps();
@@ -83,6 +128,12 @@ fn proper_initialization() {
         quote_decimal: 6,
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: Some(i32_to_dec(10)),
     };
 
     let env = inj_mock_env();
@@ -90,8 +141,8 @@ fn proper_initialization() {
     let res = instantiate(deps.as_mut(), env, info, msg.clone()).unwrap_err();
     assert_eq!(
         res,
-        ContractError::CustomError {
-            val: format!("Market with id: {} not found", msg.market_id.as_str()),
+        ContractError::MarketNotFound {
+            market_id: msg.market_id.as_str().to_string(),
         }
     );
 
@@ -102,6 +153,12 @@ fn proper_initialization() {
         quote_decimal: 6,
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: Some(i32_to_dec(10)),
     };
 
     let env = inj_mock_env();
@@ -114,7 +171,7 @@ fn proper_initialization() {
                 code_id: 10u64,
                 msg: to_binary(&TokenInstantiateMsg {
                     name: "USDT-LP".to_string(),
-                    symbol: "uLP".to_string(),
+                    symbol: "IULP".to_string(),
                     decimals: 12,
                     initial_balances: vec![],
                     mint: Some(MinterResponse {
@@ -146,6 +203,72 @@ fn proper_initialization() {
     assert_eq!("liquidity0000".to_string(), contract_info.liquidity_token);
 }
 
+#[test]
+fn instantiate_inactive_market() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    // Fail to initialize when market is not active
+    let msg = InstantiateMsg {
+        market_id: MarketId::new(TEST_INACTIVE_MARKET_ID.to_string())
+            .expect("failed to create market_id"),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::MarketNotActive {
+            market_id: msg.market_id.as_str().to_string(),
+        }
+    );
+
+    // Initialize with inactive market allowed
+    let msg = InstantiateMsg {
+        allow_inactive_market: true,
+        ..msg
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg.clone()).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert!(contract_info.allow_inactive_market);
+
+    // Fail to swap while market is not active
+    let msg_swap = ExecuteMsg::SwapPerpetual {
+        long: true,
+        quantity: i32_to_dec(8),
+        price: i32_to_dec(10),
+        margin: i32_to_dec(3),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg_swap).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::MarketNotActive {
+            market_id: msg.market_id.as_str().to_string(),
+        }
+    );
+}
+
 #[test]
 fn deposit() {
     let mut deps = test_deps();
@@ -167,6 +290,12 @@ fn deposit() {
         quote_decimal: 6,
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: Some(i32_to_dec(10)),
     };
 
     let env = inj_mock_env();
@@ -180,19 +309,21 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDC".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -203,15 +334,40 @@ fn deposit() {
         StdError::generic_err("assets must contain exactly one element").into()
     );
 
+    // Fail to deposit when the denom is malformed
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "US DT".to_string(),
+            },
+            amount: Uint128::from(100_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::InvalidDenom {
+            denom: "US DT".to_string()
+        }
+    );
+
     // Fail to deposit when wrong assets provided
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDC".to_string(),
             },
             amount: Uint128::from(100_000000u128),
         }],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -222,15 +378,46 @@ fn deposit() {
         StdError::generic_err("Asset USDC is not in the pool").into()
     );
 
+    // Fail to deposit when denom case differs from quote denom
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "usdt".to_string(),
+            },
+            amount: Uint128::from(100_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "usdt".to_string(),
+            amount: Uint128::from(100_000000u128),
+        }],
+    );
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Denom usdt does not match quote denom USDT".to_string(),
+        }
+    );
+
     // Fail to deposit when assets amount mismatch
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDT".to_string(),
             },
             amount: Uint128::from(120_000000u128),
         }],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -253,12 +440,14 @@ fn deposit() {
     // Fail to deposit when extra asset is provided
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDT".to_string(),
             },
             amount: Uint128::from(100_000000u128),
         }],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -282,15 +471,47 @@ fn deposit() {
             .into()
     );
 
+    // Fail to deposit when the share is below the minimum
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(100_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: Some(Uint128::from(100_000000000001u128)),
+        max_share_price: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(100_000000u128),
+        }],
+    );
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::SlippageExceeded {
+            share: Uint128::from(100_000000000000u128),
+            min_lp_out: Uint128::from(100_000000000001u128),
+        }
+    );
+
     // Deposit
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDT".to_string(),
             },
             amount: Uint128::from(100_000000u128),
         }],
         receiver: None,
+        min_lp_out: Some(Uint128::from(100_000000000000u128)),
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -325,15 +546,22 @@ fn deposit() {
     // Fail to deposit 0 amounts
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDT".to_string(),
             },
             amount: Uint128::zero(),
         }],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::InvalidZeroAmount {});
+
+    // Fail to deposit with a zero amount coin attached
     let info = mock_info(
         "addr0001",
         &[Coin {
@@ -342,17 +570,22 @@ fn deposit() {
         }],
     );
     let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::InvalidZeroAmount {});
+    assert_eq!(
+        res,
+        StdError::generic_err("Supplied coins contain USDT with zero amount").into()
+    );
 
     // Fail to deposit more than hardcap
     let msg = ExecuteMsg::Deposit {
         assets: vec![Asset {
-            info: AssetInfo {
+            info: AssetInfo::NativeToken {
                 denom: "USDT".to_string(),
             },
             amount: Uint128::from(10000_000000u128),
         }],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -368,82 +601,1751 @@ fn deposit() {
 }
 
 #[test]
-fn withdraw_n_fee() {
+fn user_pnl() {
     let mut deps = test_deps();
 
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // No deposits recorded
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::UserPnl {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query user pnl");
+    let pnl: UserPnlResponse = from_binary(&res).expect("failed to parse user pnl");
+    assert_eq!(
+        pnl,
+        UserPnlResponse {
+            deposited: Uint128::zero(),
+            value: Uint128::zero(),
+            pnl: FPDecimal::zero(),
+        }
+    );
+
+    // Deposit
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(100_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(100_000000u128),
+        }],
+    );
+    let _res = execute(deps.as_mut(), env, info, msg).expect("failed to deposit");
+
+    // Vault NAV increases
     deps.querier.with_token_balances(&[(
         &"liquidity0000".to_string(),
         &[(
             &String::from("addr0001"),
-            &Uint128::new(200_000000000000u128),
+            &Uint128::new(100_000000000000u128),
         )],
     )]);
     deps.querier.with_balance(&[(
         &String::from(TEST_CONTRACT_ADDR),
         &[Coin {
             denom: "USDT".to_string(),
-            amount: Uint128::from(200_000000u128),
+            amount: Uint128::from(120_000000u128),
         }],
     )]);
 
-    let msg = InstantiateMsg {
-        owner: "addr0000".to_string(),
-        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
-        quote_decimal: 6,
-        hardcap: Uint128::new(5000_000000000000u128),
-        token_code_id: 10u64,
-    };
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::UserPnl {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query user pnl");
+    let pnl: UserPnlResponse = from_binary(&res).expect("failed to parse user pnl");
+    assert_eq!(
+        pnl,
+        UserPnlResponse {
+            deposited: Uint128::from(100_000000u128),
+            value: Uint128::from(120_000000u128),
+            pnl: FPDecimal::from(20_000000u128),
+        }
+    );
 
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    // The open position equity of 40 USDT net of the settled funding is part of the value
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+    let keeper = mock_info("keeper0000", &[]);
+    execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        keeper,
+        ExecuteMsg::SettleFunding {},
+    )
+    .expect("failed to settle funding");
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::UserPnl {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query user pnl");
+    let pnl: UserPnlResponse = from_binary(&res).expect("failed to parse user pnl");
+    assert_eq!(
+        pnl,
+        UserPnlResponse {
+            deposited: Uint128::from(100_000000u128),
+            value: Uint128::from(160_000000u128),
+            pnl: FPDecimal::from(60_000000u128),
+        }
+    );
+}
 
-    // Store liquidity token
-    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+#[test]
+fn max_share_price() {
+    let mut deps = test_deps();
 
-    // Fail to withdraw when wrong liquidity is provided
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: String::from("addr0001"),
-        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
-        amount: Uint128::new(90_000000000000u128),
-    });
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(100_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(120_000000u128),
+        }],
+    )]);
 
-    let env = inj_mock_env();
-    let info = mock_info("liquidity0001", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::Unauthorized {});
+    setup_vault(deps.as_mut(), |_| {});
 
-    // Fail to set fee as non owner
-    let msg = ExecuteMsg::AddFee {
-        fee: Uint128::from(10_000000u128),
+    let deposit = |max_share_price: Option<Uint128>| ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(12_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price,
     };
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(12_000000u128),
+        }],
+    );
 
-    let env = inj_mock_env();
-    let info = mock_info("addr0001", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::Unauthorized {});
+    // Fail to deposit when the share price is above max_share_price
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info.clone(),
+        deposit(Some(Uint128::from(1_199999u128))),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::SharePriceExceeded {
+            share_price: Uint128::from(1_200000u128),
+            max_share_price: Uint128::from(1_199999u128),
+        }
+    );
 
-    // Set fee as owner
-    let msg = ExecuteMsg::AddFee {
-        fee: Uint128::from(10_000000u128),
-    };
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info.clone(),
+        deposit(Some(Uint128::from(1_200000u128))),
+    )
+    .expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "10000000000000"));
 
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+    execute(deps.as_mut(), inj_mock_env(), info, deposit(None)).expect("failed to deposit");
+}
 
-    // Withdraw
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: String::from("addr0001"),
-        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
-        amount: Uint128::new(90_000000000000u128),
-    });
+#[test]
+fn share_curve() {
+    let mut deps = test_deps();
 
-    let env = inj_mock_env();
-    let info = mock_info("liquidity0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
-    let log_withdrawn_share = res.attributes.get(2).expect("no log");
-    let log_refund_assets = res.attributes.get(3).expect("no log");
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(100_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(120_000000u128),
+        }],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to query too many steps
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::ShareCurve {
+            min: Uint128::from(10_000000u128),
+            max: Uint128::from(100_000000u128),
+            steps: 1000,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("steps must be between 1 and 100")
+    );
+
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::ShareCurve {
+            min: Uint128::from(10_000000u128),
+            max: Uint128::from(100_000000u128),
+            steps: 3,
+        },
+    )
+    .expect("failed to query share curve");
+    let curve: Vec<(Uint128, Uint128)> = from_binary(&res).expect("failed to parse share curve");
+    assert_eq!(curve.len(), 4);
+    assert_eq!(curve[0].0, Uint128::from(10_000000u128));
+    assert_eq!(curve[3].0, Uint128::from(100_000000u128));
+    assert_eq!(curve[3].1, Uint128::from(83_333333333333u128));
+    for points in curve.windows(2) {
+        assert!(points[0].1 < points[1].1);
+    }
+}
+
+#[test]
+fn withdraw_curve() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(100_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(120_000000u128),
+            },
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(4_000000000000000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to query too many steps
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::WithdrawCurve {
+            min: Uint128::zero(),
+            max: Uint128::from(100_000000000000u128),
+            steps: 1000,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("steps must be between 1 and 100")
+    );
+
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::WithdrawCurve {
+            min: Uint128::from(25_000000000000u128),
+            max: Uint128::from(100_000000000000u128),
+            steps: 3,
+        },
+    )
+    .expect("failed to query withdraw curve");
+    let curve: Vec<(Uint128, Vec<Asset>)> =
+        from_binary(&res).expect("failed to parse withdraw curve");
+    assert_eq!(curve.len(), 4);
+    assert_eq!(curve[0].0, Uint128::from(25_000000000000u128));
+    assert_eq!(curve[0].1[0].amount, Uint128::from(30_000000u128));
+    assert_eq!(curve[3].1[0].amount, Uint128::from(120_000000u128));
+    assert_eq!(
+        curve[3].1[1].amount,
+        Uint128::from(4_000000000000000000u128)
+    );
+    for points in curve.windows(2) {
+        assert!(points[0].0 < points[1].0);
+        assert!(points[0].1[0].amount < points[1].1[0].amount);
+        assert!(points[0].1[1].amount < points[1].1[1].amount);
+    }
+}
+
+#[test]
+fn owner_fee_claimable() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(5_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let msg = ExecuteMsg::AddFee {
+        fee: Uint128::from(10_000000u128),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    // The fee counter exceeds the balance, only the balance is claimable
+    let res = query(deps.as_ref(), env, QueryMsg::OwnerFeeClaimable {})
+        .expect("failed to query owner fee claimable");
+    let claimable: Asset = from_binary(&res).expect("failed to parse claimable fee");
+    assert_eq!(
+        claimable,
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(5_000000u128),
+        }
+    );
+}
+
+#[test]
+fn position_value_quote() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Zero when flat
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::PositionValueQuote {})
+        .expect("failed to query position value");
+    let value: Uint128 = from_binary(&res).expect("failed to parse position value");
+    assert_eq!(value, Uint128::zero());
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::HasExposure {})
+        .expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(!has_exposure);
+
+    // 8 contracts at a mark price of 10 USDT
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::PositionValueQuote {})
+        .expect("failed to query position value");
+    let value: Uint128 = from_binary(&res).expect("failed to parse position value");
+    assert_eq!(value, Uint128::from(80_000000u128));
+    let res =
+        query(deps.as_ref(), env, QueryMsg::HasExposure {}).expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(has_exposure);
+}
+
+#[test]
+fn deposit_with_open_position() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(200_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(200_000000u128),
+        }],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(124_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0002",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(124_000000u128),
+        }],
+    );
+
+    // Without a position the share rate follows the bank balance
+    let res = execute(deps.as_mut(), inj_mock_env(), info.clone(), msg.clone())
+        .expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "124000000000000"));
+
+    // A long of 8 from 9 to a mark price of 10 with a margin of 40 adds 48 USDT to the NAV
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+    let res = execute(deps.as_mut(), inj_mock_env(), info.clone(), msg.clone())
+        .expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "100000000000000"));
+
+    // Less the 8 USDT of funding accrued since entry once it is settled
+    let keeper = mock_info("keeper0000", &[]);
+    execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        keeper,
+        ExecuteMsg::SettleFunding {},
+    )
+    .expect("failed to settle funding");
+    let res = execute(deps.as_mut(), inj_mock_env(), info.clone(), msg.clone())
+        .expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "103333333333333"));
+
+    // Funding settled on a closed position doesn't carry into the NAV once the vault is flat
+    deps.querier.subaccount_position_response_handler = None;
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "124000000000000"));
+}
+
+#[test]
+fn withdraw_with_open_position() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(200_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(200_000000u128),
+        }],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // The position adds 40 USDT to the NAV net of the settled funding, so 200 LP are worth
+    // 240 USDT
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+    let keeper = mock_info("keeper0000", &[]);
+    execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        keeper,
+        ExecuteMsg::SettleFunding {},
+    )
+    .expect("failed to settle funding");
+    let withdraw_msg = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("addr0001"),
+            msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
+            amount: Uint128::new(amount),
+        })
+    };
+
+    // Half the supply redeems at the NAV, the same rate deposits mint at
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info,
+        withdraw_msg(100_000000000000u128),
+    )
+    .expect("failed to withdraw");
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(120_000000u128, "USDT")],
+        })
+    );
+
+    // The whole supply is worth more than the bank balance, the uncovered LP is returned
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info,
+        withdraw_msg(200_000000000000u128),
+    )
+    .expect("failed to withdraw");
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: Uint128::new(166666666666666u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(199_999999u128, "USDT")],
+        })
+    );
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: String::from("addr0001"),
+                amount: Uint128::new(33333333333334u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+}
+
+#[test]
+fn fees_above_bank_balance() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(200_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(10_000000u128),
+        }],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // The margin moved to the exchange leaves less in the bank than the collected fees
+    FEE_COLLECTED
+        .save(deps.as_mut().storage, &Uint128::new(20_000000u128))
+        .expect("failed to save fee collected");
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::TotalLiquidity {})
+        .expect("failed to query total liquidity");
+    let total_liquidity: Uint128 = from_binary(&res).expect("failed to parse total liquidity");
+    assert_eq!(total_liquidity, Uint128::zero());
+
+    // Fail to withdraw without a free balance
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
+        amount: Uint128::new(100_000000000000u128),
+    });
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::NoFreeBalance {});
+
+    // Deposits still mint against the 48 USDT of position equity
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(24_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0002",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(24_000000u128),
+        }],
+    );
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(res.attributes[4], attr("share", "100000000000000"));
+}
+
+#[test]
+fn settle_funding() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Nothing accrues while flat
+    let info = mock_info("keeper0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::SettleFunding {},
+    )
+    .expect("failed to settle funding");
+    assert_eq!(
+        CUMULATIVE_FUNDING
+            .may_load(&deps.storage)
+            .expect("failed to load cumulative funding"),
+        None
+    );
+
+    // A long of 8 entered at a cumulative funding of 0 pays 8 USDT once it reaches 1
+    deps.querier.subaccount_position_response_handler =
+        Some(Box::new(create_subaccount_position_handler(i32_to_dec(8))));
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::SettleFunding {})
+        .expect("failed to settle funding");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "settle_funding"),
+            attr("cumulative_funding", "1000000"),
+            attr("funding", "-8000000"),
+        ]
+    );
+    assert_eq!(
+        CUMULATIVE_FUNDING
+            .load(&deps.storage)
+            .expect("failed to load cumulative funding"),
+        FundingRecord {
+            cumulative_funding_entry: FPDecimal::zero(),
+            funding: i32_to_dec(-8_000000),
+        }
+    );
+}
+
+#[test]
+fn next_funding() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // The mocked market pays funding next at 100000
+    let mut env = env;
+    env.block.time = Timestamp::from_seconds(99_000);
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::NextFunding {})
+        .expect("failed to query next funding");
+    let next_funding: NextFundingResponse =
+        from_binary(&res).expect("failed to parse next funding");
+    assert_eq!(
+        next_funding,
+        NextFundingResponse {
+            next_funding_timestamp: 100000,
+            seconds_remaining: 1000,
+        }
+    );
+
+    // Overdue funding reports no time left
+    env.block.time = Timestamp::from_seconds(100_500);
+    let res =
+        query(deps.as_ref(), env, QueryMsg::NextFunding {}).expect("failed to query next funding");
+    let next_funding: NextFundingResponse =
+        from_binary(&res).expect("failed to parse next funding");
+    assert_eq!(next_funding.seconds_remaining, 0);
+}
+
+#[test]
+fn market_info() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::MarketInfo {})
+        .expect("failed to query market info");
+    let full_market: FullDerivativeMarket = from_binary(&res).expect("failed to parse market info");
+    assert_eq!(full_market.mark_price, i32_to_dec(10_000000));
+    let market = full_market.market.expect("no market");
+    assert_eq!(market.ticker, "INJ/USDT");
+    assert_eq!(
+        market.min_price_tick_size,
+        FPDecimal::from_str("0.000000000000001").expect("failed to parse string")
+    );
+    assert_eq!(
+        market.min_quantity_tick_size,
+        FPDecimal::from_str("0.001").expect("failed to parse string")
+    );
+    let perpetual_info = full_market.info.expect("no perpetual info");
+    assert_eq!(
+        perpetual_info
+            .perpetual_info
+            .market_info
+            .next_funding_timestamp,
+        100000
+    );
+}
+
+#[test]
+fn required_margin() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // 8 contracts at 1000 with an initial margin ratio of 1.5
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::RequiredMargin {
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+        },
+    )
+    .expect("failed to query required margin");
+    let margin: FPDecimal = from_binary(&res).expect("failed to parse required margin");
+    assert_eq!(margin, i32_to_dec(12000));
+}
+
+#[test]
+fn swap_tick_sizes() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let swap = |quantity: &str, price: &str| ExecuteMsg::SwapPerpetual {
+        long: true,
+        quantity: FPDecimal::from_str(quantity).expect("failed to parse string"),
+        price: FPDecimal::from_str(price).expect("failed to parse string"),
+        margin: i32_to_dec(200),
+    };
+    let price_tick = FPDecimal::from_str("0.000000000000001").expect("failed to parse string");
+    let quantity_tick = FPDecimal::from_str("0.001").expect("failed to parse string");
+
+    // Price off the market price tick
+    let price = FPDecimal::from_str("10.0000000000000005").expect("failed to parse string");
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap("8", "10.0000000000000005"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: format!(
+                "Swap: price {price} is not a multiple of min_price_tick_size: {price_tick}"
+            ),
+        }
+    );
+
+    // Quantity off the market quantity tick
+    let quantity = FPDecimal::from_str("8.0005").expect("failed to parse string");
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap("8.0005", "10")).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: format!(
+                "Swap: quantity {quantity} is not a multiple of min_quantity_tick_size: {quantity_tick}"
+            ),
+        }
+    );
+
+    // Aligned price and quantity are placed
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        swap("8.001", "10.000000000000001"),
+    )
+    .expect("failed to place perpetual order");
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn swap_with_leverage() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail to swap with leverage above the initial margin ratio of 1.5 allows
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::SwapPerpetualLeverage {
+        long: true,
+        quantity: i32_to_dec(8),
+        price: i32_to_dec(1000),
+        leverage: FPDecimal::one(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: margin 8000 below required margin: 12000".to_string(),
+        }
+    );
+
+    let msg = ExecuteMsg::SwapPerpetualLeverage {
+        long: true,
+        quantity: i32_to_dec(8),
+        price: i32_to_dec(1000),
+        leverage: FPDecimal::from_str("0.5").expect("failed to parse string"),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to place perpetual order");
+    match &get_message_data(&res.messages, 0).msg_data {
+        CreateDerivativeMarketOrder { order, .. } => {
+            assert_eq!(order.margin, i32_to_dec(16000));
+            assert_eq!(order.order_type, OrderType::Buy);
+        }
+        _ => panic!("No derivative market order found"),
+    }
+}
+
+#[test]
+fn fill_stats() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Zeros without history
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::FillStats {})
+        .expect("failed to query fill stats");
+    let fill_stats: FillStatsResponse = from_binary(&res).expect("failed to parse fill stats");
+    assert_eq!(
+        fill_stats,
+        FillStatsResponse {
+            total_notional: FPDecimal::zero(),
+            swap_count: 0,
+            average_price: FPDecimal::zero(),
+        }
+    );
+
+    // Fill 8 at 1000, then 2 at 1500
+    let first_fill = Binary::from_base64("CkIweGRkNzI5MmY2ODcwMzIwOTc2YTUxYTUwODBiMGQ2NDU5M2NhZjE3OWViM2YxOTNjZWVlZGFiNGVhNWUxNDljZWISQwoTODAwMDAwMDAwMDAwMDAwMDAwMBIWMTAwMDAwMDAwMDAwMDAwMDAwMDAwMBoUMzYwMDAwMDAwMDAwMDAwMDAwMDA=").expect("failed to decode message");
+    let mut order_response: tx::MsgCreateDerivativeMarketOrderResponse =
+        Message::parse_from_bytes(first_fill.as_slice()).expect("failed to parse order response");
+    order_response
+        .mut_results()
+        .set_quantity("2000000000000000000".to_string());
+    order_response
+        .mut_results()
+        .set_price("1500000000000000000000".to_string());
+    let second_fill = Binary::from(
+        order_response
+            .write_to_bytes()
+            .expect("failed to encode order response"),
+    );
+
+    for fill in [first_fill, second_fill] {
+        PENDING_SWAP_LONG
+            .save(deps.as_mut().storage, &true)
+            .expect("failed to save pending swap");
+        let reply_msg = Reply {
+            id: ORDER_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(fill),
+            }),
+        };
+        let _res = reply(deps.as_mut(), env.clone(), reply_msg).expect("failed to reply");
+    }
+
+    let res =
+        query(deps.as_ref(), env, QueryMsg::FillStats {}).expect("failed to query fill stats");
+    let fill_stats: FillStatsResponse = from_binary(&res).expect("failed to parse fill stats");
+    assert_eq!(
+        fill_stats,
+        FillStatsResponse {
+            total_notional: i32_to_dec(11000),
+            swap_count: 2,
+            average_price: i32_to_dec(1100),
+        }
+    );
+}
+
+#[test]
+fn update_hardcap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to update hardcap as non owner
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdateHardcap {
+        hardcap: Uint128::new(200_000000000000u128),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to lower hardcap below current supply
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::UpdateHardcap {
+            hardcap: Uint128::new(100_000000000000u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Hardcap 100000000000000 below current supply: 180000000000000".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to update hardcap");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_hardcap"),
+            attr("hardcap", "200000000000000"),
+        ]
+    );
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.hardcap, Uint128::new(200_000000000000u128));
+}
+
+#[test]
+fn max_leverage() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let msg = InstantiateMsg {
+        max_leverage: Some(FPDecimal::zero()),
+        ..test_instantiate_msg()
+    };
+
+    // Fail to instantiate with a non positive max leverage
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "max_leverage must be positive".to_string()
+        }
+    );
+
+    let msg = InstantiateMsg {
+        max_leverage: Some(i32_to_dec(10)),
+        ..msg
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    // Fail to update max leverage as non owner
+    let max_leverage = i32_to_dec(1) / i32_to_dec(2);
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdateMaxLeverage { max_leverage };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res =
+        execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update max leverage");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_max_leverage"),
+            attr("max_leverage", max_leverage.to_string()),
+        ]
+    );
+
+    let res: RiskParamsResponse = from_binary(
+        &query(deps.as_ref(), env.clone(), QueryMsg::RiskParams {})
+            .expect("failed to query risk params"),
+    )
+    .expect("failed to parse risk params");
+    assert_eq!(
+        res,
+        RiskParamsResponse {
+            min_order_notional: FPDecimal::zero(),
+            max_leverage,
+        }
+    );
+
+    // Fail to swap above the max leverage
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapPerpetual {
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            margin: i32_to_dec(12000),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: format!(
+                "Swap: leverage {} above max_leverage: {max_leverage}",
+                i32_to_dec(8000) / i32_to_dec(12000)
+            )
+        }
+    );
+
+    // Swap at the max leverage
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        ExecuteMsg::SwapPerpetual {
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            margin: i32_to_dec(16000),
+        },
+    )
+    .expect("failed to swap");
+}
+
+#[test]
+fn existing_lp_token() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        existing_lp_token: Some("LPToken0000".to_string()),
+        ..test_instantiate_msg()
+    };
+
+    // Fail to instantiate with an invalid token address
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg.clone()).unwrap_err();
+
+    let msg = InstantiateMsg {
+        existing_lp_token: Some("lptoken0000".to_string()),
+        ..msg
+    };
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("method", "instantiate"),
+            attr("liquidity_token_addr", "lptoken0000")
+        ]
+    );
+
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(
+        contract_info.liquidity_token,
+        Addr::unchecked("lptoken0000")
+    );
+}
+
+#[test]
+fn lp_token_name() {
+    let name = |denom: &str| {
+        format_lp_token_name(&denom.to_string()).expect("failed to format lp token name")
+    };
+    assert_eq!(name("USDT"), "USDT-LP");
+    // Factory denoms sharing a creator prefix used to collide
+    assert_eq!(
+        name("factory/inj1kz7vxlmrnxkrzh7zymwfhvxxkasnsl2w4s8ujs/usdx"),
+        "USDX-LP"
+    );
+    assert_eq!(
+        name("factory/inj1kz7vxlmrnxkrzh7zymwfhvxxkasnsl2w4s8ujs/usdy"),
+        "USDY-LP"
+    );
+}
+
+#[test]
+fn lp_token_metadata() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        lp_token_name: Some("Vault One LP".to_string()),
+        lp_token_symbol: Some("VONE".to_string()),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { msg, .. }) => {
+            let token_msg: TokenInstantiateMsg =
+                from_binary(msg).expect("failed to parse token instantiate msg");
+            assert_eq!(token_msg.name, "Vault One LP");
+            assert_eq!(token_msg.symbol, "VONE");
+            assert_eq!(token_msg.decimals, 12);
+        }
+        _ => panic!("No token instantiate message found"),
+    }
+}
+
+#[test]
+fn pause() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to pause as non owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: true },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: true },
+    )
+    .expect("failed to pause");
+    assert_eq!(
+        res.attributes,
+        vec![attr("action", "set_paused"), attr("paused", "true")]
+    );
+
+    // Fail to deposit while paused
+    let info = mock_info("addr0001", &[Coin::new(9_000000u128, "USDT")]);
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(9_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Paused {});
+
+    // Fail to swap while paused
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapPerpetual {
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            margin: i32_to_dec(12000),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Paused {});
+
+    // Withdrawals still go through
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
+        amount: Uint128::new(18_000000000000u128),
+    });
+    let info = mock_info("liquidity0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
+
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::AddFee {
+        fee: Uint128::from(1_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::WithdrawFee {
+        fee: Uint128::from(1_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env, info, msg).expect("failed to withdraw fee");
+}
+
+#[test]
+fn fee_reinvest() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(120_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let add_fee = ExecuteMsg::AddFee {
+        fee: Uint128::from(10_000000u128),
+    };
+    let total_liquidity = |deps: Deps<InjectiveQueryWrapper>| -> Uint128 {
+        let res = query(deps, inj_mock_env(), QueryMsg::TotalLiquidity {})
+            .expect("failed to query total liquidity");
+        from_binary(&res).expect("failed to parse total liquidity")
+    };
+
+    // Fees are reserved out of the NAV by default
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), env.clone(), info, add_fee.clone()).expect("failed to add fee");
+    assert_eq!(
+        total_liquidity(deps.as_ref()),
+        Uint128::from(110_000000u128)
+    );
+
+    // Fail to toggle from non-owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::ToggleFeeReinvest { enabled: true },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::ToggleFeeReinvest { enabled: true },
+    )
+    .expect("failed to toggle fee reinvest");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "toggle_fee_reinvest"),
+            attr("enabled", "true"),
+        ]
+    );
+
+    // Reinvested fees stay in the NAV
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, add_fee).expect("failed to add fee");
+    assert_eq!(res.attributes, vec![attr("fee_reinvested", "10000000")]);
+    assert_eq!(
+        total_liquidity(deps.as_ref()),
+        Uint128::from(110_000000u128)
+    );
+    assert_eq!(
+        FEE_COLLECTED
+            .load(deps.as_ref().storage)
+            .expect("failed to load fee collected"),
+        Uint128::from(10_000000u128)
+    );
+}
+
+#[test]
+fn config() {
+    let mut deps = test_deps();
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let res =
+        query(deps.as_ref(), env.clone(), QueryMsg::Config {}).expect("failed to query config");
+    let config: ContractInfo = from_binary(&res).expect("failed to parse config");
+    assert_eq!(
+        config,
+        ContractInfo {
+            market_id,
+            quote_denom: "USDT".to_string(),
+            quote_decimal: 6,
+            hardcap: Uint128::new(5000_000000000000u128),
+            lp_decimal: 12,
+            liquidity_token: Addr::unchecked("liquidity0000"),
+            contract_subaccount_id: get_default_subaccount_id_for_checked_address(
+                &env.contract.address
+            ),
+            allow_inactive_market: false,
+            min_order_notional: FPDecimal::zero(),
+            paused: false,
+            fee_reinvest: false,
+            max_leverage: i32_to_dec(10),
+        }
+    );
+
+    // Contract info stored before the risk settings were added loads with their defaults
+    let legacy_info = format!(
+        r#"{{"market_id":"{}","quote_denom":"USDT","quote_decimal":6,"hardcap":"5000000000000000","liquidity_token":"liquidity0000","contract_subaccount_id":"{}"}}"#,
+        config.market_id.as_str(),
+        config.contract_subaccount_id.as_str()
+    );
+    let legacy_info: ContractInfo =
+        from_slice(legacy_info.as_bytes()).expect("failed to parse legacy contract info");
+    assert_eq!(legacy_info, config);
+}
+
+#[test]
+fn zero_decimal() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        quote_decimal: 0,
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Quote decimal must be positive".to_string()
+        }
+    );
+}
+
+#[test]
+fn instantiate_params() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        allow_inactive_market: true,
+        lp_decimal: Some(8),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        instantiate(deps.as_mut(), env.clone(), info, msg.clone()).expect("failed to instantiate");
+
+    let res = query(deps.as_ref(), env, QueryMsg::InstantiateParams {})
+        .expect("failed to query instantiate params");
+    let params: InstantiateMsg = from_binary(&res).expect("failed to parse instantiate params");
+    assert_eq!(params, msg);
+}
+
+#[test]
+fn cw20_asset_msgs() {
+    let asset = Asset {
+        info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("token0000"),
+        },
+        amount: Uint128::new(100u128),
+    };
+    assert!(!asset.info.is_native_token());
+    assert!(!asset.info.equal(&AssetInfo::NativeToken {
+        denom: "token0000".to_string(),
+    }));
+    asset
+        .as_coin()
+        .expect_err("CW20 assets can't be converted to coins");
+
+    let msg: CosmosMsg = asset
+        .clone()
+        .into_msg("addr0001")
+        .expect("failed to build refund message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::new(100u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    let msg: CosmosMsg = asset
+        .into_transfer_from_msg("addr0001", TEST_CONTRACT_ADDR)
+        .expect("failed to build transfer from message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: "addr0001".to_string(),
+                recipient: TEST_CONTRACT_ADDR.to_string(),
+                amount: Uint128::new(100u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    // Native refunds still go through the bank module
+    let msg: CosmosMsg = Asset {
+        info: AssetInfo::NativeToken {
+            denom: "USDT".to_string(),
+        },
+        amount: Uint128::new(100u128),
+    }
+    .into_msg("addr0001")
+    .expect("failed to build refund message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(100u128, "USDT")],
+        })
+    );
+}
+
+#[test]
+fn sweep_token() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+            Coin {
+                denom: "AIRDROP".to_string(),
+                amount: Uint128::from(500_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail to sweep as non owner
+    let msg = ExecuteMsg::SweepToken {
+        denom: "AIRDROP".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to sweep a denom the vault doesn't hold
+    let msg = ExecuteMsg::SweepToken {
+        denom: "ATOM".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: String::from("No ATOM balance to sweep")
+        }
+    );
+
+    let msg = ExecuteMsg::SweepToken {
+        denom: "AIRDROP".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to sweep token");
+    assert_eq!(
+        res.messages,
+        vec![SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0002"),
+                amount: vec![Coin::new(500_000000u128, "AIRDROP")],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }]
+    );
+    assert_eq!(res.attributes[2], attr("swept", "500000000AIRDROP"));
+}
+
+#[test]
+fn sweep_vault_denom() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    for denom in ["USDT", "INJ"] {
+        let msg = ExecuteMsg::SweepToken {
+            denom: denom.to_string(),
+            recipient: "addr0002".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::CustomError {
+                val: format!("Can't sweep vault denom {denom}")
+            }
+        );
+    }
+}
+
+#[test]
+fn withdraw_n_fee() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(200_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(200_000000u128),
+        }],
+    )]);
+
+    let msg = InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        quote_decimal: 6,
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: Some(i32_to_dec(10)),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    // Fail to withdraw when wrong liquidity is provided
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+
+    let env = inj_mock_env();
+    let info = mock_info("liquidity0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to set fee as non owner
+    let msg = ExecuteMsg::AddFee {
+        fee: Uint128::from(10_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Set fee as owner
+    let msg = ExecuteMsg::AddFee {
+        fee: Uint128::from(10_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::FeeState {})
+        .expect("failed to query fee state");
+    let fee_state: FeeStateResponse = from_binary(&res).expect("failed to parse fee state");
+    assert_eq!(
+        fee_state,
+        FeeStateResponse {
+            fee_collected: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(10_000000u128),
+            },
+        }
+    );
+
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::SimulateWithdraw {
+            share: Uint128::new(90_000000000000u128),
+        },
+    )
+    .expect("failed to simulate withdraw");
+    let simulated: [Asset; 2] = from_binary(&res).expect("failed to parse simulation");
+
+    // Withdraw
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+
+    let env = inj_mock_env();
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
+    let log_withdrawn_share = res.attributes.get(2).expect("no log");
+    let log_refund_assets = res.attributes.get(3).expect("no log");
+    assert_eq!(
+        log_refund_assets,
+        &attr("refund_assets", format!("{}", simulated[0]))
+    );
     let msg_burn_liquidity = res.messages.get(0).expect("no message");
     let msg_refund_0 = res.messages.get(1).expect("no message");
     assert_eq!(
@@ -482,6 +2384,11 @@ fn withdraw_n_fee() {
         &attr("withdrawn_share", 90_000000000000u128.to_string())
     );
     assert_eq!(log_refund_assets, &attr("refund_assets", "85500000USDT"));
+    // 85.5 USDT refunded for 90 LP tokens
+    assert_eq!(
+        res.attributes.get(4).expect("no log"),
+        &attr("nav_per_share", "0.95")
+    );
 
     // Fail to withdraw fee as non owner
     let msg = ExecuteMsg::WithdrawFee {
@@ -501,12 +2408,7 @@ fn withdraw_n_fee() {
     let env = inj_mock_env();
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(
-        res,
-        ContractError::CustomError {
-            val: String::from("Insufficient fee accrued")
-        }
-    );
+    assert_eq!(res, ContractError::InsufficientFeeAccrued {});
 
     // Withdraw fee
     let msg = ExecuteMsg::WithdrawFee {
@@ -567,6 +2469,12 @@ fn test_swap() {
         quote_decimal: 6,
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        allow_inactive_market: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        max_leverage: Some(i32_to_dec(10)),
     };
 
     let env = inj_mock_env();
@@ -583,14 +2491,85 @@ fn test_swap() {
         long: true,
         quantity: i32_to_dec(8),
         price: i32_to_dec(1000),
-        margin: i32_to_dec(3),
+        margin: i32_to_dec(12000),
     };
     let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
     assert_eq!(res, ContractError::Unauthorized {});
 
+    // Fail to place order below min order notional
+    let info = mock_info("addr0000", &[]);
+    let msg_min_notional = ExecuteMsg::UpdateMinOrderNotional {
+        min_order_notional: i32_to_dec(10000),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg_min_notional)
+        .expect("failed to update min order notional");
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: notional 8000 below min_order_notional: 10000".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let msg_min_notional = ExecuteMsg::UpdateMinOrderNotional {
+        min_order_notional: i32_to_dec(8000),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg_min_notional)
+        .expect("failed to update min order notional");
+
+    // Fail to place order with zero margin
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapPerpetual {
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            margin: FPDecimal::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: margin must be positive".to_string()
+        }
+    );
+
+    // Fail to place order below the initial margin ratio of 1.5
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapPerpetual {
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            margin: i32_to_dec(3),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: margin 3 below required margin: 12000".to_string()
+        }
+    );
+
+    // The required margin exceeds the notional, so the order is placed with a warning
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), env.clone(), info, msg.clone())
         .expect("failed to place perpetual order");
+    assert_eq!(
+        res.attributes,
+        vec![attr("warning", "margin 12000 exceeds notional 8000")]
+    );
 
     let expected_atomic_order_message = CreateDerivativeMarketOrder {
         sender: env.contract.address.to_owned(),
@@ -606,7 +2585,7 @@ fn test_swap() {
                 price: i32_to_dec(1000),
                 quantity: i32_to_dec(8),
             },
-            margin: i32_to_dec(3),
+            margin: i32_to_dec(12000),
             order_type: OrderType::Buy,
             trigger_price: None,
         },
@@ -648,15 +2627,263 @@ fn test_swap() {
     );
     assert_eq!(attributes[2], &attr("quantity", Uint128::from(8u128)));
     assert_eq!(attributes[3], &attr("price", Uint128::from(1000u128)));
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::SwapHistory {})
+        .expect("failed to query swap history");
+    let swap_history: Vec<SwapRecord> = from_binary(&res).expect("failed to parse swap history");
+    assert_eq!(
+        swap_history,
+        vec![SwapRecord {
+            order_hash: "0xdd7292f6870320976a51a5080b0d64593caf179eb3f193ceeedab4ea5e149ceb"
+                .to_string(),
+            long: true,
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            fee: i32_to_dec(36),
+            height: inj_mock_env().block.height,
+        }]
+    );
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::LastSwapResult {})
+        .expect("failed to query last swap result");
+    let last_swap_result: Option<SwapResult> =
+        from_binary(&res).expect("failed to parse last swap result");
+    assert_eq!(
+        last_swap_result,
+        Some(SwapResult {
+            order_hash: "0xdd7292f6870320976a51a5080b0d64593caf179eb3f193ceeedab4ea5e149ceb"
+                .to_string(),
+            quantity: i32_to_dec(8),
+            price: i32_to_dec(1000),
+            fee: i32_to_dec(36),
+        })
+    );
+}
+
+#[test]
+fn swap_unfilled_reply() {
+    let mut deps = test_deps();
+
+    let order_response = tx::MsgCreateDerivativeMarketOrderResponse {
+        order_hash: "0xdd7292f6870320976a51a5080b0d64593caf179eb3f193ceeedab4ea5e149ceb"
+            .to_string(),
+        ..Default::default()
+    };
+    let reply_msg = Reply {
+        id: ORDER_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(
+                order_response
+                    .write_to_bytes()
+                    .expect("failed to encode order response"),
+            )),
+        }),
+    };
+
+    let res = reply(deps.as_mut(), inj_mock_env(), reply_msg).expect("failed to reply");
+    assert_eq!(res.messages.len(), 0);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "swap_unfilled"),
+            attr(
+                "order_hash",
+                "0xdd7292f6870320976a51a5080b0d64593caf179eb3f193ceeedab4ea5e149ceb"
+            ),
+        ]
+    );
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::SwapHistory {})
+        .expect("failed to query swap history");
+    let swap_history: Vec<SwapRecord> = from_binary(&res).expect("failed to parse swap history");
+    assert!(swap_history.is_empty());
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::LastSwapResult {})
+        .expect("failed to query last swap result");
+    let last_swap_result: Option<SwapResult> =
+        from_binary(&res).expect("failed to parse last swap result");
+    assert_eq!(last_swap_result, None);
+}
+
+#[test]
+fn cancel_order_attributes() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let msg = ExecuteMsg::CancelOrder {
+        order_hash: "0x1234567890".to_string(),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to cancel order");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "cancel_order"),
+            attr("order_hash", "0x1234567890"),
+            attr("market_id", TEST_MARKET_ID),
+        ]
+    );
+}
+
+#[test]
+fn cancel_all_orders() {
+    let mut deps = test_deps();
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let msg = InstantiateMsg {
+        market_id: market_id.clone(),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail to cancel from non-owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CancelAllOrders {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CancelAllOrders {},
+    )
+    .expect("failed to cancel all orders");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "cancel_all_orders"),
+            attr("market_id", TEST_MARKET_ID),
+        ]
+    );
+
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+    assert_eq!(
+        get_message_data(&res.messages, 0).msg_data,
+        InjectiveMsg::BatchUpdateOrders {
+            sender: env.contract.address,
+            subaccount_id: Some(subaccount_id),
+            spot_market_ids_to_cancel_all: vec![],
+            derivative_market_ids_to_cancel_all: vec![market_id],
+            spot_orders_to_cancel: vec![],
+            derivative_orders_to_cancel: vec![],
+            spot_orders_to_create: vec![],
+            derivative_orders_to_create: vec![],
+        }
+    );
+}
+
+#[test]
+fn cancel_order_msg_shapes() {
+    let sender = Addr::unchecked(TEST_CONTRACT_ADDR);
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+
+    let msg = cancel_order_msg(
+        MarketType::Spot,
+        sender.clone(),
+        market_id.clone(),
+        subaccount_id.clone(),
+        "0x1234567890".to_string(),
+    );
+    match msg {
+        CosmosMsg::Custom(msg) => assert_eq!(
+            msg.msg_data,
+            InjectiveMsg::CancelSpotOrder {
+                sender: sender.clone(),
+                market_id: market_id.clone(),
+                subaccount_id: subaccount_id.clone(),
+                order_hash: "0x1234567890".to_string(),
+            }
+        ),
+        _ => panic!("No wrapped message found"),
+    }
+
+    let msg = cancel_order_msg(
+        MarketType::Derivative,
+        sender.clone(),
+        market_id.clone(),
+        subaccount_id.clone(),
+        "0x1234567890".to_string(),
+    );
+    match msg {
+        CosmosMsg::Custom(msg) => assert_eq!(
+            msg.msg_data,
+            InjectiveMsg::CancelDerivativeOrder {
+                sender,
+                market_id,
+                subaccount_id,
+                order_hash: "0x1234567890".to_string(),
+                order_mask: ORDER_MASK_ANY,
+            }
+        ),
+        _ => panic!("No wrapped message found"),
+    }
+}
+
+fn create_subaccount_position_handler(quantity: FPDecimal) -> impl HandlesMarketAndSubaccountQuery {
+    struct Temp {
+        quantity: FPDecimal,
+    }
+    impl HandlesMarketAndSubaccountQuery for Temp {
+        fn handle(&self, _market_id: MarketId, _subaccount_id: SubaccountId) -> QuerierResult {
+            let response = SubaccountPositionInMarketResponse {
+                state: Some(Position {
+                    isLong: true,
+                    quantity: self.quantity,
+                    entry_price: i32_to_dec(9_000000),
+                    margin: i32_to_dec(40_000000),
+                    cumulative_funding_entry: FPDecimal::zero(),
+                }),
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp { quantity }
 }
 
 fn create_perpetual_market_handler() -> impl HandlesMarketIdQuery {
     struct Temp();
     impl HandlesMarketIdQuery for Temp {
         fn handle(&self, market_id: MarketId) -> QuerierResult {
+            let inactive_market_id = MarketId::new(TEST_INACTIVE_MARKET_ID.to_string())
+                .expect("failed to create market_id");
             if market_id
                 == MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id")
+                || market_id == inactive_market_id
             {
+                let status = if market_id == inactive_market_id {
+                    MarketStatus::Paused
+                } else {
+                    MarketStatus::Active
+                };
                 let response = DerivativeMarketResponse {
                     market: Some(FullDerivativeMarket {
                         market: Some(DerivativeMarket {
@@ -673,13 +2900,13 @@ fn create_perpetual_market_handler() -> impl HandlesMarketIdQuery {
                                 .expect("failed to parse string"),
                             oracle_base: "mock_oracle_base".to_string(),
                             oracle_quote: "mock_oracle_quote".to_string(),
-                            oracle_scale_factor: 1000000000u32,
+                            oracle_scale_factor: 6u32,
                             oracle_type: OracleType::Pyth,
                             market_id: market_id.clone(),
-                            status: MarketStatus::Active,
+                            status,
                             min_price_tick_size: FPDecimal::from_str("0.000000000000001")
                                 .expect("failed to parse string"),
-                            min_quantity_tick_size: FPDecimal::from_str("1000000000000000")
+                            min_quantity_tick_size: FPDecimal::from_str("0.001")
                                 .expect("failed to parse string"),
                         }),
                         info: Some(FullDerivativeMarketPerpetualInfo {
@@ -694,15 +2921,14 @@ fn create_perpetual_market_handler() -> impl HandlesMarketIdQuery {
                                     next_funding_timestamp: 100000,
                                 },
                                 funding_info: PerpetualMarketFunding {
-                                    cumulative_funding: FPDecimal::from_str("1")
-                                        .expect("failed to parse string"),
+                                    cumulative_funding: i32_to_dec(1_000000),
                                     cumulative_price: FPDecimal::from_str("1")
                                         .expect("failed to parse string"),
                                     last_timestamp: 123456789,
                                 },
                             },
                         }),
-                        mark_price: FPDecimal::from_str("10").expect("failed to parse string"),
+                        mark_price: i32_to_dec(10_000000),
                     }),
                 };
                 SystemResult::Ok(ContractResult::from(to_binary(&response)))
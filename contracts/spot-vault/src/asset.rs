@@ -3,8 +3,10 @@ use std::fmt;
 
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{
-    coin, Addr, Api, BankMsg, Coin, CosmosMsg, CustomMsg, StdError, StdResult, Uint128,
+    coin, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, CustomMsg, StdError, StdResult, Uint128,
+    WasmMsg,
 };
+use cw20::Cw20ExecuteMsg;
 
 use itertools::Itertools;
 
@@ -29,19 +31,61 @@ impl fmt::Display for Asset {
 impl Asset {
     /// For native tokens of type [`AssetInfo`] uses the default method [`BankMsg::Send`] to send a
     /// token amount to a recipient.
+    /// For CW20 tokens uses [`Cw20ExecuteMsg::Transfer`].
     pub fn into_msg<T>(self, recipient: impl Into<String>) -> StdResult<CosmosMsg<T>>
     where
         T: CustomMsg,
     {
         let recipient = recipient.into();
-        Ok(CosmosMsg::Bank(BankMsg::Send {
-            to_address: recipient,
-            amount: vec![self.as_coin()?],
-        }))
+        match &self.info {
+            AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient,
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            })),
+            AssetInfo::NativeToken { .. } => Ok(CosmosMsg::Bank(BankMsg::Send {
+                to_address: recipient,
+                amount: vec![self.as_coin()?],
+            })),
+        }
+    }
+
+    /// Pulls a CW20 token amount from `owner` to `recipient` with [`Cw20ExecuteMsg::TransferFrom`],
+    /// relying on an allowance granted beforehand. Native tokens are sent as funds instead.
+    pub fn into_transfer_from_msg<T>(
+        self,
+        owner: impl Into<String>,
+        recipient: impl Into<String>,
+    ) -> StdResult<CosmosMsg<T>>
+    where
+        T: CustomMsg,
+    {
+        match &self.info {
+            AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: owner.into(),
+                    recipient: recipient.into(),
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            })),
+            AssetInfo::NativeToken { denom } => Err(StdError::generic_err(format!(
+                "Native token {denom} can't be transferred from an allowance"
+            ))),
+        }
     }
 
     pub fn as_coin(&self) -> StdResult<Coin> {
-        Ok(coin(self.amount.u128(), &self.info.denom))
+        match &self.info {
+            AssetInfo::Token { contract_addr } => Err(StdError::generic_err(format!(
+                "Cannot convert CW20 token {contract_addr} to a native coin"
+            ))),
+            AssetInfo::NativeToken { denom } => Ok(coin(self.amount.u128(), denom)),
+        }
     }
 }
 
@@ -61,12 +105,18 @@ impl CoinsExt for Vec<Coin> {
     ) -> StdResult<()> {
         let pool_coins = pool_asset_infos
             .iter()
-            .filter_map(|asset_info| Some(asset_info.denom.to_string()))
+            .filter_map(|asset_info| match asset_info {
+                AssetInfo::Token { .. } => None,
+                AssetInfo::NativeToken { denom } => Some(denom.to_string()),
+            })
             .collect::<HashSet<_>>();
 
         let input_coins = input_assets
             .iter()
-            .filter_map(|asset| Some((asset.info.denom.to_string(), asset.amount)))
+            .filter_map(|asset| match &asset.info {
+                AssetInfo::Token { .. } => None,
+                AssetInfo::NativeToken { denom } => Some((denom.to_string(), asset.amount)),
+            })
             .map(|pair| {
                 if pool_coins.contains(&pair.0) {
                     Ok(pair)
@@ -80,7 +130,12 @@ impl CoinsExt for Vec<Coin> {
             .collect::<StdResult<HashMap<_, _>>>()?;
 
         self.iter().try_for_each(|coin| {
-            if input_coins.contains_key(&coin.denom) {
+            if coin.amount.is_zero() {
+                Err(StdError::generic_err(format!(
+                    "Supplied coins contain {} with zero amount",
+                    coin.denom
+                )))
+            } else if input_coins.contains_key(&coin.denom) {
                 if input_coins[&coin.denom] == coin.amount {
                     Ok(())
                 } else {
@@ -98,31 +153,63 @@ impl CoinsExt for Vec<Coin> {
     }
 }
 
+/// This enum describes available token types: CW20 tokens and native denoms.
 #[cw_serde]
-pub struct AssetInfo {
-    pub denom: String,
+pub enum AssetInfo {
+    /// Non-native token
+    Token { contract_addr: Addr },
+    /// Native token
+    NativeToken { denom: String },
 }
 
 impl fmt::Display for AssetInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.denom)
+        match self {
+            AssetInfo::NativeToken { denom } => write!(f, "{denom}"),
+            AssetInfo::Token { contract_addr } => write!(f, "{contract_addr}"),
+        }
     }
 }
 
 impl AssetInfo {
+    /// Returns **true** if the calling token is a native token. Otherwise returns **false**.
+    pub fn is_native_token(&self) -> bool {
+        matches!(self, AssetInfo::NativeToken { .. })
+    }
+
     /// Returns **true** if the calling token is the same as the token specified in the input parameters.
     /// Otherwise returns **false**.
     pub fn equal(&self, another_asset: &AssetInfo) -> bool {
-        self.denom == another_asset.denom
+        match (self, another_asset) {
+            (
+                AssetInfo::Token { contract_addr },
+                AssetInfo::Token {
+                    contract_addr: another_contract_addr,
+                },
+            ) => contract_addr == another_contract_addr,
+            (
+                AssetInfo::NativeToken { denom },
+                AssetInfo::NativeToken {
+                    denom: another_denom,
+                },
+            ) => denom == another_denom,
+            _ => false,
+        }
     }
 
     /// Checks that the tokens' denom or contract addr is valid.
-    pub fn check(&self, _api: &dyn Api) -> StdResult<()> {
-        let denom = &self.denom;
-        if !is_valid_symbol(denom, Some(DENOM_MAX_LENGTH)) {
-            return Err(StdError::generic_err(format!(
-                "Native denom is not in expected format [a-zA-Z\\-][3,{DENOM_MAX_LENGTH}]: {denom}",
-            )));
+    pub fn check(&self, api: &dyn Api) -> StdResult<()> {
+        match self {
+            AssetInfo::Token { contract_addr } => {
+                api.addr_validate(contract_addr.as_str())?;
+            }
+            AssetInfo::NativeToken { denom } => {
+                if !is_valid_symbol(denom, Some(DENOM_MAX_LENGTH)) {
+                    return Err(StdError::generic_err(format!(
+                        "Native denom is not in expected format [a-zA-Z\\-][3,{DENOM_MAX_LENGTH}]: {denom}",
+                    )));
+                }
+            }
         }
         Ok(())
     }
@@ -138,14 +225,47 @@ pub fn addr_opt_validate(api: &dyn Api, addr: &Option<String>) -> StdResult<Opti
 
 const TOKEN_SYMBOL_MAX_LENGTH: usize = 4;
 
-/// Returns a formatted LP token name
+/// Maximum length of each denom in the LP token name
+const LP_NAME_DENOM_MAX_LENGTH: usize = 8;
+
+/// Returns a formatted LP token name, e.g. `USDX-USDT-LP` for `factory/inj1.../usdx` and `usdt`.
+/// Identical short denoms get an index appended to keep the sides apart.
 pub fn format_lp_token_name(denom0: &String, denom1: &String) -> StdResult<String> {
     let mut short_denoms: Vec<String> = vec![];
-    let short_denom0 = denom0.chars().take(TOKEN_SYMBOL_MAX_LENGTH).collect();
-    short_denoms.push(short_denom0);
-    let short_denom1 = denom1.chars().take(TOKEN_SYMBOL_MAX_LENGTH).collect();
-    short_denoms.push(short_denom1);
-    Ok(format!("{}-LP", short_denoms.iter().join("-")).to_uppercase())
+    for denom in [denom0, denom1] {
+        let mut short_denom = format_short_denom(denom);
+        if short_denoms.contains(&short_denom) {
+            short_denom = format!("{}{}", short_denom, short_denoms.len() + 1);
+        }
+        short_denoms.push(short_denom);
+    }
+    Ok(format!("{}-LP", short_denoms.iter().join("-")))
+}
+
+/// Returns the uppercased last path segment of a denom, truncated so factory denoms sharing a
+/// creator prefix stay distinct.
+fn format_short_denom(denom: &str) -> String {
+    let last_segment = denom.rsplit('/').next().unwrap_or(denom);
+    last_segment
+        .chars()
+        .take(LP_NAME_DENOM_MAX_LENGTH)
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Returns an LP token symbol built from the first letter of each side of the market ticker,
+/// e.g. `IULP` for `INJ/USDT`. Falls back to `uLP` when the ticker doesn't yield a valid symbol.
+pub fn format_lp_token_symbol(ticker: &str) -> String {
+    let initials: String = ticker
+        .split('/')
+        .filter_map(|side| side.chars().find(|c| c.is_ascii_alphabetic()))
+        .collect();
+    let symbol = format!("{}LP", initials).to_uppercase();
+    if initials.chars().count() == 2 && is_valid_symbol(&symbol, Some(TOKEN_SYMBOL_MAX_LENGTH)) {
+        symbol
+    } else {
+        "uLP".to_string()
+    }
 }
 
 /// Checks the validity of the token symbol
@@ -1,7 +1,8 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
-    MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+    attr, from_binary, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Order, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, Uint128,
+    WasmMsg,
 };
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
 use cw20_base::msg::InstantiateMsg as TokenInstantiateMsg;
@@ -13,23 +14,49 @@ use protobuf::Message;
 #[cfg(not(feature = "library"))]
 use std::cmp::min;
 
+use injective_cosmwasm::exchange::spot::TrimmedSpotLimitOrder;
+use injective_cosmwasm::oracle::types::{PriceState, PythPriceState};
 use injective_cosmwasm::{
-    cancel_spot_order_msg, create_batch_update_orders_msg,
+    create_batch_update_orders_msg, create_deposit_msg, create_spot_market_order_msg,
     get_default_subaccount_id_for_checked_address, InjectiveMsgWrapper, InjectiveQuerier,
-    InjectiveQueryWrapper, MarketStatus, OrderType, SpotOrder,
+    InjectiveQueryWrapper, MarketStatus, OrderData, OrderType, SpotMarket, SpotOrder,
 };
 
-use crate::asset::{addr_opt_validate, format_lp_token_name, Asset, AssetInfo, CoinsExt};
+use crate::asset::{
+    addr_opt_validate, format_lp_token_name, format_lp_token_symbol, Asset, AssetInfo, CoinsExt,
+};
 use crate::error::ContractError;
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::helpers::{
+    cancel_order_msg, ceil_to_tick, floor_to_tick, is_on_tick, MarketType, ORDER_MASK_ANY,
+};
+use crate::msg::{
+    BalanceReconciliation, CanWithdrawResponse, Cw20HookMsg, EffectiveFeeRateResponse,
+    EffectiveHardcapResponse, ExecuteMsg, FeeStateResponse, InstantiateMsg, InventorySkewResponse,
+    MigrateMsg, OpenOrder, PriceSource, QueryMsg, RiskParamsResponse, SimulateDepositResponse,
+    SpotOrderParams, ValidateDepositResponse,
+};
 use crate::querier::{query_balance, query_supply, query_token_balance};
 use crate::response::MsgInstantiateContractResponse;
-use crate::state::{ContractInfo, BASE_FEE_COLLECTED, CONTRACT_INFO, QUOTE_FEE_COLLECTED};
+use crate::state::{
+    default_max_conf_ratio, ContractInfo, OrderMeta, ACCOUNTED_LIQUIDITY, BASE_FEE_COLLECTED,
+    CONTRACT_INFO, CREDITS, INITIAL_SEEDED, INSTANTIATE_PARAMS, LAST_SWAP_ORACLE_PRICE,
+    OPEN_ORDERS, PENDING_BATCH_ORDERS, PENDING_CANCEL, PENDING_ORDER, QUOTE_FEE_COLLECTED,
+    RESIDUE_COLLECTED, TOTAL_CREDITS, USER_MINTED_SHARES,
+};
 
 /// A `reply` call code ID used for sub-messages.
 pub const INSTANTIATE_TOKEN_REPLY_ID: u64 = 1u64;
 pub const ORDER_REPLY_ID: u64 = 2u64;
+pub const CANCEL_REPLY_ID: u64 = 3u64;
+pub const DEFAULT_LP_DECIMAL: u8 = 12;
+pub const MAX_LP_DECIMAL: u8 = 18;
 pub const PRICE_VALID_DURATION: i64 = 60; // 1 min
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_BATCH_CANCEL: usize = 20;
+pub const MAX_WITHDRAW_CURVE_STEPS: u32 = 100;
+/// Share of the free target balance a single consolidated withdrawal may convert into
+pub const MAX_CONSOLIDATION_BPS: u64 = 1_000;
+pub const CONTRACT_VERSION: u64 = 1;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -38,15 +65,34 @@ pub fn instantiate(
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let lp_decimal = msg.lp_decimal.unwrap_or(DEFAULT_LP_DECIMAL);
+    if lp_decimal > MAX_LP_DECIMAL {
+        return Err(ContractError::CustomError {
+            val: format!("LP decimal must not exceed {MAX_LP_DECIMAL}"),
+        });
+    }
+    if msg.base_decimal == 0 || msg.quote_decimal == 0 {
+        return Err(ContractError::CustomError {
+            val: "Base and quote decimals must be positive".to_string(),
+        });
+    }
+
+    assert_valid_price_ids(deps.as_ref(), &msg.base_price_id, &msg.quote_price_id)?;
+    INSTANTIATE_PARAMS.save(deps.storage, &msg)?;
+
     let querier = InjectiveQuerier::new(&deps.querier);
     if let Some(market) = querier.query_spot_market(&msg.market_id)?.market {
         if market.status != MarketStatus::Active {
-            return Err(ContractError::CustomError {
-                val: format!("Market with id: {} not active", msg.market_id.as_str()),
+            return Err(ContractError::MarketNotActive {
+                market_id: msg.market_id.as_str().to_string(),
             });
         }
         cw_ownable::initialize_owner(deps.storage, deps.api, Some(msg.owner.as_str()))
             .expect(format!("Invalid owner: {}", msg.owner).as_str());
+        let liquidity_token = match &msg.existing_lp_token {
+            Some(existing_lp_token) => deps.api.addr_validate(existing_lp_token)?,
+            None => Addr::unchecked(""),
+        };
         let contract_info = ContractInfo {
             market_id: msg.market_id,
             base_denom: market.base_denom,
@@ -56,16 +102,42 @@ pub fn instantiate(
             base_price_id: msg.base_price_id,
             quote_price_id: msg.quote_price_id,
             hardcap: msg.hardcap,
-            liquidity_token: Addr::unchecked(""),
+            base_hardcap: None,
+            quote_hardcap: None,
+            credit_overpayment: msg.credit_overpayment,
+            lp_decimal,
+            min_order_notional: FPDecimal::zero(),
+            max_slippage_bps: None,
+            max_spread_bps: None,
+            max_price_jump_bps: None,
+            max_conf_ratio: default_max_conf_ratio(),
+            deposit_start_time: msg.deposit_start_time,
+            max_open_orders: None,
+            paused: false,
+            per_user_cap: None,
+            market_price_fallback: msg.market_price_fallback,
+            treasury: None,
+            liquidity_token,
             contract_subaccount_id: get_default_subaccount_id_for_checked_address(
                 &env.contract.address,
             ),
+            contract_version: CONTRACT_VERSION,
         };
         CONTRACT_INFO.save(deps.storage, &contract_info)?;
         BASE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
         QUOTE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
-        let token_name =
-            format_lp_token_name(&contract_info.base_denom, &contract_info.quote_denom)?;
+        if msg.existing_lp_token.is_some() {
+            return Ok(Response::<InjectiveMsgWrapper>::new()
+                .add_attribute("method", "instantiate")
+                .add_attribute("liquidity_token_addr", contract_info.liquidity_token));
+        }
+        let token_name = match msg.lp_token_name {
+            Some(lp_token_name) => lp_token_name,
+            None => format_lp_token_name(&contract_info.base_denom, &contract_info.quote_denom)?,
+        };
+        let token_symbol = msg
+            .lp_token_symbol
+            .unwrap_or_else(|| format_lp_token_symbol(&market.ticker));
 
         // Create the LP token contract
         let sub_msg: Vec<SubMsg<InjectiveMsgWrapper>> = vec![SubMsg {
@@ -73,8 +145,8 @@ pub fn instantiate(
                 code_id: msg.token_code_id,
                 msg: to_binary(&TokenInstantiateMsg {
                     name: token_name,
-                    symbol: "uLP".to_string(),
-                    decimals: 12,
+                    symbol: token_symbol,
+                    decimals: lp_decimal,
                     initial_balances: vec![],
                     mint: Some(MinterResponse {
                         minter: env.contract.address.to_string(),
@@ -96,12 +168,65 @@ pub fn instantiate(
             .add_submessages(sub_msg)
             .add_attribute("method", "instantiate"))
     } else {
-        Err(ContractError::CustomError {
-            val: format!("Market with id: {} not found", msg.market_id.as_str()),
+        Err(ContractError::MarketNotFound {
+            market_id: msg.market_id.as_str().to_string(),
         })
     }
 }
 
+/// Checks the price ids are distinct and both resolve to a Pyth price.
+fn assert_valid_price_ids(
+    deps: Deps<InjectiveQueryWrapper>,
+    base_price_id: &str,
+    quote_price_id: &str,
+) -> Result<(), ContractError> {
+    if base_price_id == quote_price_id {
+        return Err(ContractError::CustomError {
+            val: format!("Base and quote price ids must differ: {base_price_id}"),
+        });
+    }
+
+    let querier = InjectiveQuerier::new(&deps.querier);
+    for price_id in [base_price_id, quote_price_id] {
+        if querier.query_pyth_price(price_id)?.price_state.is_none() {
+            return Err(ContractError::CustomError {
+                val: format!("Price id does not resolve: {price_id}"),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The entry point to the contract for migrating deployed vaults to new code.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    _msg: MigrateMsg,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if contract_info.liquidity_token == Addr::unchecked("") {
+        return Err(ContractError::CustomError {
+            val: "Cannot migrate before the liquidity token is set".to_string(),
+        });
+    }
+
+    // No state transform is needed yet, only the version is bumped
+    let previous_version = contract_info.contract_version;
+    contract_info.contract_version = previous_version + 1;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "migrate"),
+        attr("previous_version", previous_version.to_string()),
+        attr(
+            "contract_version",
+            contract_info.contract_version.to_string(),
+        ),
+    ]))
+}
+
 /// The entry point to the contract for processing replies from submessages.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn reply(
@@ -112,6 +237,7 @@ pub fn reply(
     match msg.id {
         INSTANTIATE_TOKEN_REPLY_ID => handle_instantiate_token_reply(deps, env, msg),
         ORDER_REPLY_ID => handle_order_reply(deps, env, msg),
+        CANCEL_REPLY_ID => handle_cancel_reply(deps, env, msg),
         _ => Err(ContractError::UnrecognisedReply(msg.id)),
     }
 }
@@ -147,7 +273,7 @@ fn handle_instantiate_token_reply(
 }
 
 fn handle_order_reply(
-    _deps: DepsMut<InjectiveQueryWrapper>,
+    deps: DepsMut<InjectiveQueryWrapper>,
     _env: Env,
     msg: Reply,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
@@ -168,11 +294,57 @@ fn handle_order_reply(
         err: err.to_string(),
     })?;
 
-    let order_hash = order_response.spot_order_hashes.into_vec()[0].clone();
+    let order_hashes = order_response.spot_order_hashes.into_vec();
+
+    if let Some(orders) = PENDING_BATCH_ORDERS.may_load(deps.storage)? {
+        PENDING_BATCH_ORDERS.remove(deps.storage);
+
+        let mut attrs = vec![];
+        for (order_hash, order) in order_hashes.iter().zip(orders) {
+            OPEN_ORDERS.save(deps.storage, order_hash, &order)?;
+            attrs.push(attr("order_hash", order_hash));
+        }
+        if attrs.is_empty() {
+            attrs.push(attr("order_hash", "none"));
+        }
+        return Ok(Response::new().add_attributes(attrs));
+    }
+
+    let pending_order = PENDING_ORDER.may_load(deps.storage)?;
+    PENDING_ORDER.remove(deps.storage);
+
+    // No hash is returned when the order didn't rest on the book
+    let order_hash = match order_hashes.into_iter().next() {
+        Some(order_hash) => order_hash,
+        None => return Ok(Response::new().add_attributes(vec![attr("order_hash", "none")])),
+    };
+    if let Some(order) = pending_order {
+        OPEN_ORDERS.save(deps.storage, &order_hash, &order)?;
+    }
 
     Ok(Response::new().add_attributes(vec![attr("order_hash", order_hash)]))
 }
 
+fn handle_cancel_reply(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    _msg: Reply,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let order_hashes = PENDING_CANCEL.load(deps.storage)?;
+    PENDING_CANCEL.remove(deps.storage);
+    for order_hash in order_hashes.iter() {
+        OPEN_ORDERS.remove(deps.storage, order_hash);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_order_confirmed")
+        .add_attributes(
+            order_hashes
+                .into_iter()
+                .map(|order_hash| attr("order_hash", order_hash)),
+        ))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut<InjectiveQueryWrapper>,
@@ -195,13 +367,52 @@ pub fn execute(
             }
         }
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
-        ExecuteMsg::Deposit { assets, receiver } => deposit(deps, env, info, assets, receiver),
+        ExecuteMsg::Deposit {
+            assets,
+            receiver,
+            min_lp_out,
+            max_share_price,
+        } => deposit(
+            deps,
+            env,
+            info,
+            assets,
+            receiver,
+            min_lp_out,
+            max_share_price,
+        ),
+        ExecuteMsg::InitialSeed { assets } => initial_seed(deps, env, info, assets),
+        ExecuteMsg::DepositAndRebalance {
+            asset,
+            max_slippage_bps,
+            receiver,
+        } => deposit_and_rebalance(deps, env, info, asset, max_slippage_bps, receiver),
         ExecuteMsg::SwapSpot {
             buying,
             quantity,
             price,
-        } => try_swap(deps, env, info, buying, quantity, price),
+            fund_subaccount,
+            post_only,
+        } => try_swap(
+            deps,
+            env,
+            info,
+            spot_order_type(buying, post_only),
+            quantity,
+            price,
+            fund_subaccount,
+        ),
+        ExecuteMsg::SwapSpotBatch { orders } => try_swap_batch(deps, env, info, orders),
+        ExecuteMsg::SwapSpotAtOracle {
+            buying,
+            quantity,
+            buffer_bps,
+        } => try_swap_at_oracle(deps, env, info, buying, quantity, buffer_bps),
         ExecuteMsg::CancelOrder { order_hash } => try_cancel_order(deps, env, info, order_hash),
+        ExecuteMsg::BatchCancelByHashes { hashes } => {
+            try_batch_cancel_orders(deps, env, info, hashes)
+        }
+        ExecuteMsg::CancelAllOrders {} => try_cancel_all_orders(deps, env, info),
         ExecuteMsg::AddFee {
             base_fee,
             quote_fee,
@@ -210,6 +421,49 @@ pub fn execute(
             base_fee,
             quote_fee,
         } => withdraw_fee(deps, env, info, base_fee, quote_fee),
+        ExecuteMsg::ForwardFees { fee_vault } => forward_fees(deps, env, info, fee_vault),
+        ExecuteMsg::CollectResidue {} => collect_residue(deps, env, info),
+        ExecuteMsg::SweepToken { denom, recipient } => {
+            sweep_token(deps, env, info, denom, recipient)
+        }
+        ExecuteMsg::BurnContractLp {} => burn_contract_lp(deps, env, info),
+        ExecuteMsg::UpdateAssetHardcaps {
+            base_hardcap,
+            quote_hardcap,
+        } => update_asset_hardcaps(deps, env, info, base_hardcap, quote_hardcap),
+        ExecuteMsg::ClaimCredit {} => claim_credit(deps, env, info),
+        ExecuteMsg::CheckPrices {} => check_prices(deps, env),
+        ExecuteMsg::UpdateMinOrderNotional { min_order_notional } => {
+            update_min_order_notional(deps, env, info, min_order_notional)
+        }
+        ExecuteMsg::UpdateMaxConfRatio { max_conf_ratio } => {
+            update_max_conf_ratio(deps, env, info, max_conf_ratio)
+        }
+        ExecuteMsg::UpdateMaxOpenOrders { max_open_orders } => {
+            update_max_open_orders(deps, env, info, max_open_orders)
+        }
+        ExecuteMsg::UpdatePerUserCap { per_user_cap } => {
+            update_per_user_cap(deps, env, info, per_user_cap)
+        }
+        ExecuteMsg::UpdateTreasury { treasury } => update_treasury(deps, env, info, treasury),
+        ExecuteMsg::SetPaused { paused } => set_paused(deps, env, info, paused),
+        ExecuteMsg::UpdateHardcap { hardcap } => update_hardcap(deps, env, info, hardcap),
+        ExecuteMsg::UpdatePriceIds {
+            base_price_id,
+            quote_price_id,
+        } => update_price_ids(deps, env, info, base_price_id, quote_price_id),
+        ExecuteMsg::UpdateRiskParams {
+            max_slippage_bps,
+            max_spread_bps,
+            max_price_jump_bps,
+        } => update_risk_params(
+            deps,
+            env,
+            info,
+            max_slippage_bps,
+            max_spread_bps,
+            max_price_jump_bps,
+        ),
     }
 }
 
@@ -223,12 +477,19 @@ fn receive_cw20(
     cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
     match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::Withdraw {}) => withdraw(
+        Ok(Cw20HookMsg::Withdraw {
+            to_subaccount,
+            max_imbalance_bps,
+            target_denom,
+        }) => withdraw(
             deps,
             env,
             info,
             Addr::unchecked(cw20_msg.sender),
             cw20_msg.amount,
+            to_subaccount,
+            max_imbalance_bps,
+            target_denom,
         ),
         Err(err) => Err(err.into()),
     }
@@ -236,7 +497,8 @@ fn receive_cw20(
 
 /// Deposit tokens with the specified input parameters.
 ///
-/// * **assets** is an array with assets supported by vault.
+/// * **assets** is an array with one or both assets supported by vault, a single asset is
+/// deposited single-sided.
 ///
 /// * **receiver** is an optional parameter which defines the receiver of the LP tokens.
 /// If no custom receiver is specified, the vault will mint LP tokens for the function caller.
@@ -246,67 +508,72 @@ fn deposit(
     info: MessageInfo,
     assets: Vec<Asset>,
     receiver: Option<String>,
+    min_lp_out: Option<Uint128>,
+    max_share_price: Option<Uint128>,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
-    if assets.len() != 2 {
-        return Err(StdError::generic_err("assets must contain exactly two elements").into());
+    if assets.is_empty() || assets.len() > 2 {
+        return Err(StdError::generic_err("assets must contain one or two elements").into());
+    }
+    for asset in assets.iter() {
+        assert_valid_denom(deps.api, &asset.info)?;
     }
-    assets[0].info.check(deps.api)?;
-    assets[1].info.check(deps.api)?;
 
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    assert_not_paused(&contract_info)?;
+    assert_deposits_open(&env, &contract_info)?;
 
     let supported = vec![
-        AssetInfo {
+        AssetInfo::NativeToken {
             denom: contract_info.base_denom.clone(),
         },
-        AssetInfo {
+        AssetInfo::NativeToken {
             denom: contract_info.quote_denom.clone(),
         },
     ];
     info.funds.assert_coins_properly_sent(&assets, &supported)?;
-
-    let amounts = [
-        assets
-            .iter()
-            .find(|a| a.info.equal(&supported[0]))
-            .map(|a| a.amount)
-            .expect("Wrong asset info is given"),
-        assets
-            .iter()
-            .find(|a| a.info.equal(&supported[1]))
-            .map(|a| a.amount)
-            .expect("Wrong asset info is given"),
-    ];
+    let amounts = get_deposit_amounts(&contract_info, &assets)?;
+    let single_sided = assets.len() == 1;
+    let zero_amount = if single_sided {
+        amounts.iter().all(Uint128::is_zero)
+    } else {
+        amounts.iter().any(Uint128::is_zero)
+    };
+    if zero_amount {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
 
     let prices = get_prices(deps.as_ref(), env.clone())?;
-
-    let scaled_amount0 = FPDecimal::from(amounts[0]).scaled(-(contract_info.base_decimal as i32));
-    let scaled_amount1 = FPDecimal::from(amounts[1]).scaled(-(contract_info.quote_decimal as i32));
-
-    let token0_value = scaled_amount0 * prices[0];
-    let token1_value = scaled_amount1 * prices[1];
-    let single_deposit_value = min(token0_value, token1_value);
-
-    let actual_deposits = [
-        single_deposit_value / prices[0],
-        single_deposit_value / prices[1],
-    ];
-
-    if actual_deposits[0].is_zero() || actual_deposits[1].is_zero() {
-        return Err(ContractError::InvalidZeroAmount {});
+    if let Some(max_share_price) = max_share_price {
+        assert_max_share_price(deps.as_ref(), &env, &contract_info, prices, max_share_price)?;
     }
+    let (deposited, share) = get_deposit_shares(
+        deps.as_ref(),
+        &env,
+        &contract_info,
+        prices,
+        amounts,
+        single_sided,
+    )?;
 
-    let unscaled_amount0 = Uint128::new(u128::from(
-        actual_deposits[0].scaled(contract_info.base_decimal as i32),
-    ));
-    let unscaled_amount1 = Uint128::new(u128::from(
-        actual_deposits[1].scaled(contract_info.quote_decimal as i32),
-    ));
+    // The balanced part of a tiny deposit can round to zero on one side
+    if !single_sided && deposited.iter().any(Uint128::is_zero) {
+        return Err(ContractError::CustomError {
+            val: format!("Deposit too small: balanced amounts round to zero"),
+        });
+    }
+    if share.is_zero() {
+        return Err(ContractError::ZeroShare {});
+    }
+    if let Some(min_lp_out) = min_lp_out {
+        if share < min_lp_out {
+            return Err(ContractError::SlippageExceeded { share, min_lp_out });
+        }
+    }
 
     let mut messages = vec![];
 
-    let refund0 = amounts[0] - unscaled_amount0;
-    let refund1 = amounts[1] - unscaled_amount1;
+    let refund0 = amounts[0] - deposited[0];
+    let refund1 = amounts[1] - deposited[1];
     let mut refund_assets = vec![];
     if !refund0.is_zero() {
         refund_assets.push(Coin::new(
@@ -321,35 +588,33 @@ fn deposit(
         ));
     }
     let mut refund_message: Option<BankMsg> = None;
-    if !refund_assets.is_empty() {
+    if contract_info.credit_overpayment {
+        // Keep the overpayment aside for the sender to claim later
+        credit_overpayment(deps.storage, &info.sender, [refund0, refund1])?;
+    } else if !refund_assets.is_empty() {
         refund_message = Some(BankMsg::Send {
             to_address: info.sender.to_string(),
             amount: refund_assets,
         });
     }
 
-    let scaled_share = convert_to_shares(
-        deps.as_ref(),
-        env,
-        actual_deposits,
-        prices,
-        [contract_info.base_decimal, contract_info.quote_decimal],
-    )?;
-    let share = Uint128::new(u128::from(scaled_share.scaled(12)));
-
-    if share.is_zero() {
-        return Err(ContractError::CustomError {
-            val: format!("Zero share amount"),
-        });
-    }
-
     let receiver = addr_opt_validate(deps.api, &receiver)?.unwrap_or_else(|| info.sender.clone());
+    assert_not_treasury(&contract_info, &info.sender, &receiver)?;
 
     let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
 
     if total_share + share > contract_info.hardcap {
         return Err(ContractError::ExceedHardcap {});
     }
+    let pending_refunds = if contract_info.credit_overpayment {
+        [Uint128::zero(), Uint128::zero()]
+    } else {
+        [refund0, refund1]
+    };
+    assert_asset_hardcaps(deps.as_ref(), &env, &contract_info, pending_refunds)?;
+    record_user_shares(deps.storage, &contract_info, &receiver, share)?;
+
+    update_accounted_liquidity(deps.storage, deposited, [Uint128::zero(), Uint128::zero()])?;
 
     // Mint LP tokens for the sender or for the receiver (if set)
     messages.extend(mint_liquidity_token_message(
@@ -369,11 +634,11 @@ fn deposit(
                 format!(
                     "{}, {}",
                     Asset {
-                        amount: unscaled_amount0,
+                        amount: deposited[0],
                         info: supported[0].clone(),
                     },
                     Asset {
-                        amount: unscaled_amount1,
+                        amount: deposited[1],
                         info: supported[1].clone(),
                     }
                 ),
@@ -387,190 +652,1741 @@ fn deposit(
     Ok(res)
 }
 
-fn try_swap(
+/// Seeds the vault with the first deposit, which bootstraps the share price and mints the
+/// initial LP tokens to the owner. Only allowed once and before any other deposit.
+fn initial_seed(
     deps: DepsMut<InjectiveQueryWrapper>,
     env: Env,
     info: MessageInfo,
-    buying: bool,
-    quantity: FPDecimal,
-    price: FPDecimal,
+    assets: Vec<Asset>,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
-    let contract_info = CONTRACT_INFO.load(deps.storage)?;
-
     if !is_owner(deps.storage, &info.sender)? {
         return Err(ContractError::Unauthorized {});
     }
+    if INITIAL_SEEDED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::CustomError {
+            val: "Vault already seeded".to_string(),
+        });
+    }
 
-    let contract = env.contract.address;
-    let subaccount_id = contract_info.contract_subaccount_id;
-    let min_amount = price * quantity;
-    if !info.funds.is_empty() {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if contract_info.liquidity_token == Addr::unchecked("") {
         return Err(ContractError::CustomError {
-            val: "Do not provide funds!".to_string(),
+            val: "Cannot seed before the liquidity token is set".to_string(),
         });
     }
-    let source_denom = if buying {
-        contract_info.quote_denom
-    } else {
-        contract_info.base_denom
-    };
-    let fee_collected = if buying {
-        QUOTE_FEE_COLLECTED.load(deps.storage)?
-    } else {
-        BASE_FEE_COLLECTED.load(deps.storage)?
-    };
-    let balance = FPDecimal::from(
-        query_balance(&deps.querier, contract.to_string(), source_denom)? - fee_collected,
-    );
-    if balance < min_amount {
+    if !query_supply(&deps.querier, &contract_info.liquidity_token)?.is_zero() {
         return Err(ContractError::CustomError {
-            val: format!("Swap: {balance} below min_amount: {min_amount}"),
+            val: "Cannot seed a vault with deposits".to_string(),
         });
     }
-    let order_type = if buying {
-        OrderType::Buy
-    } else {
-        OrderType::Sell
-    };
-    let order = SpotOrder::new(
-        price,
-        quantity,
-        order_type,
-        &contract_info.market_id,
-        subaccount_id.clone(),
-        Some(contract.to_owned()),
-    );
 
-    let order_message = SubMsg::reply_on_success(
-        create_batch_update_orders_msg(
-            contract,
-            None,
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![order],
-            vec![],
-        ),
-        ORDER_REPLY_ID,
-    );
-    let response = Response::<InjectiveMsgWrapper>::new().add_submessage(order_message);
+    INITIAL_SEEDED.save(deps.storage, &true)?;
+    let res = deposit(deps, env, info, assets, None, None, None)?;
 
-    Ok(response)
+    Ok(res.add_attribute("initial_seed", "true"))
 }
 
-fn try_cancel_order(
-    deps: DepsMut<InjectiveQueryWrapper>,
-    env: Env,
-    info: MessageInfo,
-    order_hash: String,
-) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
-    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+/// Returns the (base, quote) amounts of a deposit, zero for a missing asset.
+fn get_deposit_amounts(contract_info: &ContractInfo, assets: &[Asset]) -> StdResult<[Uint128; 2]> {
+    if assets.is_empty() || assets.len() > 2 {
+        return Err(StdError::generic_err(
+            "assets must contain one or two elements",
+        ));
+    }
+    if assets.len() == 2 && assets[0].info.equal(&assets[1].info) {
+        return Err(StdError::generic_err(format!(
+            "Duplicate asset {}",
+            assets[0].info
+        )));
+    }
 
-    if !is_owner(deps.storage, &info.sender)? {
-        return Err(ContractError::Unauthorized {});
+    let supported = [
+        AssetInfo::NativeToken {
+            denom: contract_info.base_denom.clone(),
+        },
+        AssetInfo::NativeToken {
+            denom: contract_info.quote_denom.clone(),
+        },
+    ];
+    if let Some(asset) = assets
+        .iter()
+        .find(|a| !supported.iter().any(|info| a.info.equal(info)))
+    {
+        return Err(StdError::generic_err(format!(
+            "Asset {} is not in the pool",
+            asset.info
+        )));
     }
 
-    let contract = env.contract.address;
-    let subaccount_id = contract_info.contract_subaccount_id;
+    Ok(supported.map(|info| {
+        assets
+            .iter()
+            .find(|a| a.info.equal(&info))
+            .map_or(Uint128::zero(), |a| a.amount)
+    }))
+}
 
-    let cancel_message = cancel_spot_order_msg(
-        contract,
-        contract_info.market_id.clone(),
-        subaccount_id.clone(),
-        order_hash,
+/// Values a deposit of `amounts` and returns the (base, quote) amounts taken and the LP shares
+/// minted for them.
+///
+/// * **single_sided** takes the whole amounts at the oracle price, otherwise only the balanced
+/// part of the deposit is taken and the rest is refunded.
+fn get_deposit_shares(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    prices: [FPDecimal; 2],
+    amounts: [Uint128; 2],
+    single_sided: bool,
+) -> StdResult<([Uint128; 2], Uint128)> {
+    let decimals = [contract_info.base_decimal, contract_info.quote_decimal];
+    let scaled_amounts = [
+        FPDecimal::from(amounts[0]).scaled(-(decimals[0] as i32)),
+        FPDecimal::from(amounts[1]).scaled(-(decimals[1] as i32)),
+    ];
+
+    let mut actual_deposits = if single_sided {
+        scaled_amounts
+    } else if scaled_amounts[0] * prices[0] <= scaled_amounts[1] * prices[1] {
+        // The base side is binding and is taken as is
+        [scaled_amounts[0], FPDecimal::zero()]
+    } else {
+        [scaled_amounts[1] * prices[1] / prices[0], FPDecimal::zero()]
+    };
+
+    // Keep the deposited base on the market quantity tick so the vault holds no untradeable dust.
+    // The quote side of a balanced deposit follows the rounded base value.
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let quantity_tick = querier
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?
+        .min_quantity_tick_size;
+    let base_deposit = round_down_to_tick(
+        Uint128::new(u128::from(actual_deposits[0].scaled(decimals[0] as i32))),
+        quantity_tick,
     );
-    let response = Response::<InjectiveMsgWrapper>::new().add_message(cancel_message);
+    actual_deposits[0] = FPDecimal::from(base_deposit).scaled(-(decimals[0] as i32));
+    if !single_sided {
+        actual_deposits[1] = actual_deposits[0] * prices[0] / prices[1];
+    }
 
-    Ok(response)
+    let scaled_share = convert_to_shares(deps, env.clone(), actual_deposits, prices, decimals)?;
+
+    Ok((
+        [
+            Uint128::new(u128::from(actual_deposits[0].scaled(decimals[0] as i32))),
+            Uint128::new(u128::from(actual_deposits[1].scaled(decimals[1] as i32))),
+        ],
+        Uint128::new(u128::from(
+            scaled_share.scaled(contract_info.lp_decimal as i32),
+        )),
+    ))
 }
 
-fn add_fee(
-    deps: DepsMut<InjectiveQueryWrapper>,
-    _env: Env,
-    info: MessageInfo,
-    base_fee: Uint128,
-    quote_fee: Uint128,
-) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
-    if !is_owner(deps.storage, &info.sender)? {
-        return Err(ContractError::Unauthorized {});
+/// Rounds `amount` down to a multiple of the market tick. Ticks below one unit leave it as is.
+fn round_down_to_tick(amount: Uint128, tick: FPDecimal) -> Uint128 {
+    let tick = u128::from(tick);
+    if tick <= 1 {
+        return amount;
     }
 
-    let base_fee_collected = BASE_FEE_COLLECTED.load(deps.storage)?;
-    let quote_fee_collected = QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    amount - Uint128::new(amount.u128() % tick)
+}
 
-    BASE_FEE_COLLECTED.save(deps.storage, &(base_fee_collected + base_fee))?;
-    QUOTE_FEE_COLLECTED.save(deps.storage, &(quote_fee_collected + quote_fee))?;
+/// Rejects a deposit when the vault value per LP token, in USD scaled by 10^8, is above
+/// `max_share_price`. A vault without LP supply has no share price yet.
+fn assert_max_share_price(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    prices: [FPDecimal; 2],
+    max_share_price: Uint128,
+) -> Result<(), ContractError> {
+    let total_share = FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?)
+        .scaled(-(contract_info.lp_decimal as i32));
+    if total_share.is_zero() {
+        return Ok(());
+    }
 
-    Ok(Response::default())
+    let balances = get_vault_balances(deps, env)?;
+    let total_value = FPDecimal::from(balances[0]).scaled(-(contract_info.base_decimal as i32))
+        * prices[0]
+        + FPDecimal::from(balances[1]).scaled(-(contract_info.quote_decimal as i32)) * prices[1];
+    let share_price = Uint128::new(u128::from((total_value / total_share).scaled(8)));
+    if share_price > max_share_price {
+        return Err(ContractError::SharePriceExceeded {
+            share_price,
+            max_share_price,
+        });
+    }
+
+    Ok(())
 }
 
-fn withdraw_fee(
+/// Deposit a single asset and swap half of it into the paired asset.
+///
+/// * **asset** is the deposited asset, either the base or the quote.
+///
+/// * **max_slippage_bps** bounds the rebalancing order price relative to the oracle price.
+///
+/// * **receiver** is an optional parameter which defines the receiver of the LP tokens.
+fn deposit_and_rebalance(
     deps: DepsMut<InjectiveQueryWrapper>,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    base_fee: Uint128,
-    quote_fee: Uint128,
+    asset: Asset,
+    max_slippage_bps: u64,
+    receiver: Option<String>,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
-    let contract_info = CONTRACT_INFO.load(deps.storage)?;
-
-    if !is_owner(deps.storage, &info.sender)? {
-        return Err(ContractError::Unauthorized {});
-    }
-    if base_fee.is_zero() && quote_fee.is_zero() {
+    assert_valid_denom(deps.api, &asset.info)?;
+    if max_slippage_bps > BPS_DENOMINATOR {
         return Err(ContractError::CustomError {
-            val: format!("Can't withdraw zero fees"),
+            val: format!("max_slippage_bps must not exceed {BPS_DENOMINATOR}"),
         });
     }
 
-    let base_fee_collected = BASE_FEE_COLLECTED.load(deps.storage)?;
-    let quote_fee_collected = QUOTE_FEE_COLLECTED.load(deps.storage)?;
-    if base_fee_collected < base_fee || quote_fee_collected < quote_fee {
-        return Err(ContractError::CustomError {
-            val: format!("Insufficient fee accrued"),
-        });
-    }
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    assert_not_paused(&contract_info)?;
+    assert_deposits_open(&env, &contract_info)?;
+
+    let supported = vec![
+        AssetInfo::NativeToken {
+            denom: contract_info.base_denom.clone(),
+        },
+        AssetInfo::NativeToken {
+            denom: contract_info.quote_denom.clone(),
+        },
+    ];
+    info.funds
+        .assert_coins_properly_sent(&[asset.clone()], &supported)?;
+    if asset.amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let prices = get_prices(deps.as_ref(), env.clone())?;
+    let decimals = [contract_info.base_decimal, contract_info.quote_decimal];
+    let is_base = asset.info.equal(&supported[0]);
+    let index = if is_base { 0 } else { 1 };
+    let mut amounts = [FPDecimal::zero(), FPDecimal::zero()];
+    amounts[index] = FPDecimal::from(asset.amount).scaled(-(decimals[index] as i32));
+
+    let scaled_share = convert_to_shares(deps.as_ref(), env.clone(), amounts, prices, decimals)?;
+    let share = Uint128::new(u128::from(
+        scaled_share.scaled(contract_info.lp_decimal as i32),
+    ));
+    if share.is_zero() {
+        return Err(ContractError::ZeroShare {});
+    }
+
+    let receiver = addr_opt_validate(deps.api, &receiver)?.unwrap_or_else(|| info.sender.clone());
+    assert_not_treasury(&contract_info, &info.sender, &receiver)?;
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if total_share + share > contract_info.hardcap {
+        return Err(ContractError::ExceedHardcap {});
+    }
+    assert_asset_hardcaps(
+        deps.as_ref(),
+        &env,
+        &contract_info,
+        [Uint128::zero(), Uint128::zero()],
+    )?;
+    record_user_shares(deps.storage, &contract_info, &receiver, share)?;
+
+    let mut deposited = [Uint128::zero(), Uint128::zero()];
+    deposited[index] = asset.amount;
+    update_accounted_liquidity(deps.storage, deposited, [Uint128::zero(), Uint128::zero()])?;
+
+    let oracle_price = get_oracle_chain_price(&contract_info, prices);
+    let slippage =
+        FPDecimal::from(max_slippage_bps as u128) / FPDecimal::from(BPS_DENOMINATOR as u128);
+    let half_amount = FPDecimal::from(asset.amount) / FPDecimal::from(2u128);
+    // The worst price is rounded towards the oracle so the slippage bound still holds
+    let market = InjectiveQuerier::new(&deps.querier)
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?;
+    let (order_type, price, quantity) = if is_base {
+        let price = ceil_to_tick(
+            oracle_price * (FPDecimal::one() - slippage),
+            market.min_price_tick_size,
+        );
+        (
+            OrderType::Sell,
+            price,
+            floor_to_tick(half_amount, market.min_quantity_tick_size),
+        )
+    } else {
+        let price = floor_to_tick(
+            oracle_price * (FPDecimal::one() + slippage),
+            market.min_price_tick_size,
+        );
+        (
+            OrderType::Buy,
+            price,
+            floor_to_tick(half_amount / price, market.min_quantity_tick_size),
+        )
+    };
+    assert_order_valid(deps.as_ref(), &contract_info, price, quantity)?;
+    let order = SpotOrder::new(
+        price,
+        quantity,
+        order_type,
+        &contract_info.market_id,
+        contract_info.contract_subaccount_id.clone(),
+        Some(env.contract.address.clone()),
+    );
+
+    let mut messages = mint_liquidity_token_message(&contract_info, &receiver, share)?;
+    messages.push(create_spot_market_order_msg(env.contract.address, order));
+
+    Ok(Response::<InjectiveMsgWrapper>::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "deposit_and_rebalance"),
+            attr("sender", info.sender),
+            attr("receiver", receiver),
+            attr("assets", asset.to_string()),
+            attr("share", share),
+            attr("rebalance_price", price.to_string()),
+            attr("rebalance_quantity", quantity.to_string()),
+        ]))
+}
+
+fn assert_not_paused(contract_info: &ContractInfo) -> Result<(), ContractError> {
+    if contract_info.paused {
+        return Err(ContractError::Paused {});
+    }
+
+    Ok(())
+}
+
+/// Checks that the vault market is still active before placing an order on it.
+fn assert_market_active(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> Result<(), ContractError> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let status = querier
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .map(|market| market.status);
+    if status != Some(MarketStatus::Active) {
+        return Err(ContractError::MarketNotActive {
+            market_id: contract_info.market_id.as_str().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Rejects deposits before the configured `deposit_start_time`.
+fn assert_deposits_open(env: &Env, contract_info: &ContractInfo) -> Result<(), ContractError> {
+    if let Some(deposit_start_time) = contract_info.deposit_start_time {
+        if env.block.time.seconds() < deposit_start_time {
+            return Err(ContractError::CustomError {
+                val: "deposits not open yet".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Rejects deposits sent by or minting LP to the treasury, so fees it receives can't be
+/// recycled into shares.
+fn assert_not_treasury(
+    contract_info: &ContractInfo,
+    sender: &Addr,
+    receiver: &Addr,
+) -> Result<(), ContractError> {
+    match &contract_info.treasury {
+        Some(treasury) if treasury == sender || treasury == receiver => {
+            Err(ContractError::TreasuryDeposit {})
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Adds `share` to the LP minted to `receiver` by deposits, rejecting it past the per-user cap.
+fn record_user_shares(
+    storage: &mut dyn Storage,
+    contract_info: &ContractInfo,
+    receiver: &Addr,
+    share: Uint128,
+) -> Result<(), ContractError> {
+    let minted = USER_MINTED_SHARES
+        .may_load(storage, receiver)?
+        .unwrap_or_default()
+        + share;
+    if let Some(cap) = contract_info.per_user_cap {
+        if minted > cap {
+            return Err(ContractError::ExceedPerUserCap { cap });
+        }
+    }
+    USER_MINTED_SHARES.save(storage, receiver, &minted)?;
+
+    Ok(())
+}
+
+/// Checks that the vault balance of each asset stays within its cap once the deposit settles.
+///
+/// * **refunds** are the amounts that will be sent back to the depositor.
+fn assert_asset_hardcaps(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    refunds: [Uint128; 2],
+) -> Result<(), ContractError> {
+    if contract_info.base_hardcap.is_none() && contract_info.quote_hardcap.is_none() {
+        return Ok(());
+    }
+
+    let balances = get_vault_balances(deps, env)?;
+    let caps = [
+        (&contract_info.base_denom, contract_info.base_hardcap),
+        (&contract_info.quote_denom, contract_info.quote_hardcap),
+    ];
+    for (i, (denom, hardcap)) in caps.into_iter().enumerate() {
+        if let Some(hardcap) = hardcap {
+            if balances[i].saturating_sub(refunds[i]) > hardcap {
+                return Err(ContractError::ExceedAssetHardcap {
+                    denom: denom.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn try_swap(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    order_type: OrderType,
+    quantity: FPDecimal,
+    price: FPDecimal,
+    fund_subaccount: bool,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let buying = matches!(order_type, OrderType::Buy | OrderType::BuyPo);
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    assert_not_paused(&contract_info)?;
+
+    let oracle_price = assert_swap_risk(deps.as_ref(), &env, &contract_info, price)?;
+    let balances = get_vault_balances(deps.as_ref(), &env)?;
+    let bank_balances = get_bank_balances(deps.as_ref(), &env)?;
+    let contract = env.contract.address;
+    let subaccount_id = contract_info.contract_subaccount_id;
+    let min_amount = price * quantity;
+    if !info.funds.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "Do not provide funds!".to_string(),
+        });
+    }
+    assert_order_valid(deps.as_ref(), &contract_info, price, quantity)?;
+    let balance = FPDecimal::from(if buying { balances[1] } else { balances[0] });
+    if balance < min_amount {
+        return Err(ContractError::CustomError {
+            val: format!("Swap: {balance} below min_amount: {min_amount}"),
+        });
+    }
+    if matches!(order_type, OrderType::BuyPo | OrderType::SellPo) {
+        assert_post_only_price(deps.as_ref(), &contract_info, buying, price)?;
+    }
+    let order = SpotOrder::new(
+        price,
+        quantity,
+        order_type,
+        &contract_info.market_id,
+        subaccount_id.clone(),
+        Some(contract.to_owned()),
+    );
+
+    let mut response = Response::<InjectiveMsgWrapper>::new();
+    if fund_subaccount {
+        let deposit = if buying {
+            Coin::new(
+                u128::from(bank_balances[1]),
+                contract_info.quote_denom.clone(),
+            )
+        } else {
+            Coin::new(
+                u128::from(bank_balances[0]),
+                contract_info.base_denom.clone(),
+            )
+        };
+        response = response
+            .add_attribute("subaccount_deposit", deposit.to_string())
+            .add_message(create_deposit_msg(
+                contract.clone(),
+                subaccount_id.clone(),
+                deposit,
+            ));
+    }
+
+    if let Some(oracle_price) = oracle_price {
+        LAST_SWAP_ORACLE_PRICE.save(deps.storage, &oracle_price)?;
+    }
+    PENDING_ORDER.save(
+        deps.storage,
+        &OrderMeta {
+            buying,
+            price,
+            quantity,
+        },
+    )?;
+
+    let order_message = SubMsg::reply_on_success(
+        create_batch_update_orders_msg(
+            contract,
+            None,
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![order],
+            vec![],
+        ),
+        ORDER_REPLY_ID,
+    );
+
+    Ok(response.add_submessage(order_message))
+}
+
+/// Places all `orders` in one batch update. The combined notional of each side must be covered
+/// by the vault balance net of collected fees.
+fn try_swap_batch(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    orders: Vec<SpotOrderParams>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    assert_not_paused(&contract_info)?;
+    assert_market_active(deps.as_ref(), &contract_info)?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "Do not provide funds!".to_string(),
+        });
+    }
+    if orders.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "SwapBatch: no orders provided".to_string(),
+        });
+    }
+
+    if let Some(max_open_orders) = contract_info.max_open_orders {
+        let open_orders = get_live_orders(deps.as_ref(), &contract_info)?.len();
+        if open_orders + orders.len() > max_open_orders as usize {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "SwapBatch: {open_orders} open orders and {} new exceed max_open_orders",
+                    orders.len()
+                ),
+            });
+        }
+    }
+
+    let mut oracle_price = None;
+    for order in orders.iter() {
+        oracle_price = assert_swap_risk(deps.as_ref(), &env, &contract_info, order.price)?;
+        assert_tick_sizes(deps.as_ref(), &contract_info, order.price, order.quantity)?;
+        let notional = order.price * order.quantity;
+        if notional < contract_info.min_order_notional {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "SwapBatch: notional {notional} below min_order_notional: {}",
+                    contract_info.min_order_notional
+                ),
+            });
+        }
+    }
+
+    let notional_of = |buying: bool| {
+        orders
+            .iter()
+            .filter(|order| order.buying == buying)
+            .fold(FPDecimal::zero(), |total, order| {
+                total + order.price * order.quantity
+            })
+    };
+    let notionals = [notional_of(false), notional_of(true)];
+    let balances = get_vault_balances(deps.as_ref(), &env)?;
+    for (balance, notional) in balances.iter().zip(notionals) {
+        let balance = FPDecimal::from(*balance);
+        if balance < notional {
+            return Err(ContractError::CustomError {
+                val: format!("SwapBatch: {balance} below combined notional: {notional}"),
+            });
+        }
+    }
+
+    let contract = env.contract.address;
+    let spot_orders = orders
+        .iter()
+        .map(|order| {
+            SpotOrder::new(
+                order.price,
+                order.quantity,
+                spot_order_type(order.buying, false),
+                &contract_info.market_id,
+                contract_info.contract_subaccount_id.clone(),
+                Some(contract.to_owned()),
+            )
+        })
+        .collect();
+
+    if let Some(oracle_price) = oracle_price {
+        LAST_SWAP_ORACLE_PRICE.save(deps.storage, &oracle_price)?;
+    }
+    PENDING_BATCH_ORDERS.save(
+        deps.storage,
+        &orders
+            .iter()
+            .map(|order| OrderMeta {
+                buying: order.buying,
+                price: order.price,
+                quantity: order.quantity,
+            })
+            .collect::<Vec<_>>(),
+    )?;
+
+    let order_message = SubMsg::reply_on_success(
+        create_batch_update_orders_msg(
+            contract,
+            None,
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            spot_orders,
+            vec![],
+        ),
+        ORDER_REPLY_ID,
+    );
+
+    Ok(Response::new()
+        .add_submessage(order_message)
+        .add_attributes(vec![
+            attr("action", "swap_spot_batch"),
+            attr("orders", orders.len().to_string()),
+        ]))
+}
+
+/// Checks an order the vault places on its market: the market is active, the open order limit
+/// is not reached, price and quantity sit on the market ticks and the notional is large enough.
+fn assert_order_valid(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    price: FPDecimal,
+    quantity: FPDecimal,
+) -> Result<(), ContractError> {
+    assert_market_active(deps, contract_info)?;
+
+    if let Some(max_open_orders) = contract_info.max_open_orders {
+        let open_orders = get_live_orders(deps, contract_info)?.len();
+        if open_orders >= max_open_orders as usize {
+            return Err(ContractError::CustomError {
+                val: format!("Swap: {open_orders} open orders reached max_open_orders"),
+            });
+        }
+    }
+
+    assert_tick_sizes(deps, contract_info, price, quantity)?;
+    let notional = price * quantity;
+    if notional < contract_info.min_order_notional {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: notional {notional} below min_order_notional: {}",
+                contract_info.min_order_notional
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Rejects an order the exchange would fail for a price or quantity off the market ticks.
+fn assert_tick_sizes(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    price: FPDecimal,
+    quantity: FPDecimal,
+) -> Result<(), ContractError> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let market = querier
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?;
+
+    if !is_on_tick(price, market.min_price_tick_size) {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: price {price} is not a multiple of min_price_tick_size: {}",
+                market.min_price_tick_size
+            ),
+        });
+    }
+    if !is_on_tick(quantity, market.min_quantity_tick_size) {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Swap: quantity {quantity} is not a multiple of min_quantity_tick_size: {}",
+                market.min_quantity_tick_size
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+fn spot_order_type(buying: bool, post_only: bool) -> OrderType {
+    match (buying, post_only) {
+        (true, false) => OrderType::Buy,
+        (true, true) => OrderType::BuyPo,
+        (false, false) => OrderType::Sell,
+        (false, true) => OrderType::SellPo,
+    }
+}
+
+/// Rejects a post-only buy at or above the best ask and a post-only sell at or below the best
+/// bid. Skipped when the top of book can't be read, the exchange rejects crossing orders anyway.
+fn assert_post_only_price(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+    buying: bool,
+    price: FPDecimal,
+) -> Result<(), ContractError> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let tob = match querier.query_spot_market_mid_price_and_tob(&contract_info.market_id) {
+        Ok(tob) => tob,
+        Err(_) => return Ok(()),
+    };
+    if buying {
+        if let Some(best_sell_price) = tob.best_sell_price {
+            if price >= best_sell_price {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Swap: post-only buy price {price} crosses best ask {best_sell_price}"
+                    ),
+                });
+            }
+        }
+    } else if let Some(best_buy_price) = tob.best_buy_price {
+        if price <= best_buy_price {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Swap: post-only sell price {price} crosses best bid {best_buy_price}"
+                ),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the swap price against the configured risk parameters and returns the oracle price
+/// in chain units, `None` when no parameter is set.
+///
+/// * **max_slippage_bps** bounds the deviation of the order price from the oracle price.
+///
+/// * **max_spread_bps** bounds the confidence interval of each price feed.
+///
+/// * **max_price_jump_bps** bounds the oracle price move since the last swap.
+fn assert_swap_risk(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    price: FPDecimal,
+) -> Result<Option<FPDecimal>, ContractError> {
+    if contract_info.max_slippage_bps.is_none()
+        && contract_info.max_spread_bps.is_none()
+        && contract_info.max_price_jump_bps.is_none()
+    {
+        return Ok(None);
+    }
+
+    let prices = get_prices(deps, env.clone())?;
+    let oracle_price = get_oracle_chain_price(contract_info, prices);
+
+    if let Some(max_slippage_bps) = contract_info.max_slippage_bps {
+        if bps_diff(price, oracle_price) > FPDecimal::from(max_slippage_bps as u128) {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Swap: price deviates from oracle price by more than {max_slippage_bps} bps"
+                ),
+            });
+        }
+    }
+    if let Some(max_spread_bps) = contract_info.max_spread_bps {
+        let querier = InjectiveQuerier::new(&deps.querier);
+        for price_id in [&contract_info.base_price_id, &contract_info.quote_price_id] {
+            if let Some(pyth_price) = querier.query_pyth_price(price_id.as_str())?.price_state {
+                let spread = pyth_price.conf * FPDecimal::from(BPS_DENOMINATOR as u128)
+                    / pyth_price.price_state.price;
+                if spread > FPDecimal::from(max_spread_bps as u128) {
+                    return Err(ContractError::CustomError {
+                        val: format!("Swap: {price_id} spread is more than {max_spread_bps} bps"),
+                    });
+                }
+            }
+        }
+    }
+    if let Some(max_price_jump_bps) = contract_info.max_price_jump_bps {
+        if let Some(last_price) = LAST_SWAP_ORACLE_PRICE.may_load(deps.storage)? {
+            if bps_diff(oracle_price, last_price) > FPDecimal::from(max_price_jump_bps as u128) {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Swap: oracle price moved more than {max_price_jump_bps} bps since the last swap"
+                    ),
+                });
+            }
+        }
+    }
+
+    Ok(Some(oracle_price))
+}
+
+/// Returns the relative difference of `value` from `reference` in basis points.
+fn bps_diff(value: FPDecimal, reference: FPDecimal) -> FPDecimal {
+    let diff = if value > reference {
+        value - reference
+    } else {
+        reference - value
+    };
+    diff * FPDecimal::from(BPS_DENOMINATOR as u128) / reference
+}
+
+/// Places a swap priced off the oracle, paying up to `buffer_bps` above it when buying and
+/// accepting down to `buffer_bps` below it when selling.
+fn try_swap_at_oracle(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    buying: bool,
+    quantity: FPDecimal,
+    buffer_bps: u64,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if buffer_bps >= BPS_DENOMINATOR {
+        return Err(ContractError::CustomError {
+            val: format!("buffer_bps must be below {BPS_DENOMINATOR}"),
+        });
+    }
+
+    let (prices, price_source) = get_prices_with_source(deps.as_ref(), &env)?;
+    let oracle_price = get_oracle_chain_price(&contract_info, prices);
+    let buffer = FPDecimal::from(buffer_bps as u128) / FPDecimal::from(BPS_DENOMINATOR as u128);
+    let price = if buying {
+        oracle_price * (FPDecimal::one() + buffer)
+    } else {
+        oracle_price * (FPDecimal::one() - buffer)
+    };
+
+    Ok(try_swap(
+        deps,
+        env,
+        info,
+        spot_order_type(buying, false),
+        quantity,
+        price,
+        false,
+    )?
+    .add_attributes(vec![
+        attr("action", "swap_at_oracle"),
+        attr("price", price.to_string()),
+        attr("price_source", price_source.to_string()),
+    ]))
+}
+
+/// Returns the oracle price of the base in quote, in chain units.
+fn get_oracle_chain_price(contract_info: &ContractInfo, prices: [FPDecimal; 2]) -> FPDecimal {
+    (prices[0] / prices[1])
+        .scaled(contract_info.quote_decimal as i32 - contract_info.base_decimal as i32)
+}
+
+fn try_cancel_order(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    order_hash: String,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let contract = env.contract.address;
+    let subaccount_id = contract_info.contract_subaccount_id;
+
+    // The order stays tracked until the exchange confirms the cancel
+    PENDING_CANCEL.save(deps.storage, &vec![order_hash.clone()])?;
+
+    let cancel_message = SubMsg::reply_on_success(
+        cancel_order_msg(
+            MarketType::Spot,
+            contract,
+            contract_info.market_id.clone(),
+            subaccount_id.clone(),
+            order_hash.clone(),
+        ),
+        CANCEL_REPLY_ID,
+    );
+    let response = Response::<InjectiveMsgWrapper>::new()
+        .add_submessage(cancel_message)
+        .add_attributes(vec![
+            attr("action", "cancel_order"),
+            attr("order_hash", order_hash),
+            attr("market_id", contract_info.market_id.as_str()),
+        ]);
+
+    Ok(response)
+}
+
+/// Cancels several spot orders of the vault subaccount in a single batch update.
+fn try_batch_cancel_orders(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    hashes: Vec<String>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if hashes.is_empty() || hashes.len() > MAX_BATCH_CANCEL {
+        return Err(ContractError::CustomError {
+            val: format!("Number of hashes must be between 1 and {MAX_BATCH_CANCEL}"),
+        });
+    }
+
+    // The orders stay tracked until the exchange confirms the cancels
+    PENDING_CANCEL.save(deps.storage, &hashes)?;
+
+    let contract = env.contract.address;
+    let subaccount_id = contract_info.contract_subaccount_id;
+    let orders_to_cancel = hashes
+        .into_iter()
+        .map(|order_hash| OrderData {
+            market_id: contract_info.market_id.clone(),
+            subaccount_id: subaccount_id.clone(),
+            order_hash,
+            order_mask: ORDER_MASK_ANY,
+        })
+        .collect();
+
+    let cancel_message = SubMsg::reply_on_success(
+        create_batch_update_orders_msg(
+            contract,
+            None,
+            vec![],
+            vec![],
+            orders_to_cancel,
+            vec![],
+            vec![],
+            vec![],
+        ),
+        CANCEL_REPLY_ID,
+    );
+    let response = Response::<InjectiveMsgWrapper>::new().add_submessage(cancel_message);
+
+    Ok(response)
+}
+
+fn try_cancel_all_orders(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // The orders stay tracked until the exchange confirms the cancels
+    let order_hashes = OPEN_ORDERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    PENDING_CANCEL.save(deps.storage, &order_hashes)?;
+
+    let cancel_message = SubMsg::reply_on_success(
+        create_batch_update_orders_msg(
+            env.contract.address,
+            Some(contract_info.contract_subaccount_id),
+            vec![contract_info.market_id.clone()],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        ),
+        CANCEL_REPLY_ID,
+    );
+    let response = Response::<InjectiveMsgWrapper>::new()
+        .add_submessage(cancel_message)
+        .add_attributes(vec![
+            attr("action", "cancel_all_orders"),
+            attr("market_id", contract_info.market_id.as_str()),
+        ]);
+
+    Ok(response)
+}
+
+fn add_fee(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    base_fee: Uint128,
+    quote_fee: Uint128,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let base_fee_collected = BASE_FEE_COLLECTED.load(deps.storage)?;
+    let quote_fee_collected = QUOTE_FEE_COLLECTED.load(deps.storage)?;
+
+    BASE_FEE_COLLECTED.save(deps.storage, &(base_fee_collected + base_fee))?;
+    QUOTE_FEE_COLLECTED.save(deps.storage, &(quote_fee_collected + quote_fee))?;
+    // Fees are taken out of the LP liquidity
+    update_accounted_liquidity(
+        deps.storage,
+        [Uint128::zero(), Uint128::zero()],
+        [base_fee, quote_fee],
+    )?;
+
+    Ok(Response::default())
+}
+
+fn withdraw_fee(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    base_fee: Uint128,
+    quote_fee: Uint128,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if base_fee.is_zero() && quote_fee.is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("Can't withdraw zero fees"),
+        });
+    }
+
+    let base_fee_collected = BASE_FEE_COLLECTED.load(deps.storage)?;
+    let quote_fee_collected = QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    if base_fee_collected < base_fee || quote_fee_collected < quote_fee {
+        return Err(ContractError::InsufficientFeeAccrued {});
+    }
+
+    BASE_FEE_COLLECTED.save(deps.storage, &(base_fee_collected - base_fee))?;
+    QUOTE_FEE_COLLECTED.save(deps.storage, &(quote_fee_collected - quote_fee))?;
+
+    let mut fees: Vec<Coin> = vec![];
+    if !base_fee.is_zero() {
+        fees.push(Coin::new(
+            u128::from(base_fee),
+            contract_info.base_denom.clone(),
+        ));
+    }
+    if !quote_fee.is_zero() {
+        fees.push(Coin::new(
+            u128::from(quote_fee),
+            contract_info.quote_denom.clone(),
+        ));
+    }
+
+    let recipient = contract_info.treasury.clone().unwrap_or(info.sender);
+    let msgs = vec![BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: fees,
+    }];
+
+    Ok(Response::default().add_messages(msgs).add_attribute(
+        "fee_withdrawn",
+        format!(
+            "{}, {}",
+            Asset {
+                amount: base_fee,
+                info: AssetInfo::NativeToken {
+                    denom: contract_info.base_denom
+                },
+            },
+            Asset {
+                amount: quote_fee,
+                info: AssetInfo::NativeToken {
+                    denom: contract_info.quote_denom
+                },
+            }
+        ),
+    ))
+}
+
+fn forward_fees(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    fee_vault: String,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    let fee_vault = deps.api.addr_validate(&fee_vault)?;
+
+    let base_fee = BASE_FEE_COLLECTED.load(deps.storage)?;
+    let quote_fee = QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    let residue = RESIDUE_COLLECTED
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if base_fee.is_zero() && quote_fee.is_zero() && residue.is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("No fees to forward"),
+        });
+    }
+
+    BASE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
+    QUOTE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
+    RESIDUE_COLLECTED.save(deps.storage, &Uint128::zero())?;
+
+    let mut fees: Vec<Coin> = vec![];
+    if !base_fee.is_zero() {
+        fees.push(Coin::new(
+            u128::from(base_fee),
+            contract_info.base_denom.clone(),
+        ));
+    }
+    if !quote_fee.is_zero() {
+        fees.push(Coin::new(
+            u128::from(quote_fee),
+            contract_info.quote_denom.clone(),
+        ));
+    }
+    if let (Some(residue_denom), false) = (get_residue_denom(&contract_info), residue.is_zero()) {
+        fees.push(Coin::new(u128::from(residue), residue_denom));
+    }
+    // The bank module expects coins sorted by denom
+    fees.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    let msgs = vec![BankMsg::Send {
+        to_address: fee_vault.to_string(),
+        amount: fees,
+    }];
+
+    Ok(Response::default().add_messages(msgs).add_attributes(vec![
+        attr("action", "forward_fees"),
+        attr("fee_vault", fee_vault.to_string()),
+        attr(
+            "fee_forwarded",
+            format!(
+                "{}, {}",
+                Asset {
+                    amount: base_fee,
+                    info: AssetInfo::NativeToken {
+                        denom: contract_info.base_denom
+                    },
+                },
+                Asset {
+                    amount: quote_fee,
+                    info: AssetInfo::NativeToken {
+                        denom: contract_info.quote_denom
+                    },
+                }
+            ),
+        ),
+        attr("residue_forwarded", residue),
+    ]))
+}
+
+/// Moves the INJ residue not yet collected into [`RESIDUE_COLLECTED`], so withdrawals stop
+/// refunding it and [`forward_fees`] sends it to the fee vault.
+fn collect_residue(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
-    BASE_FEE_COLLECTED.save(deps.storage, &(base_fee_collected - base_fee))?;
-    QUOTE_FEE_COLLECTED.save(deps.storage, &(quote_fee_collected - quote_fee))?;
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    let residue_denom = match get_residue_denom(&contract_info) {
+        Some(residue_denom) => residue_denom,
+        None => {
+            return Err(ContractError::CustomError {
+                val: "INJ is a pool denom, there is no residue".to_string(),
+            })
+        }
+    };
 
-    let mut fees: Vec<Coin> = vec![];
-    if !base_fee.is_zero() {
-        fees.push(Coin::new(
-            u128::from(base_fee),
+    let balance = query_balance(
+        &deps.querier,
+        env.contract.address.to_string(),
+        &residue_denom,
+    )?;
+    let collected = RESIDUE_COLLECTED
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let uncollected = balance.saturating_sub(collected);
+    if uncollected.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "No residue to collect".to_string(),
+        });
+    }
+    RESIDUE_COLLECTED.save(deps.storage, &balance)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "collect_residue"),
+        attr("residue_collected", uncollected),
+    ]))
+}
+
+/// Sends the whole balance of a denom sent to the vault by mistake to `recipient`. The pool
+/// denoms and the INJ residue belong to LP holders and can't be swept.
+fn sweep_token(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    recipient: String,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if denom == contract_info.base_denom || denom == contract_info.quote_denom || denom == "INJ" {
+        return Err(ContractError::CustomError {
+            val: format!("Can't sweep vault denom {denom}"),
+        });
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let amount = query_balance(&deps.querier, env.contract.address.to_string(), &denom)?;
+    if amount.is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("No {denom} balance to sweep"),
+        });
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin::new(u128::from(amount), denom.clone())],
+        })
+        .add_attributes(vec![
+            attr("action", "sweep_token"),
+            attr("recipient", recipient),
+            attr("swept", format!("{amount}{denom}")),
+        ]))
+}
+
+/// Returns the denom withdrawers get a share of on top of the pool assets, `None` when it is
+/// one of them.
+fn get_residue_denom(contract_info: &ContractInfo) -> Option<String> {
+    let fee_denom = "INJ".to_string();
+    if contract_info.base_denom != fee_denom && contract_info.quote_denom != fee_denom {
+        Some(fee_denom)
+    } else {
+        None
+    }
+}
+
+/// Winds the vault down once it holds the whole LP supply, burning its own LP and sending the
+/// base and quote balances, collected fees included, to the owner. Credited overpayments stay
+/// claimable.
+fn burn_contract_lp(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    let contract_share = query_token_balance(
+        &deps.querier,
+        &contract_info.liquidity_token,
+        env.contract.address.to_string(),
+    )?;
+    if contract_share.is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("No contract LP to burn"),
+        });
+    }
+    if contract_share != total_share {
+        return Err(ContractError::CustomError {
+            val: format!("LP holders remain: {}", total_share - contract_share),
+        });
+    }
+
+    let total_credits = TOTAL_CREDITS.may_load(deps.storage)?.unwrap_or_default();
+    let mut swept: Vec<Coin> = vec![];
+    for (denom, credit) in [
+        (&contract_info.base_denom, total_credits[0]),
+        (&contract_info.quote_denom, total_credits[1]),
+    ] {
+        let amount = query_balance(&deps.querier, env.contract.address.to_string(), denom)?
+            .saturating_sub(credit);
+        if !amount.is_zero() {
+            swept.push(Coin::new(u128::from(amount), denom.clone()));
+        }
+    }
+
+    BASE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
+    QUOTE_FEE_COLLECTED.save(deps.storage, &Uint128::zero())?;
+    ACCOUNTED_LIQUIDITY.save(deps.storage, &[Uint128::zero(), Uint128::zero()])?;
+
+    let mut messages: Vec<CosmosMsg<InjectiveMsgWrapper>> =
+        vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_info.liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: contract_share,
+            })?,
+            funds: vec![],
+        })];
+    if !swept.is_empty() {
+        messages.push(
+            BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: swept,
+            }
+            .into(),
+        );
+    }
+
+    Ok(Response::<InjectiveMsgWrapper>::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "burn_contract_lp"),
+            attr("burnt_share", contract_share),
+        ]))
+}
+
+fn update_asset_hardcaps(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    base_hardcap: Option<Uint128>,
+    quote_hardcap: Option<Uint128>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.base_hardcap = base_hardcap;
+    contract_info.quote_hardcap = quote_hardcap;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_asset_hardcaps"),
+        attr(
+            "base_hardcap",
+            base_hardcap.map_or("none".to_string(), |cap| cap.to_string()),
+        ),
+        attr(
+            "quote_hardcap",
+            quote_hardcap.map_or("none".to_string(), |cap| cap.to_string()),
+        ),
+    ]))
+}
+
+/// Moves the accounted LP liquidity by the `added` and `removed` base and quote amounts.
+///
+/// Removals saturate at zero, as vaults deployed before the liquidity was accounted start
+/// from zero.
+fn update_accounted_liquidity(
+    storage: &mut dyn Storage,
+    added: [Uint128; 2],
+    removed: [Uint128; 2],
+) -> StdResult<()> {
+    let accounted = ACCOUNTED_LIQUIDITY.may_load(storage)?.unwrap_or_default();
+    ACCOUNTED_LIQUIDITY.save(
+        storage,
+        &[
+            (accounted[0] + added[0]).saturating_sub(removed[0]),
+            (accounted[1] + added[1]).saturating_sub(removed[1]),
+        ],
+    )
+}
+
+/// Adds deposit overpayment to the user's claimable credit.
+fn credit_overpayment(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    amounts: [Uint128; 2],
+) -> StdResult<()> {
+    if amounts[0].is_zero() && amounts[1].is_zero() {
+        return Ok(());
+    }
+
+    let credit = CREDITS.may_load(storage, user)?.unwrap_or_default();
+    CREDITS.save(
+        storage,
+        user,
+        &[credit[0] + amounts[0], credit[1] + amounts[1]],
+    )?;
+    let total_credits = TOTAL_CREDITS.may_load(storage)?.unwrap_or_default();
+    TOTAL_CREDITS.save(
+        storage,
+        &[total_credits[0] + amounts[0], total_credits[1] + amounts[1]],
+    )?;
+
+    Ok(())
+}
+
+/// Sends the credited deposit overpayment back to the caller.
+fn claim_credit(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    let credit = CREDITS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if credit[0].is_zero() && credit[1].is_zero() {
+        return Err(ContractError::CustomError {
+            val: format!("No credit to claim"),
+        });
+    }
+
+    CREDITS.remove(deps.storage, &info.sender);
+    let total_credits = TOTAL_CREDITS.load(deps.storage)?;
+    TOTAL_CREDITS.save(
+        deps.storage,
+        &[total_credits[0] - credit[0], total_credits[1] - credit[1]],
+    )?;
+
+    let mut amounts: Vec<Coin> = vec![];
+    if !credit[0].is_zero() {
+        amounts.push(Coin::new(
+            u128::from(credit[0]),
             contract_info.base_denom.clone(),
         ));
     }
-    if !quote_fee.is_zero() {
-        fees.push(Coin::new(
-            u128::from(quote_fee),
+    if !credit[1].is_zero() {
+        amounts.push(Coin::new(
+            u128::from(credit[1]),
             contract_info.quote_denom.clone(),
         ));
     }
 
-    let msgs = vec![BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: fees,
-    }];
+    Ok(Response::<InjectiveMsgWrapper>::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: amounts,
+        })
+        .add_attributes(vec![
+            attr("action", "claim_credit"),
+            attr("sender", info.sender),
+            attr(
+                "credit",
+                format!(
+                    "{}, {}",
+                    Asset {
+                        amount: credit[0],
+                        info: AssetInfo::NativeToken {
+                            denom: contract_info.base_denom
+                        },
+                    },
+                    Asset {
+                        amount: credit[1],
+                        info: AssetInfo::NativeToken {
+                            denom: contract_info.quote_denom
+                        },
+                    }
+                ),
+            ),
+        ]))
+}
+
+/// Verifies that both price feeds are fresh and positive, reporting the current prices.
+fn check_prices(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    env: Env,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    let price_states = get_price_states(deps.as_ref(), &env)?;
+
+    Ok(Response::<InjectiveMsgWrapper>::new().add_attributes(vec![
+        attr("action", "check_prices"),
+        attr("base_price", price_states[0].price.to_string()),
+        attr("base_timestamp", price_states[0].timestamp.to_string()),
+        attr("quote_price", price_states[1].price.to_string()),
+        attr("quote_timestamp", price_states[1].timestamp.to_string()),
+    ]))
+}
+
+fn update_min_order_notional(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    min_order_notional: FPDecimal,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.min_order_notional = min_order_notional;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_min_order_notional"),
+        attr("min_order_notional", min_order_notional.to_string()),
+    ]))
+}
+
+fn update_max_conf_ratio(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    max_conf_ratio: FPDecimal,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if max_conf_ratio <= FPDecimal::zero() || max_conf_ratio > FPDecimal::one() {
+        return Err(ContractError::CustomError {
+            val: format!("max_conf_ratio must be in (0, 1]: {max_conf_ratio}"),
+        });
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.max_conf_ratio = max_conf_ratio;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_max_conf_ratio"),
+        attr("max_conf_ratio", max_conf_ratio.to_string()),
+    ]))
+}
+
+fn update_max_open_orders(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    max_open_orders: Option<u32>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.max_open_orders = max_open_orders;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_max_open_orders"),
+        attr(
+            "max_open_orders",
+            max_open_orders.map_or("none".to_string(), |max| max.to_string()),
+        ),
+    ]))
+}
+
+fn update_per_user_cap(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    per_user_cap: Option<Uint128>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.per_user_cap = per_user_cap;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_per_user_cap"),
+        attr(
+            "per_user_cap",
+            per_user_cap.map_or("none".to_string(), |cap| cap.to_string()),
+        ),
+    ]))
+}
+
+fn update_treasury(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    treasury: Option<String>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.treasury = addr_opt_validate(deps.api, &treasury)?;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_treasury"),
+        attr("treasury", treasury.unwrap_or_else(|| "none".to_string())),
+    ]))
+}
+
+fn set_paused(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    contract_info.paused = paused;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_paused"),
+        attr("paused", paused.to_string()),
+    ]))
+}
+
+fn update_hardcap(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    hardcap: Uint128,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if hardcap < total_share {
+        return Err(ContractError::CustomError {
+            val: format!("Hardcap {hardcap} below current supply: {total_share}"),
+        });
+    }
+    contract_info.hardcap = hardcap;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_hardcap"),
+        attr("hardcap", hardcap),
+    ]))
+}
+
+fn update_price_ids(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    base_price_id: String,
+    quote_price_id: String,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+    assert_valid_price_ids(deps.as_ref(), &base_price_id, &quote_price_id)?;
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_base_price_id =
+        std::mem::replace(&mut contract_info.base_price_id, base_price_id.clone());
+    let old_quote_price_id =
+        std::mem::replace(&mut contract_info.quote_price_id, quote_price_id.clone());
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
 
-    Ok(Response::default().add_messages(msgs).add_attribute(
-        "fee_withdrawn",
-        format!(
-            "{}, {}",
-            Asset {
-                amount: base_fee,
-                info: AssetInfo {
-                    denom: contract_info.base_denom
-                },
-            },
-            Asset {
-                amount: quote_fee,
-                info: AssetInfo {
-                    denom: contract_info.quote_denom
-                },
-            }
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_price_ids"),
+        attr("old_base_price_id", old_base_price_id),
+        attr("base_price_id", base_price_id),
+        attr("old_quote_price_id", old_quote_price_id),
+        attr("quote_price_id", quote_price_id),
+    ]))
+}
+
+/// Surfaces a malformed deposit denom as [`ContractError::InvalidDenom`].
+fn assert_valid_denom(api: &dyn Api, asset_info: &AssetInfo) -> Result<(), ContractError> {
+    asset_info
+        .check(api)
+        .map_err(|_| ContractError::InvalidDenom {
+            denom: asset_info.to_string(),
+        })
+}
+
+fn update_risk_params(
+    deps: DepsMut<InjectiveQueryWrapper>,
+    _env: Env,
+    info: MessageInfo,
+    max_slippage_bps: Option<u64>,
+    max_spread_bps: Option<u64>,
+    max_price_jump_bps: Option<u64>,
+) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
+    if !is_owner(deps.storage, &info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let mut attributes = vec![attr("action", "update_risk_params")];
+    for (name, value, param) in [
+        (
+            "max_slippage_bps",
+            max_slippage_bps,
+            &mut contract_info.max_slippage_bps,
         ),
-    ))
+        (
+            "max_spread_bps",
+            max_spread_bps,
+            &mut contract_info.max_spread_bps,
+        ),
+        (
+            "max_price_jump_bps",
+            max_price_jump_bps,
+            &mut contract_info.max_price_jump_bps,
+        ),
+    ] {
+        if let Some(value) = value {
+            if value > BPS_DENOMINATOR {
+                return Err(ContractError::CustomError {
+                    val: format!("{name} must not exceed {BPS_DENOMINATOR}"),
+                });
+            }
+            *param = Some(value);
+            attributes.push(attr(name, value.to_string()));
+        }
+    }
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new().add_attributes(attributes))
 }
 
 /// Mint LP tokens for a beneficiary.
@@ -600,13 +2416,17 @@ fn mint_liquidity_token_message(
 /// Withdraw tokens from the pool.
 /// * **sender** is the address that will receive assets back from the vault contract.
 ///
-/// * **share_amount** is the amount of LP tokens to burn.
+/// * **share_amount** is the amount of LP tokens to burn. Shares the free balance can't cover
+/// are returned to the sender.
 fn withdraw(
     deps: DepsMut<InjectiveQueryWrapper>,
     env: Env,
     info: MessageInfo,
     sender: Addr,
     share_amount: Uint128,
+    to_subaccount: Option<SubaccountId>,
+    max_imbalance_bps: Option<u64>,
+    target_denom: Option<String>,
 ) -> Result<Response<InjectiveMsgWrapper>, ContractError> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
@@ -619,130 +2439,830 @@ fn withdraw(
         });
     }
 
-    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
-    let refund_assets = get_share_in_assets(deps.as_ref(), env, share_amount, total_share)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    let (mut refund_assets, mut residue) =
+        get_share_in_assets(deps.as_ref(), env.clone(), share_amount, total_share)?;
+
+    // Refunds are paid from the bank and can't use the balance committed to resting orders. When a
+    // side is short, only the shares covered by the free balance are burned and the rest are
+    // returned to the sender.
+    let balances = get_bank_balances(deps.as_ref(), &env)?;
+    let committed = get_order_committed_amounts(deps.as_ref(), &contract_info)?;
+    let free_balances = [
+        balances[0].saturating_sub(committed[0]),
+        balances[1].saturating_sub(committed[1]),
+    ];
+    let burn_amount = refund_assets
+        .iter()
+        .zip(free_balances)
+        .filter(|(refund_asset, free_balance)| refund_asset.amount > *free_balance)
+        .map(|(refund_asset, free_balance)| {
+            share_amount.multiply_ratio(free_balance, refund_asset.amount)
+        })
+        .min()
+        .unwrap_or(share_amount);
+    let partial_withdrawal = burn_amount < share_amount;
+    if partial_withdrawal {
+        if burn_amount.is_zero() {
+            return Err(ContractError::CustomError {
+                val: "Withdraw: no free balance to refund".to_string(),
+            });
+        }
+        (refund_assets, residue) =
+            get_share_in_assets(deps.as_ref(), env.clone(), burn_amount, total_share)?;
+        for (refund_asset, free_balance) in refund_assets.iter_mut().zip(free_balances) {
+            refund_asset.amount = min(refund_asset.amount, free_balance);
+        }
+    }
+    if let Some(max_imbalance_bps) = max_imbalance_bps {
+        assert_refund_balanced(
+            deps.as_ref(),
+            &env,
+            &contract_info,
+            &refund_assets,
+            max_imbalance_bps,
+        )?;
+    }
+    if let Some(target_denom) = &target_denom {
+        consolidate_refund(
+            deps.as_ref(),
+            &env,
+            &contract_info,
+            &mut refund_assets,
+            target_denom,
+            free_balances,
+        )?;
+    }
+
+    update_accounted_liquidity(
+        deps.storage,
+        [Uint128::zero(), Uint128::zero()],
+        [refund_assets[0].amount, refund_assets[1].amount],
+    )?;
+
+    let mut messages: Vec<CosmosMsg<InjectiveMsgWrapper>> =
+        vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_info.liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: burn_amount,
+            })?,
+            funds: vec![],
+        })];
+    for refund_asset in refund_assets
+        .iter()
+        .chain(residue.iter())
+        .filter(|a| !a.amount.is_zero())
+    {
+        messages.push(match &to_subaccount {
+            Some(subaccount_id) => create_deposit_msg(
+                env.contract.address.clone(),
+                subaccount_id.clone(),
+                refund_asset.as_coin()?,
+            ),
+            None => refund_asset.clone().into_msg(sender.clone())?,
+        });
+    }
+    let returned_share = share_amount - burn_amount;
+    if !returned_share.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_info.liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: sender.to_string(),
+                amount: returned_share,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    let mut attributes = vec![
+        attr("action", "withdraw"),
+        attr("sender", sender),
+        attr("withdrawn_share", burn_amount),
+        attr(
+            "refund_assets",
+            format!("{}, {}", refund_assets[0], refund_assets[1]),
+        ),
+    ];
+    if partial_withdrawal {
+        attributes.push(attr("partial_withdrawal", "true"));
+        attributes.push(attr("returned_share", returned_share));
+    }
+    if let Some(target_denom) = target_denom {
+        let consolidated = refund_assets
+            .iter()
+            .find(|a| a.info.to_string() == target_denom)
+            .map(|a| a.amount)
+            .unwrap_or_default();
+        attributes.push(attr("consolidated_amount", consolidated));
+    }
+
+    Ok(Response::<InjectiveMsgWrapper>::new()
+        .add_messages(messages)
+        .add_attributes(attributes))
+}
+
+/// Moves the whole refund into `target_denom`. The other side is valued at the oracle price less
+/// `max_slippage_bps`, the cost the vault would pay to trade it back, so the remaining LPs don't
+/// fund the conversion. Consolidation is rejected while `max_slippage_bps` is not set, and the
+/// converted amount may use at most `MAX_CONSOLIDATION_BPS` of the free target balance.
+fn consolidate_refund(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    refund_assets: &mut [Asset; 2],
+    target_denom: &str,
+    free_balances: [Uint128; 2],
+) -> Result<(), ContractError> {
+    let target = if target_denom == contract_info.base_denom {
+        0
+    } else if target_denom == contract_info.quote_denom {
+        1
+    } else {
+        return Err(ContractError::CustomError {
+            val: format!("Withdraw: target denom {target_denom} is not a pool denom"),
+        });
+    };
+    let other = 1 - target;
+    let decimals = [contract_info.base_decimal, contract_info.quote_decimal];
+    let max_slippage_bps =
+        contract_info
+            .max_slippage_bps
+            .ok_or_else(|| ContractError::CustomError {
+                val: "Withdraw: consolidation requires max_slippage_bps".to_string(),
+            })?;
+    let slippage =
+        FPDecimal::from(max_slippage_bps as u128) / FPDecimal::from(BPS_DENOMINATOR as u128);
+
+    let prices = get_prices(deps, env.clone())?;
+    let other_value = FPDecimal::from(refund_assets[other].amount)
+        .scaled(-(decimals[other] as i32))
+        * prices[other]
+        * (FPDecimal::one() - slippage);
+    let converted = Uint128::new(u128::from(
+        (other_value / prices[target]).scaled(decimals[target] as i32),
+    ));
+    let max_converted =
+        free_balances[target].multiply_ratio(MAX_CONSOLIDATION_BPS, BPS_DENOMINATOR);
+    if converted > max_converted {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Withdraw: converted amount {converted} exceeds {MAX_CONSOLIDATION_BPS} bps of the free {target_denom} balance"
+            ),
+        });
+    }
+    let consolidated = refund_assets[target].amount + converted;
+    if consolidated > free_balances[target] {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Withdraw: consolidated amount {consolidated} exceeds free {target_denom} balance: {}",
+                free_balances[target]
+            ),
+        });
+    }
+
+    refund_assets[target].amount = consolidated;
+    refund_assets[other].amount = Uint128::zero();
+    Ok(())
+}
+
+/// Returns the vault's orders still resting on the exchange. Unlike `OPEN_ORDERS`, filled
+/// orders and orders cancelled on the exchange directly are not listed.
+fn get_live_orders(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<Vec<TrimmedSpotLimitOrder>> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    Ok(querier
+        .query_trader_spot_orders(
+            &contract_info.market_id,
+            &contract_info.contract_subaccount_id,
+        )?
+        .orders
+        .unwrap_or_default())
+}
+
+/// Returns the base and quote amounts committed to the unfilled part of the vault's resting
+/// orders, sells lock base and buys lock quote.
+fn get_order_committed_amounts(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<[Uint128; 2]> {
+    let orders = get_live_orders(deps, contract_info)?;
+
+    let [base, quote] = orders.into_iter().fold(
+        [FPDecimal::zero(), FPDecimal::zero()],
+        |[base, quote], order| {
+            if order.isBuy {
+                [base, quote + order.price * order.fillable]
+            } else {
+                [base + order.fillable, quote]
+            }
+        },
+    );
+    Ok([
+        Uint128::new(u128::from(base)),
+        Uint128::new(u128::from(quote)),
+    ])
+}
+
+/// Checks the oracle values of the base and quote refunds differ by at most `max_imbalance_bps`
+/// of their total.
+fn assert_refund_balanced(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+    contract_info: &ContractInfo,
+    refund_assets: &[Asset; 2],
+    max_imbalance_bps: u64,
+) -> Result<(), ContractError> {
+    let prices = get_prices(deps, env.clone())?;
+    let values = [
+        FPDecimal::from(refund_assets[0].amount).scaled(-(contract_info.base_decimal as i32))
+            * prices[0],
+        FPDecimal::from(refund_assets[1].amount).scaled(-(contract_info.quote_decimal as i32))
+            * prices[1],
+    ];
+    let total_value = values[0] + values[1];
+    if total_value.is_zero() {
+        return Ok(());
+    }
+
+    let diff = if values[0] > values[1] {
+        values[0] - values[1]
+    } else {
+        values[1] - values[0]
+    };
+    let imbalance_bps = diff * FPDecimal::from(BPS_DENOMINATOR as u128) / total_value;
+    if imbalance_bps > FPDecimal::from(max_imbalance_bps as u128) {
+        return Err(ContractError::CustomError {
+            val: format!(
+                "Withdraw: refund imbalance {imbalance_bps} bps exceeds {max_imbalance_bps} bps"
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps<InjectiveQueryWrapper>, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Ownership {} => to_binary(&get_ownership(deps.storage)?),
+        QueryMsg::TokensForShares { share } => to_binary(&get_tokens_for_shares(deps, env, share)?),
+        QueryMsg::TotalLiquidity {} => to_binary(&get_total_liquidity(deps, env)?),
+        QueryMsg::UserLiquidity { user } => to_binary(&get_user_liquidity(deps, env, user)?),
+        QueryMsg::Prices {} => to_binary(&query_prices(deps, env)?),
+        QueryMsg::SpotPriceFromOracle {} => to_binary(&query_spot_price_from_oracle(deps, env)?),
+        QueryMsg::Tokens {} => to_binary(&query_tokens(deps.storage)?),
+        QueryMsg::LockedLiquidity {} => to_binary(&query_locked_liquidity(deps, env)?),
+        QueryMsg::Credit { user } => to_binary(&query_credit(deps, user)?),
+        QueryMsg::FeeState {} => to_binary(&query_fee_state(deps)?),
+        QueryMsg::EffectiveFeeRate {} => to_binary(&query_effective_fee_rate(deps)?),
+        QueryMsg::InventorySkew {} => to_binary(&query_inventory_skew(deps, env)?),
+        QueryMsg::MarketInfo {} => to_binary(&query_market_info(deps)?),
+        QueryMsg::PriceSource {} => to_binary(&get_prices_with_source(deps, &env)?.1),
+        QueryMsg::RiskParams {} => to_binary(&query_risk_params(deps)?),
+        QueryMsg::EffectiveHardcap {} => to_binary(&query_effective_hardcap(deps, env)?),
+        QueryMsg::MakerVolume {} => to_binary(&query_maker_volume(deps)?),
+        QueryMsg::OwnerFeeClaimable {} => to_binary(&query_owner_fee_claimable(deps, env)?),
+        QueryMsg::ValidateDeposit { assets, receiver } => {
+            to_binary(&query_validate_deposit(deps, env, assets, receiver)?)
+        }
+        QueryMsg::PendingOrderNotional {} => to_binary(&query_pending_order_notional(deps)?),
+        QueryMsg::SimulateDeposit { assets } => {
+            to_binary(&query_simulate_deposit(deps, env, assets)?)
+        }
+        QueryMsg::WithdrawableAssets { user } => {
+            to_binary(&query_withdrawable_assets(deps, env, user)?)
+        }
+        QueryMsg::CanWithdraw { user, amount } => {
+            to_binary(&query_can_withdraw(deps, env, user, amount)?)
+        }
+        QueryMsg::SimulateWithdraw { share } => {
+            to_binary(&query_simulate_withdraw(deps, env, share)?)
+        }
+        QueryMsg::WithdrawCurve { min, max, steps } => {
+            to_binary(&query_withdraw_curve(deps, env, min, max, steps)?)
+        }
+        QueryMsg::InstantiateParams {} => to_binary(&INSTANTIATE_PARAMS.load(deps.storage)?),
+        QueryMsg::Config {} => to_binary(&CONTRACT_INFO.load(deps.storage)?),
+        QueryMsg::BalanceReconciliation {} => to_binary(&query_balance_reconciliation(deps, env)?),
+        QueryMsg::HasExposure {} => to_binary(&query_has_exposure(deps)?),
+        QueryMsg::OpenOrders {} => to_binary(&query_open_orders(deps)?),
+    }
+}
+
+fn get_tokens_for_shares(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    share: Uint128,
+) -> StdResult<[Uint128; 2]> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let [balance0, balance1] = get_vault_balances(deps, &env)?;
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+
+    let asset0 = balance0 * share / total_share;
+    let asset1 = balance1 * share / total_share;
+
+    Ok([asset0, asset1])
+}
+
+fn get_total_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[Uint128; 2]> {
+    get_vault_balances(deps, &env)
+}
+
+fn get_user_liquidity(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    user: String,
+) -> StdResult<[Asset; 2]> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    let share = query_token_balance(&deps.querier, &contract_info.liquidity_token, user)?;
+    let [balance0, balance1] = get_vault_balances(deps, &env)?;
+    let liquidity0 = balance0 * share / total_share;
+    let liquidity1 = balance1 * share / total_share;
+
+    Ok([
+        Asset {
+            amount: liquidity0,
+            info: AssetInfo::NativeToken {
+                denom: contract_info.base_denom.clone(),
+            },
+        },
+        Asset {
+            amount: liquidity1,
+            info: AssetInfo::NativeToken {
+                denom: contract_info.quote_denom.clone(),
+            },
+        },
+    ])
+}
+
+fn query_credit(deps: Deps<InjectiveQueryWrapper>, user: String) -> StdResult<[Uint128; 2]> {
+    let user = deps.api.addr_validate(&user)?;
+
+    Ok(CREDITS.may_load(deps.storage, &user)?.unwrap_or_default())
+}
+
+/// Returns the stored fee counters as they are, without reconciling them against balances.
+fn query_fee_state(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FeeStateResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    Ok(FeeStateResponse {
+        base_fee_collected: Asset {
+            amount: BASE_FEE_COLLECTED.load(deps.storage)?,
+            info: AssetInfo::NativeToken {
+                denom: contract_info.base_denom,
+            },
+        },
+        quote_fee_collected: Asset {
+            amount: QUOTE_FEE_COLLECTED.load(deps.storage)?,
+            info: AssetInfo::NativeToken {
+                denom: contract_info.quote_denom,
+            },
+        },
+    })
+}
+
+/// Returns every risk limit applied to swaps and price reads.
+fn query_risk_params(deps: Deps<InjectiveQueryWrapper>) -> StdResult<RiskParamsResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    Ok(RiskParamsResponse {
+        min_order_notional: contract_info.min_order_notional,
+        max_slippage_bps: contract_info.max_slippage_bps,
+        max_spread_bps: contract_info.max_spread_bps,
+        max_price_jump_bps: contract_info.max_price_jump_bps,
+        max_conf_ratio: contract_info.max_conf_ratio,
+    })
+}
+
+fn query_market_info(deps: Deps<InjectiveQueryWrapper>) -> StdResult<SpotMarket> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let market = querier
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?;
+
+    Ok(market)
+}
+
+/// Returns the market fee rates net of the relayer share, which comes back to the vault as the
+/// fee recipient of its orders. Maker rebates carry no relayer share.
+fn query_effective_fee_rate(
+    deps: Deps<InjectiveQueryWrapper>,
+) -> StdResult<EffectiveFeeRateResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let market = querier
+        .query_spot_market(&contract_info.market_id)?
+        .market
+        .ok_or_else(|| ContractError::MarketNotFound {
+            market_id: contract_info.market_id.as_str().to_string(),
+        })?;
+
+    let net_rate = |fee_rate: FPDecimal| {
+        if fee_rate > FPDecimal::zero() {
+            fee_rate * (FPDecimal::one() - market.relayer_fee_share_rate)
+        } else {
+            fee_rate
+        }
+    };
+
+    Ok(EffectiveFeeRateResponse {
+        maker_fee_rate: net_rate(market.maker_fee_rate),
+        taker_fee_rate: net_rate(market.taker_fee_rate),
+    })
+}
+
+/// Returns how far the oracle value split of the vault balances is from 50/50, in bps of the
+/// vault value. An empty vault has no skew.
+fn query_inventory_skew(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+) -> StdResult<InventorySkewResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let prices = get_prices(deps, env.clone())?;
+    let balances = get_vault_balances(deps, &env)?;
+    let base_value =
+        FPDecimal::from(balances[0]).scaled(-(contract_info.base_decimal as i32)) * prices[0];
+    let quote_value =
+        FPDecimal::from(balances[1]).scaled(-(contract_info.quote_decimal as i32)) * prices[1];
+
+    let total_value = base_value + quote_value;
+    let skew_bps = if total_value.is_zero() {
+        0
+    } else {
+        let half_bps = FPDecimal::from(BPS_DENOMINATOR as u128 / 2);
+        let base_bps = base_value * FPDecimal::from(BPS_DENOMINATOR as u128) / total_value;
+        if base_bps >= half_bps {
+            u128::from(base_bps - half_bps) as i64
+        } else {
+            -(u128::from(half_bps - base_bps) as i64)
+        }
+    };
+
+    Ok(InventorySkewResponse {
+        base_value,
+        quote_value,
+        skew_bps,
+    })
+}
+
+/// Returns the recent maker volume of the vault subaccount on its market, used to estimate
+/// maker rebates. Falls back to zero when the chain doesn't report aggregate volumes.
+fn query_maker_volume(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FPDecimal> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let volumes = querier
+        .query_aggregate_account_volume(&contract_info.contract_subaccount_id.to_string())
+        .ok()
+        .and_then(|res| res.aggregate_volumes)
+        .unwrap_or_default();
+
+    Ok(volumes
+        .into_iter()
+        .filter(|market_volume| market_volume.market_id == contract_info.market_id)
+        .fold(FPDecimal::zero(), |total, market_volume| {
+            total + market_volume.volume.maker_volume
+        }))
+}
+
+/// Returns the quote notional, in chain units, still committed to the vault's resting orders.
+fn query_pending_order_notional(deps: Deps<InjectiveQueryWrapper>) -> StdResult<FPDecimal> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+
+    let orders = querier
+        .query_trader_spot_orders(
+            &contract_info.market_id,
+            &contract_info.contract_subaccount_id,
+        )?
+        .orders
+        .unwrap_or_default();
+
+    Ok(orders.into_iter().fold(FPDecimal::zero(), |total, order| {
+        total + order.price * order.fillable
+    }))
+}
+
+/// Returns the fee the owner can withdraw right now, capped by the balance left after the
+/// credits reserved for users.
+fn query_owner_fee_claimable(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[Asset; 2]> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_credits = TOTAL_CREDITS.may_load(deps.storage)?.unwrap_or_default();
+    let fees = [
+        BASE_FEE_COLLECTED.load(deps.storage)?,
+        QUOTE_FEE_COLLECTED.load(deps.storage)?,
+    ];
+    let denoms = [contract_info.base_denom, contract_info.quote_denom];
+
+    let claimable = |i: usize| -> StdResult<Asset> {
+        let balance = query_balance(&deps.querier, env.contract.address.to_string(), &denoms[i])?;
+        let available = balance.checked_sub(total_credits[i]).unwrap_or_default();
+        Ok(Asset {
+            amount: min(fees[i], available),
+            info: AssetInfo::NativeToken {
+                denom: denoms[i].clone(),
+            },
+        })
+    };
+
+    Ok([claimable(0)?, claimable(1)?])
+}
+
+pub fn query_tokens(storage: &dyn Storage) -> StdResult<[String; 2]> {
+    let contract_info = CONTRACT_INFO.load(storage)?;
+
+    Ok([contract_info.base_denom, contract_info.quote_denom])
+}
+
+/// Returns the LP balance held by the vault itself. These shares can't be redeemed by anyone.
+fn query_locked_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<Uint128> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
-    let mut messages: Vec<CosmosMsg<InjectiveMsgWrapper>> =
-        vec![CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_info.liquidity_token.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Burn {
-                amount: share_amount,
-            })?,
-            funds: vec![],
-        })];
-    if !refund_assets[0].amount.is_zero() {
-        messages.push(refund_assets[0].clone().into_msg(sender.clone())?);
-    }
-    if !refund_assets[1].amount.is_zero() {
-        messages.push(refund_assets[1].clone().into_msg(sender.clone())?);
-    }
-    if !refund_assets[2].amount.is_zero() {
-        messages.push(refund_assets[2].clone().into_msg(sender.clone())?);
-    }
+    query_token_balance(
+        &deps.querier,
+        &contract_info.liquidity_token,
+        env.contract.address,
+    )
+}
 
-    Ok(Response::<InjectiveMsgWrapper>::new()
-        .add_messages(messages)
-        .add_attributes(vec![
-            attr("action", "withdraw"),
-            attr("sender", sender),
-            attr("withdrawn_share", share_amount),
-            attr(
-                "refund_assets",
-                format!("{}, {}", refund_assets[0], refund_assets[1]),
-            ),
-        ]))
+/// Returns whether the vault has any order resting on the exchange.
+fn query_has_exposure(deps: Deps<InjectiveQueryWrapper>) -> StdResult<bool> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    Ok(!get_live_orders(deps, &contract_info)?.is_empty())
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps<InjectiveQueryWrapper>, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Ownership {} => to_binary(&get_ownership(deps.storage)?),
-        QueryMsg::TokensForShares { share } => to_binary(&get_tokens_for_shares(deps, env, share)?),
-        QueryMsg::TotalLiquidity {} => to_binary(&get_total_liquidity(deps, env)?),
-        QueryMsg::UserLiquidity { user } => to_binary(&get_user_liquidity(deps, env, user)?),
-        QueryMsg::Prices {} => to_binary(&query_prices(deps, env)?),
-        QueryMsg::Tokens {} => to_binary(&query_tokens(deps.storage)?),
-    }
+/// Returns the orders placed by the vault that were not cancelled through the vault. Orders
+/// that filled or were cancelled on the exchange directly stay listed.
+fn query_open_orders(deps: Deps<InjectiveQueryWrapper>) -> StdResult<Vec<OpenOrder>> {
+    OPEN_ORDERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (order_hash, order) = item?;
+            Ok(OpenOrder {
+                order_hash,
+                buying: order.buying,
+                price: order.price,
+                quantity: order.quantity,
+            })
+        })
+        .collect()
 }
 
-fn get_tokens_for_shares(
+/// Compares the base and quote bank balances with the amounts the vault accounts for.
+///
+/// Exchange fills move funds between base and quote without going through the accounting, so
+/// trading gains and losses show up as a surplus or deficit next to donated funds.
+fn query_balance_reconciliation(
     deps: Deps<InjectiveQueryWrapper>,
     env: Env,
-    share: Uint128,
-) -> StdResult<[Uint128; 2]> {
+) -> StdResult<[BalanceReconciliation; 2]> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let balance0 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.base_denom,
-    )? - BASE_FEE_COLLECTED.load(deps.storage)?;
-    let balance1 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.quote_denom,
-    )? - QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    let liquidity = ACCOUNTED_LIQUIDITY
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let fees = [
+        BASE_FEE_COLLECTED.load(deps.storage)?,
+        QUOTE_FEE_COLLECTED.load(deps.storage)?,
+    ];
+    let credits = TOTAL_CREDITS.may_load(deps.storage)?.unwrap_or_default();
+    let denoms = [contract_info.base_denom, contract_info.quote_denom];
+    let balances = [
+        query_balance(&deps.querier, env.contract.address.to_string(), &denoms[0])?,
+        query_balance(&deps.querier, env.contract.address.to_string(), &denoms[1])?,
+    ];
 
-    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    Ok([0, 1].map(|i| {
+        let accounted = liquidity[i] + fees[i] + credits[i];
+        BalanceReconciliation {
+            denom: denoms[i].clone(),
+            balance: balances[i],
+            accounted,
+            surplus: balances[i].saturating_sub(accounted),
+            deficit: accounted.saturating_sub(balances[i]),
+        }
+    }))
+}
 
-    let asset0 = balance0 * share / total_share;
-    let asset1 = balance1 * share / total_share;
+/// Returns the base and quote balances that belong to LPs: the bank balances from
+/// `get_bank_balances` plus the deposits on the vault exchange subaccount.
+fn get_vault_balances(deps: Deps<InjectiveQueryWrapper>, env: &Env) -> StdResult<[Uint128; 2]> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let [balance0, balance1] = get_bank_balances(deps, env)?;
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let subaccount_balance = |denom: &String| -> StdResult<Uint128> {
+        let total_balance = querier
+            .query_subaccount_deposit(&contract_info.contract_subaccount_id, denom)?
+            .deposits
+            .total_balance;
+        Ok(Uint128::new(u128::from(total_balance)))
+    };
 
-    Ok([asset0, asset1])
+    Ok([
+        balance0 + subaccount_balance(&contract_info.base_denom)?,
+        balance1 + subaccount_balance(&contract_info.quote_denom)?,
+    ])
 }
 
-fn get_total_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[Uint128; 2]> {
+/// Returns the base and quote bank balances that belong to LPs, excluding collected fees and
+/// credited deposit overpayments.
+///
+/// Fees and credits can exceed the balance once swaps moved funds out, the LP balance is zero
+/// then.
+fn get_bank_balances(deps: Deps<InjectiveQueryWrapper>, env: &Env) -> StdResult<[Uint128; 2]> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_credits = TOTAL_CREDITS.may_load(deps.storage)?.unwrap_or_default();
     let balance0 = query_balance(
         &deps.querier,
         env.contract.address.to_string(),
         &contract_info.base_denom,
-    )? - BASE_FEE_COLLECTED.load(deps.storage)?;
+    )?
+    .saturating_sub(BASE_FEE_COLLECTED.load(deps.storage)?)
+    .saturating_sub(total_credits[0]);
     let balance1 = query_balance(
         &deps.querier,
         env.contract.address.to_string(),
         &contract_info.quote_denom,
-    )? - QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    )?
+    .saturating_sub(QUOTE_FEE_COLLECTED.load(deps.storage)?)
+    .saturating_sub(total_credits[1]);
 
     Ok([balance0, balance1])
 }
 
-fn get_user_liquidity(deps: Deps<InjectiveQueryWrapper>, env: Env, user: String) -> StdResult<[Asset; 2]> {
+/// Returns the deposit headroom in LP shares under the global and per-asset caps.
+///
+/// Deposits are balanced in value, so an asset cap limits the deposit value to twice its
+/// remaining value.
+fn query_effective_hardcap(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+) -> StdResult<EffectiveHardcapResponse> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
     let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
-    let share = query_token_balance(&deps.querier, &contract_info.liquidity_token, user)?;
-    let balance0 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.base_denom,
-    )? - BASE_FEE_COLLECTED.load(deps.storage)?;
-    let balance1 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.quote_denom,
-    )? - QUOTE_FEE_COLLECTED.load(deps.storage)?;
-    let liquidity0 = balance0 * share / total_share;
-    let liquidity1 = balance1 * share / total_share;
+    let mut res = EffectiveHardcapResponse {
+        binding_constraint: "global".to_string(),
+        remaining_shares: contract_info.hardcap.saturating_sub(total_share),
+    };
+    if contract_info.base_hardcap.is_none() && contract_info.quote_hardcap.is_none() {
+        return Ok(res);
+    }
 
-    Ok([
-        Asset {
-            amount: liquidity0,
-            info: AssetInfo {
-                denom: contract_info.base_denom.clone(),
-            },
-        },
-        Asset {
-            amount: liquidity1,
-            info: AssetInfo {
-                denom: contract_info.quote_denom.clone(),
-            },
+    let prices = get_prices(deps, env.clone())?;
+    let decimals = [contract_info.base_decimal, contract_info.quote_decimal];
+    let balances = get_vault_balances(deps, &env)?;
+    let caps = [
+        (&contract_info.base_denom, contract_info.base_hardcap),
+        (&contract_info.quote_denom, contract_info.quote_hardcap),
+    ];
+    for (i, (denom, hardcap)) in caps.into_iter().enumerate() {
+        if let Some(hardcap) = hardcap {
+            let remaining_value = FPDecimal::from(hardcap.saturating_sub(balances[i]))
+                .scaled(-(decimals[i] as i32))
+                * prices[i];
+            let share = convert_to_shares(
+                deps,
+                env.clone(),
+                [remaining_value / prices[0], remaining_value / prices[1]],
+                prices,
+                decimals,
+            )?;
+            let remaining_shares =
+                Uint128::new(u128::from(share.scaled(contract_info.lp_decimal as i32)));
+            if remaining_shares < res.remaining_shares {
+                res = EffectiveHardcapResponse {
+                    binding_constraint: denom.clone(),
+                    remaining_shares,
+                };
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// Previews a deposit along the same valuation path as `deposit`, failing when it would exceed
+/// the hardcap.
+fn query_simulate_deposit(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    assets: Vec<Asset>,
+) -> StdResult<SimulateDepositResponse> {
+    for asset in assets.iter() {
+        asset.info.check(deps.api)?;
+    }
+
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let amounts = get_deposit_amounts(&contract_info, &assets)?;
+    let prices = get_prices(deps, env.clone())?;
+    let (deposited, share) = get_deposit_shares(
+        deps,
+        &env,
+        &contract_info,
+        prices,
+        amounts,
+        assets.len() == 1,
+    )?;
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if total_share + share > contract_info.hardcap {
+        return Err(StdError::generic_err(
+            ContractError::ExceedHardcap {}.to_string(),
+        ));
+    }
+
+    let denoms = [contract_info.base_denom, contract_info.quote_denom];
+    let to_asset = |i: usize, amount: Uint128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: denoms[i].clone(),
         },
-    ])
+        amount,
+    };
+    Ok(SimulateDepositResponse {
+        share,
+        actual_deposits: [to_asset(0, deposited[0]), to_asset(1, deposited[1])],
+        refund: [
+            to_asset(0, amounts[0] - deposited[0]),
+            to_asset(1, amounts[1] - deposited[1]),
+        ],
+    })
 }
 
-pub fn query_tokens(storage: &dyn Storage) -> StdResult<[String; 2]> {
-    let contract_info = CONTRACT_INFO.load(storage)?;
+fn query_validate_deposit(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    assets: Vec<Asset>,
+    receiver: Option<String>,
+) -> StdResult<ValidateDepositResponse> {
+    for asset in assets.iter() {
+        asset.info.check(deps.api)?;
+    }
+    let receiver = addr_opt_validate(deps.api, &receiver)?;
 
-    Ok([contract_info.base_denom, contract_info.quote_denom])
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let amounts = get_deposit_amounts(&contract_info, &assets)?;
+
+    let not_paused = !contract_info.paused;
+    let deposits_open = assert_deposits_open(&env, &contract_info).is_ok();
+
+    let (prices_fresh, shares, deposited) = match get_prices(deps, env.clone()) {
+        Ok(prices) => {
+            let (deposited, shares) = get_deposit_shares(
+                deps,
+                &env,
+                &contract_info,
+                prices,
+                amounts,
+                assets.len() == 1,
+            )?;
+            // Overpayment stays in the vault when it is credited
+            let deposited = if contract_info.credit_overpayment {
+                amounts
+            } else {
+                deposited
+            };
+            (true, shares, deposited)
+        }
+        Err(_) => (false, Uint128::zero(), amounts),
+    };
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    let mut within_hardcap = total_share + shares <= contract_info.hardcap;
+    let balances = get_vault_balances(deps, &env)?;
+    let caps = [contract_info.base_hardcap, contract_info.quote_hardcap];
+    for (i, hardcap) in caps.into_iter().enumerate() {
+        if let Some(hardcap) = hardcap {
+            within_hardcap &= balances[i] + deposited[i] <= hardcap;
+        }
+    }
+
+    // Without a receiver the depositor is unknown, so only the new shares count against the cap
+    let minted = match &receiver {
+        Some(receiver) => USER_MINTED_SHARES
+            .may_load(deps.storage, receiver)?
+            .unwrap_or_default(),
+        None => Uint128::zero(),
+    };
+    let within_user_cap = contract_info
+        .per_user_cap
+        .map_or(true, |cap| minted + shares <= cap);
+
+    Ok(ValidateDepositResponse {
+        within_hardcap,
+        within_user_cap,
+        allowlisted: true,
+        not_paused,
+        deposits_open,
+        prices_fresh,
+        shares,
+    })
 }
 
 fn convert_to_shares(
@@ -754,81 +3274,187 @@ fn convert_to_shares(
 ) -> StdResult<FPDecimal> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
 
-    let total_share =
-        FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?).scaled(-12);
+    let total_share = FPDecimal::from(query_supply(&deps.querier, &contract_info.liquidity_token)?)
+        .scaled(-(contract_info.lp_decimal as i32));
     let total_deposit_value = amounts[0] * prices[0] + amounts[1] * prices[1];
     let share = if total_share.is_zero() {
         total_deposit_value
     } else {
-        let balance0 = FPDecimal::from(
-            query_balance(
-                &deps.querier,
-                env.contract.address.to_string(),
-                contract_info.base_denom,
-            )? - BASE_FEE_COLLECTED.load(deps.storage)?,
-        )
-        .scaled(-(decimals[0] as i32));
-        let balance1 = FPDecimal::from(
-            query_balance(
-                &deps.querier,
-                env.contract.address.to_string(),
-                contract_info.quote_denom,
-            )? - QUOTE_FEE_COLLECTED.load(deps.storage)?,
-        )
-        .scaled(-(decimals[1] as i32));
+        let balances = get_vault_balances(deps, &env)?;
+        let balance0 = FPDecimal::from(balances[0]).scaled(-(decimals[0] as i32));
+        let balance1 = FPDecimal::from(balances[1]).scaled(-(decimals[1] as i32));
         let total_value = balance0 * prices[0] + balance1 * prices[1];
+        if total_value.is_zero() {
+            return Err(StdError::generic_err(format!(
+                "Vault value is zero with outstanding LP supply: {total_share}"
+            )));
+        }
         total_share * total_deposit_value / total_value
     };
 
     Ok(share)
 }
 
+/// Returns the non-zero assets, including the INJ residue, a full withdrawal of `user` pays.
+fn query_withdrawable_assets(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    user: String,
+) -> StdResult<Vec<Asset>> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let share = query_token_balance(&deps.querier, &contract_info.liquidity_token, user)?;
+    if share.is_zero() {
+        return Ok(vec![]);
+    }
+
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    let (assets, residue) = get_share_in_assets(deps, env, share, total_share)?;
+
+    Ok(assets
+        .into_iter()
+        .chain(residue)
+        .filter(|asset| !asset.amount.is_zero())
+        .collect())
+}
+
+/// Checks whether `user` can withdraw `amount` LP tokens, reporting the reason when not.
+fn query_can_withdraw(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    user: String,
+    amount: Uint128,
+) -> StdResult<CanWithdrawResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let reason = if contract_info.liquidity_token == Addr::unchecked("") {
+        Some("Liquidity token not set".to_string())
+    } else if amount.is_zero() {
+        Some("Can't withdraw zero amount".to_string())
+    } else {
+        let share = query_token_balance(&deps.querier, &contract_info.liquidity_token, user)?;
+        if share < amount {
+            Some(format!("Insufficient LP balance: {share}"))
+        } else {
+            let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+            let (assets, _) = get_share_in_assets(deps, env, amount, total_share)?;
+            if assets.iter().all(|asset| asset.amount.is_zero()) {
+                Some("Insufficient vault liquidity".to_string())
+            } else {
+                None
+            }
+        }
+    };
+
+    Ok(CanWithdrawResponse {
+        can_withdraw: reason.is_none(),
+        reason,
+    })
+}
+
+/// Returns the base and quote assets, followed by the INJ residue when it is not a vault
+/// asset, burning `share` LP tokens pays.
+fn query_simulate_withdraw(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    share: Uint128,
+) -> StdResult<Vec<Asset>> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if share.is_zero() || share > total_share {
+        return Err(StdError::generic_err(format!(
+            "Share must be between 1 and total supply {total_share}"
+        )));
+    }
+
+    let (assets, residue) = get_share_in_assets(deps, env, share, total_share)?;
+
+    Ok(assets.into_iter().chain(residue).collect())
+}
+
+/// Returns `(share, assets)` pairs for `steps + 1` evenly spaced LP amounts between `min` and
+/// `max`, the assets being what burning the share pays as in [`query_simulate_withdraw`].
+fn query_withdraw_curve(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+    min: Uint128,
+    max: Uint128,
+    steps: u32,
+) -> StdResult<Vec<(Uint128, Vec<Asset>)>> {
+    if min > max {
+        return Err(StdError::generic_err("min must not exceed max"));
+    }
+    if steps == 0 || steps > MAX_WITHDRAW_CURVE_STEPS {
+        return Err(StdError::generic_err(format!(
+            "steps must be between 1 and {MAX_WITHDRAW_CURVE_STEPS}"
+        )));
+    }
+
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let total_share = query_supply(&deps.querier, &contract_info.liquidity_token)?;
+    if total_share.is_zero() || max > total_share {
+        return Err(StdError::generic_err(format!(
+            "max must not exceed total supply {total_share}"
+        )));
+    }
+
+    (0..=steps)
+        .map(|step| {
+            let share = min + (max - min).multiply_ratio(step, steps);
+            let (assets, residue) = get_share_in_assets(deps, env.clone(), share, total_share)?;
+            Ok((share, assets.into_iter().chain(residue).collect()))
+        })
+        .collect()
+}
+
+/// Returns the base and quote assets for `share`, and the INJ residue unless INJ is one of the
+/// vault assets.
 fn get_share_in_assets(
     deps: Deps<InjectiveQueryWrapper>,
     env: Env,
     share: Uint128,
     total_share: Uint128,
-) -> StdResult<[Asset; 3]> {
+) -> StdResult<([Asset; 2], Option<Asset>)> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let balance0 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.base_denom,
-    )? - BASE_FEE_COLLECTED.load(deps.storage)?;
-    let balance1 = query_balance(
-        &deps.querier,
-        env.contract.address.to_string(),
-        &contract_info.quote_denom,
-    )? - QUOTE_FEE_COLLECTED.load(deps.storage)?;
+    let [balance0, balance1] = get_vault_balances(deps, &env)?;
     let refund_amount0 = balance0 * share / total_share;
     let refund_amount1 = balance1 * share / total_share;
-    let mut fee_amount = Uint128::zero();
-    let fee_denom = "INJ".to_string();
-    if contract_info.base_denom != fee_denom && contract_info.quote_denom != fee_denom {
-        let inj_balance: Uint128 =
-            query_balance(&deps.querier, env.contract.address.to_string(), &fee_denom)?;
-        fee_amount = inj_balance * share / total_share;
-    }
-    Ok([
-        Asset {
-            amount: refund_amount0,
-            info: AssetInfo {
-                denom: contract_info.base_denom.clone(),
-            },
-        },
-        Asset {
-            amount: refund_amount1,
-            info: AssetInfo {
-                denom: contract_info.quote_denom.clone(),
+    let residue = match get_residue_denom(&contract_info) {
+        Some(residue_denom) => {
+            let inj_balance: Uint128 = query_balance(
+                &deps.querier,
+                env.contract.address.to_string(),
+                &residue_denom,
+            )?
+            .saturating_sub(
+                RESIDUE_COLLECTED
+                    .may_load(deps.storage)?
+                    .unwrap_or_default(),
+            );
+            Some(Asset {
+                amount: inj_balance * share / total_share,
+                info: AssetInfo::NativeToken {
+                    denom: residue_denom,
+                },
+            })
+        }
+        None => None,
+    };
+    Ok((
+        [
+            Asset {
+                amount: refund_amount0,
+                info: AssetInfo::NativeToken {
+                    denom: contract_info.base_denom.clone(),
+                },
             },
-        },
-        Asset {
-            amount: fee_amount,
-            info: AssetInfo {
-                denom: fee_denom.clone(),
+            Asset {
+                amount: refund_amount1,
+                info: AssetInfo::NativeToken {
+                    denom: contract_info.quote_denom.clone(),
+                },
             },
-        },
-    ])
+        ],
+        residue,
+    ))
 }
 
 fn query_prices(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[Uint128; 2]> {
@@ -840,33 +3466,136 @@ fn query_prices(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[Uint1
     ])
 }
 
-fn get_prices(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<[FPDecimal; 2]> {
+/// Returns the base price in quote derived from the Pyth feeds, scaled by 10^8.
+fn query_spot_price_from_oracle(deps: Deps<InjectiveQueryWrapper>, env: Env) -> StdResult<Uint128> {
+    let prices: [FPDecimal; 2] = get_prices(deps, env)?;
+
+    Ok(Uint128::new(u128::from((prices[0] / prices[1]).scaled(8))))
+}
+
+fn get_prices(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: Env,
+) -> Result<[FPDecimal; 2], ContractError> {
+    let (prices, _) = get_prices_with_source(deps, &env)?;
+
+    Ok(prices)
+}
+
+/// Returns the base and quote prices and where they come from.
+///
+/// With `market_price_fallback` set, a Pyth price failing the checks of [`check_pyth_price`] is
+/// derived from the other feed and the spot market mid price. The Pyth error is returned when
+/// both feeds fail or the market has no mid price.
+fn get_prices_with_source(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+) -> Result<([FPDecimal; 2], PriceSource), ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let [base_pyth_price, quote_pyth_price] = query_pyth_prices(deps, &contract_info)?;
+    let base_check = check_pyth_price(&base_pyth_price, env, &contract_info);
+    let quote_check = check_pyth_price(&quote_pyth_price, env, &contract_info);
+    let base_price = base_pyth_price.price_state.price;
+    let quote_price = quote_pyth_price.price_state.price;
+
+    match (base_check, quote_check) {
+        (Ok(()), Ok(())) => Ok(([base_price, quote_price], PriceSource::Pyth)),
+        (Err(err), Ok(())) if contract_info.market_price_fallback => {
+            let mid_price = get_market_mid_price(deps, &contract_info).map_err(|_| err)?;
+            Ok((
+                [mid_price * quote_price, quote_price],
+                PriceSource::MarketMid,
+            ))
+        }
+        (Ok(()), Err(err)) if contract_info.market_price_fallback => {
+            let mid_price = get_market_mid_price(deps, &contract_info).map_err(|_| err)?;
+            Ok(([base_price, base_price / mid_price], PriceSource::MarketMid))
+        }
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    }
+}
+
+/// Returns the spot market mid price of the base in quote, adjusted for the asset decimals.
+fn get_market_mid_price(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<FPDecimal> {
+    let querier = InjectiveQuerier::new(&deps.querier);
+    let mid_price = querier
+        .query_spot_market_mid_price_and_tob(&contract_info.market_id)?
+        .mid_price
+        .filter(|mid_price| *mid_price > FPDecimal::zero())
+        .ok_or_else(|| StdError::generic_err("Market mid price missing"))?;
+
+    Ok(mid_price.scaled(contract_info.base_decimal as i32 - contract_info.quote_decimal as i32))
+}
+
+/// Returns the base and quote Pyth price states, failing if either is stale, non-positive or
+/// has a confidence interval wider than `max_conf_ratio`.
+fn get_price_states(
+    deps: Deps<InjectiveQueryWrapper>,
+    env: &Env,
+) -> Result<[PriceState; 2], ContractError> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let [base_pyth_price, quote_pyth_price] = query_pyth_prices(deps, &contract_info)?;
+    check_pyth_price(&base_pyth_price, env, &contract_info)?;
+    check_pyth_price(&quote_pyth_price, env, &contract_info)?;
+
+    Ok([base_pyth_price.price_state, quote_pyth_price.price_state])
+}
+
+fn query_pyth_prices(
+    deps: Deps<InjectiveQueryWrapper>,
+    contract_info: &ContractInfo,
+) -> StdResult<[PythPriceState; 2]> {
     let querier = InjectiveQuerier::new(&deps.querier);
     let response0 = querier.query_pyth_price(contract_info.base_price_id.as_str())?;
     let response1 = querier.query_pyth_price(contract_info.quote_price_id.as_str())?;
-    let base_price_state = response0
+    let base_pyth_price = response0
         .price_state
-        .expect("Failed to get base asset price")
-        .price_state;
-    let base_price = base_price_state.price;
-    let quote_price_state = response1
+        .expect("Failed to get base asset price");
+    let quote_pyth_price = response1
         .price_state
-        .expect("Failed to get quote asset price")
-        .price_state;
-    let quote_price = quote_price_state.price;
+        .expect("Failed to get quote asset price");
+
+    Ok([base_pyth_price, quote_pyth_price])
+}
 
+/// Fails if the Pyth price is stale, non-positive or has a confidence interval wider than
+/// `max_conf_ratio`.
+fn check_pyth_price(
+    pyth_price: &PythPriceState,
+    env: &Env,
+    contract_info: &ContractInfo,
+) -> Result<(), ContractError> {
     let timestamp = env.block.time.seconds() as i64;
-    if base_price_state.timestamp < timestamp - PRICE_VALID_DURATION {
+    if pyth_price.publish_time <= 0 {
         return Err(StdError::GenericErr {
-            msg: "Price too old".to_owned(),
-        });
+            msg: "Price publish time missing".to_owned(),
+        }
+        .into());
     }
-    if quote_price_state.timestamp < timestamp - PRICE_VALID_DURATION {
+    if pyth_price.publish_time > timestamp {
         return Err(StdError::GenericErr {
-            msg: "Price too old".to_owned(),
-        });
+            msg: "Price published in the future".to_owned(),
+        }
+        .into());
+    }
+    if pyth_price.publish_time < timestamp - PRICE_VALID_DURATION {
+        return Err(ContractError::PriceTooOld {});
+    }
+    if pyth_price.price_state.price <= FPDecimal::zero() {
+        return Err(StdError::GenericErr {
+            msg: "Price not positive".to_owned(),
+        }
+        .into());
+    }
+    if pyth_price.conf / pyth_price.price_state.price > contract_info.max_conf_ratio {
+        return Err(StdError::GenericErr {
+            msg: format!("Price confidence too wide: {}", pyth_price.price_id),
+        }
+        .into());
     }
 
-    Ok([base_price, quote_price])
+    Ok(())
 }
@@ -1,5 +1,7 @@
 use cosmwasm_std::{to_binary, Addr, CosmosMsg, StdResult, SubMsg, WasmMsg};
-use injective_cosmwasm::InjectiveMsgWrapper;
+use injective_cosmwasm::{
+    cancel_derivative_order_msg, cancel_spot_order_msg, InjectiveMsgWrapper, MarketId, SubaccountId,
+};
 use injective_math::FPDecimal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -31,6 +33,38 @@ pub fn i32_to_dec(source: i32) -> FPDecimal {
     FPDecimal::from(i128::from(source))
 }
 
+/// Whether `value` is a whole number of `tick`. A zero tick accepts any value.
+pub fn is_on_tick(value: FPDecimal, tick: FPDecimal) -> bool {
+    if tick.is_zero() {
+        return true;
+    }
+    if value.is_negative() {
+        return false;
+    }
+
+    let ticks = value / tick;
+    FPDecimal::from(u128::from(ticks)) == ticks
+}
+
+/// Rounds `value` down to a multiple of `tick`.
+pub fn floor_to_tick(value: FPDecimal, tick: FPDecimal) -> FPDecimal {
+    if tick.is_zero() {
+        return value;
+    }
+
+    FPDecimal::from(u128::from(value / tick)) * tick
+}
+
+/// Rounds `value` up to a multiple of `tick`.
+pub fn ceil_to_tick(value: FPDecimal, tick: FPDecimal) -> FPDecimal {
+    let floored = floor_to_tick(value, tick);
+    if floored == value {
+        return value;
+    }
+
+    floored + tick
+}
+
 pub fn get_message_data(
     response: &[SubMsg<InjectiveMsgWrapper>],
     position: usize,
@@ -41,3 +75,32 @@ pub fn get_message_data(
     };
     sth
 }
+
+/// Order mask matching any derivative order type.
+pub const ORDER_MASK_ANY: i32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum MarketType {
+    Spot,
+    Derivative,
+}
+
+/// Builds the exchange message cancelling an order on the given market type.
+pub fn cancel_order_msg(
+    market_type: MarketType,
+    sender: Addr,
+    market_id: MarketId,
+    subaccount_id: SubaccountId,
+    order_hash: String,
+) -> CosmosMsg<InjectiveMsgWrapper> {
+    match market_type {
+        MarketType::Spot => cancel_spot_order_msg(sender, market_id, subaccount_id, order_hash),
+        MarketType::Derivative => cancel_derivative_order_msg(
+            sender,
+            market_id,
+            subaccount_id,
+            order_hash,
+            ORDER_MASK_ANY,
+        ),
+    }
+}
@@ -3,8 +3,9 @@ use cw20::Cw20ReceiveMsg;
 use cw_ownable::Action;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-use injective_cosmwasm::MarketId;
+use injective_cosmwasm::{MarketId, SubaccountId};
 use injective_math::FPDecimal;
 
 use crate::asset::Asset;
@@ -19,6 +20,23 @@ pub struct InstantiateMsg {
     pub quote_price_id: String,
     pub hardcap: Uint128,
     pub token_code_id: u64,
+    /// Credit deposit overpayment for a later claim instead of refunding it right away
+    #[serde(default)]
+    pub credit_overpayment: bool,
+    /// Decimals of the LP token, defaults to 12
+    pub lp_decimal: Option<u8>,
+    /// Name of the LP token, derived from the denoms when not set
+    pub lp_token_name: Option<String>,
+    /// Symbol of the LP token, derived from the market ticker when not set
+    pub lp_token_symbol: Option<String>,
+    /// Reuse an existing CW20 as the LP token instead of instantiating one, the vault must be
+    /// its minter
+    pub existing_lp_token: Option<String>,
+    /// Block time in seconds before which deposits are rejected
+    pub deposit_start_time: Option<u64>,
+    /// Derive a stale Pyth price from the other feed and the spot market mid price
+    #[serde(default)]
+    pub market_price_fallback: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -29,21 +47,64 @@ pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
     /// Deposit allows someone to deposit in the vault
     Deposit {
-        /// The amounts to deposit
+        /// The amounts to deposit, one or both vault assets
         assets: Vec<Asset>,
         /// The receiver of LP tokens
         receiver: Option<String>,
+        /// The minimum LP tokens to receive, guarding against price moves before execution
+        min_lp_out: Option<Uint128>,
+        /// Maximum vault value per LP token in USD scaled by 10^8, guarding against
+        /// depositing right after a NAV jump
+        max_share_price: Option<Uint128>,
+    },
+    /// Seed the vault once after the LP token is created, minting the initial LP to the owner
+    InitialSeed {
+        /// The amounts to seed, one or both vault assets
+        assets: Vec<Asset>,
+    },
+    /// Deposit a single asset and swap half of it into the paired asset
+    DepositAndRebalance {
+        /// The asset to deposit
+        asset: Asset,
+        /// Max deviation of the swap price from the oracle price, in basis points
+        max_slippage_bps: u64,
+        /// The receiver of LP tokens
+        receiver: Option<String>,
     },
     /// SpotSwap
     SwapSpot {
         buying: bool,
         quantity: FPDecimal,
         price: FPDecimal,
+        /// Deposit the bank balance of the spent asset onto the vault subaccount along with
+        /// the order
+        #[serde(default)]
+        fund_subaccount: bool,
+        /// Place a maker-only order, rejected instead of crossing the book
+        #[serde(default)]
+        post_only: bool,
+    },
+    /// Place several limit orders in a single batch update, e.g. a grid around the mid price
+    SwapSpotBatch {
+        orders: Vec<SpotOrderParams>,
+    },
+    /// SpotSwap at the oracle price moved `buffer_bps` against the vault so the order is
+    /// marketable
+    SwapSpotAtOracle {
+        buying: bool,
+        quantity: FPDecimal,
+        buffer_bps: u64,
     },
     /// Cancel placed order
     CancelOrder {
         order_hash: String,
     },
+    /// Cancel several placed orders in one batch
+    BatchCancelByHashes {
+        hashes: Vec<String>,
+    },
+    /// Cancel every order of the vault subaccount on the vault market
+    CancelAllOrders {},
     /// Add fees
     AddFee {
         base_fee: Uint128,
@@ -54,21 +115,272 @@ pub enum ExecuteMsg {
         base_fee: Uint128,
         quote_fee: Uint128,
     },
+    /// Send all collected fees to a fee vault contract and reset the fee counters
+    ForwardFees {
+        fee_vault: String,
+    },
+    /// Set the INJ residue held by the vault aside as protocol revenue instead of refunding it
+    /// to withdrawers
+    CollectResidue {},
+    /// Send the whole balance of a denom that is not a vault asset to `recipient`
+    SweepToken {
+        denom: String,
+        recipient: String,
+    },
+    /// Burn the LP held by the vault once every other holder exited and sweep the remaining
+    /// assets to the owner
+    BurnContractLp {},
+    /// Update the per-asset balance caps, `None` removes a cap
+    UpdateAssetHardcaps {
+        base_hardcap: Option<Uint128>,
+        quote_hardcap: Option<Uint128>,
+    },
+    /// Claim the credited deposit overpayment
+    ClaimCredit {},
+    /// Check that both price feeds are live
+    CheckPrices {},
+    /// Update the minimum `price * quantity` accepted for swaps
+    UpdateMinOrderNotional {
+        min_order_notional: FPDecimal,
+    },
+    /// Update the maximum Pyth `conf / price` ratio accepted when reading prices
+    UpdateMaxConfRatio {
+        max_conf_ratio: FPDecimal,
+    },
+    /// Update the maximum number of tracked open orders, `None` removes the limit
+    UpdateMaxOpenOrders {
+        max_open_orders: Option<u32>,
+    },
+    /// Update the maximum LP a single receiver can be minted by deposits, `None` removes the
+    /// cap
+    UpdatePerUserCap {
+        per_user_cap: Option<Uint128>,
+    },
+    /// Update the address receiving withdrawn fees, `None` sends them to the owner
+    UpdateTreasury {
+        treasury: Option<String>,
+    },
+    /// Halt deposits and swaps, withdrawals stay open
+    SetPaused {
+        paused: bool,
+    },
+    /// Update the maximum LP token supply
+    UpdateHardcap {
+        hardcap: Uint128,
+    },
+    /// Update the Pyth price ids, each must resolve to a price
+    UpdatePriceIds {
+        base_price_id: String,
+        quote_price_id: String,
+    },
+    /// Update the swap risk parameters in basis points, `None` leaves a parameter unchanged
+    UpdateRiskParams {
+        max_slippage_bps: Option<u64>,
+        max_spread_bps: Option<u64>,
+        max_price_jump_bps: Option<u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Ownership {},
-    TokensForShares { share: Uint128 },
+    TokensForShares {
+        share: Uint128,
+    },
     TotalLiquidity {},
-    UserLiquidity { user: String },
+    UserLiquidity {
+        user: String,
+    },
     Prices {},
+    /// The base price in quote from the oracle, scaled by 10^8
+    SpotPriceFromOracle {},
     Tokens {},
+    LockedLiquidity {},
+    Credit {
+        user: String,
+    },
+    /// Base and quote fees collected so far, withdrawable by the owner with `WithdrawFee`
+    FeeState {},
+    EffectiveHardcap {},
+    MakerVolume {},
+    OwnerFeeClaimable {},
+    ValidateDeposit {
+        assets: Vec<Asset>,
+        receiver: Option<String>,
+    },
+    PendingOrderNotional {},
+    SimulateDeposit {
+        assets: Vec<Asset>,
+    },
+    WithdrawableAssets {
+        user: String,
+    },
+    CanWithdraw {
+        user: String,
+        amount: Uint128,
+    },
+    SimulateWithdraw {
+        share: Uint128,
+    },
+    /// Withdrawal proceeds for `steps + 1` evenly spaced LP amounts between `min` and `max`
+    WithdrawCurve {
+        min: Uint128,
+        max: Uint128,
+        steps: u32,
+    },
+    InstantiateParams {},
+    /// The stored vault configuration
+    Config {},
+    BalanceReconciliation {},
+    OpenOrders {},
+    /// Whether the vault has orders resting on the exchange
+    HasExposure {},
+    RiskParams {},
+    /// Where the prices currently used by the vault come from
+    PriceSource {},
+    /// Maker and taker fee rates paid by the vault net of the relayer share it gets back
+    EffectiveFeeRate {},
+    /// Distance of the vault value split from 50/50 between base and quote
+    InventorySkew {},
+    /// The spot market the vault trades on
+    MarketInfo {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    /// Both Pyth feeds
+    Pyth,
+    /// One Pyth feed and the spot market mid price
+    MarketMid,
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::Pyth => write!(f, "pyth"),
+            PriceSource::MarketMid => write!(f, "market_mid"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeStateResponse {
+    pub base_fee_collected: Asset,
+    pub quote_fee_collected: Asset,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EffectiveFeeRateResponse {
+    pub maker_fee_rate: FPDecimal,
+    pub taker_fee_rate: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InventorySkewResponse {
+    /// Oracle value of the base balance in USD
+    pub base_value: FPDecimal,
+    /// Oracle value of the quote balance in USD
+    pub quote_value: FPDecimal,
+    /// Base share of the vault value minus 5000 bps, positive when the vault is base heavy
+    pub skew_bps: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RiskParamsResponse {
+    pub min_order_notional: FPDecimal,
+    pub max_slippage_bps: Option<u64>,
+    pub max_spread_bps: Option<u64>,
+    pub max_price_jump_bps: Option<u64>,
+    pub max_conf_ratio: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EffectiveHardcapResponse {
+    /// The cap limiting deposits the most, `global` or the capped asset denom
+    pub binding_constraint: String,
+    /// LP shares that can still be minted before hitting the binding cap
+    pub remaining_shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidateDepositResponse {
+    /// Minted shares stay within the global hardcap and deposits within the asset hardcaps
+    pub within_hardcap: bool,
+    /// LP already minted to the receiver plus the estimated shares stay within `per_user_cap`
+    pub within_user_cap: bool,
+    /// The vault has no depositor allowlist, always true
+    pub allowlisted: bool,
+    /// Deposits are not halted by `SetPaused`
+    pub not_paused: bool,
+    /// The block time is past the configured `deposit_start_time`
+    pub deposits_open: bool,
+    /// Both oracle prices are recent and positive
+    pub prices_fresh: bool,
+    /// Estimated LP shares minted for the deposit, zero when prices are not fresh
+    pub shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateDepositResponse {
+    /// LP shares minted for the deposit
+    pub share: Uint128,
+    /// Base and quote amounts taken by the vault
+    pub actual_deposits: [Asset; 2],
+    /// Base and quote amounts refunded or credited to the depositor
+    pub refund: [Asset; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SpotOrderParams {
+    pub buying: bool,
+    pub quantity: FPDecimal,
+    pub price: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OpenOrder {
+    pub order_hash: String,
+    pub buying: bool,
+    pub price: FPDecimal,
+    pub quantity: FPDecimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CanWithdrawResponse {
+    pub can_withdraw: bool,
+    /// Why the withdrawal would fail, `None` when it succeeds
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BalanceReconciliation {
+    pub denom: String,
+    /// Bank balance of the vault
+    pub balance: Uint128,
+    /// LP liquidity, collected fees and credited overpayments the vault accounts for
+    pub accounted: Uint128,
+    /// Balance above the accounted amount, e.g. donated funds or trading gains
+    pub surplus: Uint128,
+    /// Accounted amount missing from the balance
+    pub deficit: Uint128,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
-    Withdraw {},
+    /// Redeem LP tokens, delivering the assets to `to_subaccount` on the exchange when set
+    Withdraw {
+        to_subaccount: Option<SubaccountId>,
+        /// Reject when the base and quote refund values differ by more than this share of
+        /// their total, in basis points
+        max_imbalance_bps: Option<u64>,
+        /// Pay the whole refund in this pool denom, converting the other side at the oracle
+        /// price less the vault's `max_slippage_bps`
+        target_denom: Option<String>,
+    },
 }
@@ -1,9 +1,13 @@
 use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use injective_cosmwasm::{MarketId, SubaccountId};
+use injective_math::FPDecimal;
+
+use crate::contract::DEFAULT_LP_DECIMAL;
+use crate::msg::InstantiateMsg;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ContractInfo {
@@ -15,12 +19,110 @@ pub struct ContractInfo {
     pub base_price_id: String,
     pub quote_price_id: String,
     pub hardcap: Uint128,
+    #[serde(default)]
+    pub base_hardcap: Option<Uint128>,
+    #[serde(default)]
+    pub quote_hardcap: Option<Uint128>,
+    #[serde(default)]
+    pub credit_overpayment: bool,
+    #[serde(default = "default_min_order_notional")]
+    pub min_order_notional: FPDecimal,
+    #[serde(default)]
+    pub max_slippage_bps: Option<u64>,
+    #[serde(default)]
+    pub max_spread_bps: Option<u64>,
+    #[serde(default)]
+    pub max_price_jump_bps: Option<u64>,
+    /// Maximum Pyth `conf / price` ratio accepted when reading prices
+    #[serde(default = "default_max_conf_ratio")]
+    pub max_conf_ratio: FPDecimal,
+    /// Block time in seconds before which deposits are rejected
+    #[serde(default)]
+    pub deposit_start_time: Option<u64>,
+    /// Maximum number of tracked open orders, unbounded when not set
+    #[serde(default)]
+    pub max_open_orders: Option<u32>,
+    /// Deposits and swaps are halted while set
+    #[serde(default)]
+    pub paused: bool,
+    /// Maximum LP minted by deposits to a single receiver, unbounded when not set
+    #[serde(default)]
+    pub per_user_cap: Option<Uint128>,
+    /// Derive a stale Pyth price from the other feed and the spot market mid price
+    #[serde(default)]
+    pub market_price_fallback: bool,
+    /// LP token decimals, vaults deployed before this was configurable use 12
+    #[serde(default = "default_lp_decimal")]
+    pub lp_decimal: u8,
+    /// Receives withdrawn fees and can't deposit or be minted LP, fees go to the owner when not
+    /// set
+    #[serde(default)]
+    pub treasury: Option<Addr>,
     pub liquidity_token: Addr,
     pub contract_subaccount_id: SubaccountId,
+    /// State version, vaults deployed before versioning load as 0
+    #[serde(default)]
+    pub contract_version: u64,
+}
+
+pub fn default_min_order_notional() -> FPDecimal {
+    FPDecimal::zero()
+}
+
+pub fn default_lp_decimal() -> u8 {
+    DEFAULT_LP_DECIMAL
+}
+
+/// Default maximum `conf / price` ratio, 2%
+pub fn default_max_conf_ratio() -> FPDecimal {
+    FPDecimal::from(2u128) / FPDecimal::from(100u128)
 }
 
 pub const CONTRACT_INFO: Item<ContractInfo> = Item::new("vault");
 
+/// The message the vault was instantiated with
+pub const INSTANTIATE_PARAMS: Item<InstantiateMsg> = Item::new("instantiate_params");
+
 pub const BASE_FEE_COLLECTED: Item<Uint128> = Item::new("base_fee_collected");
 
 pub const QUOTE_FEE_COLLECTED: Item<Uint128> = Item::new("quote_fee_collected");
+
+/// INJ residue set aside as protocol revenue, forwarded along with the fees
+pub const RESIDUE_COLLECTED: Item<Uint128> = Item::new("residue_collected");
+
+/// Deposit overpayment (base, quote) kept aside for each user to claim
+pub const CREDITS: Map<&Addr, [Uint128; 2]> = Map::new("credits");
+
+/// LP minted by deposits to each receiver so far, checked against `per_user_cap`
+pub const USER_MINTED_SHARES: Map<&Addr, Uint128> = Map::new("user_minted_shares");
+
+pub const TOTAL_CREDITS: Item<[Uint128; 2]> = Item::new("total_credits");
+
+/// Base and quote amounts deposited by LPs net of withdrawals and fees, reconciled against the
+/// vault balances
+pub const ACCOUNTED_LIQUIDITY: Item<[Uint128; 2]> = Item::new("accounted_liquidity");
+
+/// Whether the owner already seeded the vault
+pub const INITIAL_SEEDED: Item<bool> = Item::new("initial_seeded");
+
+/// Oracle price in chain units at the last swap, used to bound oracle price jumps
+pub const LAST_SWAP_ORACLE_PRICE: Item<FPDecimal> = Item::new("last_swap_oracle_price");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OrderMeta {
+    pub buying: bool,
+    pub price: FPDecimal,
+    pub quantity: FPDecimal,
+}
+
+/// Order placed by the last swap, recorded under its hash once the exchange replies
+pub const PENDING_ORDER: Item<OrderMeta> = Item::new("pending_order");
+
+/// Hashes of the orders cancelled last, removed from the open orders once the cancel succeeds
+pub const PENDING_CANCEL: Item<Vec<String>> = Item::new("pending_cancel");
+
+/// Orders placed by the last batch swap, recorded in order under the returned hashes
+pub const PENDING_BATCH_ORDERS: Item<Vec<OrderMeta>> = Item::new("pending_batch_orders");
+
+/// Orders placed by the vault and not cancelled yet, keyed by order hash
+pub const OPEN_ORDERS: Map<&str, OrderMeta> = Map::new("open_orders");
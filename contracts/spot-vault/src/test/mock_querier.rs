@@ -10,8 +10,11 @@ use std::panic;
 use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
 
 use injective_cosmwasm::{
-    HandlesMarketIdQuery, HandlesPythPriceQuery, InjectiveQuery, InjectiveQueryWrapper,
+    Deposit, HandlesAccountAddressQuery, HandlesMarketAndSubaccountQuery, HandlesMarketIdQuery,
+    HandlesPythPriceQuery, HandlesSubaccountAndDenomQuery, InjectiveQuery, InjectiveQueryWrapper,
+    SubaccountDepositResponse, TraderSpotOrdersResponse,
 };
+use injective_math::FPDecimal;
 
 const TEST_CONTRACT_ADDR: &str = "inj14hj2tavq8fpesdwxxcu44rty3hh90vhujaxlnz";
 
@@ -40,6 +43,10 @@ where
 pub struct WasmMockQuerier {
     pub pyth_price_response_handler: Option<Box<dyn HandlesPythPriceQuery>>,
     pub spot_market_response_handler: Option<Box<dyn HandlesMarketIdQuery>>,
+    pub aggregate_account_volume_handler: Option<Box<dyn HandlesAccountAddressQuery>>,
+    pub trader_spot_orders_response_handler: Option<Box<dyn HandlesMarketAndSubaccountQuery>>,
+    pub spot_market_mid_price_and_tob_handler: Option<Box<dyn HandlesMarketIdQuery>>,
+    pub subaccount_deposit_response_handler: Option<Box<dyn HandlesSubaccountAndDenomQuery>>,
     base: MockQuerier<InjectiveQueryWrapper>,
     token_querier: TokenQuerier,
 }
@@ -184,6 +191,47 @@ impl WasmMockQuerier {
                         None => panic!("SpotMarketHandler not set"),
                     }
                 }
+                InjectiveQuery::AggregateAccountVolume { account } => {
+                    match &self.aggregate_account_volume_handler {
+                        Some(handler) => handler.handle(account),
+                        None => SystemResult::Err(SystemError::UnsupportedRequest {
+                            kind: "AggregateAccountVolume".to_string(),
+                        }),
+                    }
+                }
+                InjectiveQuery::TraderSpotOrders {
+                    market_id,
+                    subaccount_id,
+                } => match &self.trader_spot_orders_response_handler {
+                    Some(handler) => handler.handle(market_id, subaccount_id),
+                    // The vault has no resting orders unless they are mocked
+                    None => SystemResult::Ok(ContractResult::from(to_binary(
+                        &TraderSpotOrdersResponse { orders: None },
+                    ))),
+                },
+                InjectiveQuery::SpotMarketMidPriceAndTob { market_id } => {
+                    match &self.spot_market_mid_price_and_tob_handler {
+                        Some(handler) => handler.handle(market_id),
+                        None => SystemResult::Err(SystemError::UnsupportedRequest {
+                            kind: "SpotMarketMidPriceAndTob".to_string(),
+                        }),
+                    }
+                }
+                InjectiveQuery::SubaccountDeposit {
+                    subaccount_id,
+                    denom,
+                } => match &self.subaccount_deposit_response_handler {
+                    Some(handler) => handler.handle(subaccount_id, denom),
+                    // Nothing is deposited on the exchange unless mocked
+                    None => SystemResult::Ok(ContractResult::from(to_binary(
+                        &SubaccountDepositResponse {
+                            deposits: Deposit {
+                                available_balance: FPDecimal::zero(),
+                                total_balance: FPDecimal::zero(),
+                            },
+                        },
+                    ))),
+                },
                 _ => panic!("Unknown query"),
             },
             _ => self.base.handle_query(request),
@@ -196,6 +244,10 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             pyth_price_response_handler: None,
             spot_market_response_handler: None,
+            aggregate_account_volume_handler: None,
+            trader_spot_orders_response_handler: None,
+            spot_market_mid_price_and_tob_handler: None,
+            subaccount_deposit_response_handler: None,
             base,
             token_querier: TokenQuerier::default(),
         }
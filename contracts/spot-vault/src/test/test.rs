@@ -2,36 +2,54 @@ use std::str::FromStr;
 
 use cosmwasm_std::testing::{mock_info, MockApi, MockStorage};
 use cosmwasm_std::{
-    attr, to_binary, BankMsg, Binary, Coin, ContractResult, DepsMut, OwnedDeps, QuerierResult,
-    Reply, ReplyOn, StdError, SubMsg, SubMsgResponse, SubMsgResult, SystemResult, Uint128, WasmMsg,
+    attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Deps,
+    DepsMut, OwnedDeps, QuerierResult, Reply, ReplyOn, StdError, Storage, SubMsg, SubMsgResponse,
+    SubMsgResult, SystemResult, Timestamp, Uint128, WasmMsg,
 };
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
 use cw20_base::msg::InstantiateMsg as TokenInstantiateMsg;
 use injective_cosmwasm::oracle::types::{PriceState, PythPriceState};
-use injective_cosmwasm::InjectiveMsg::BatchUpdateOrders;
+use injective_cosmwasm::InjectiveMsg::{self, BatchUpdateOrders};
 use injective_cosmwasm::{
-    exchange::spot::{ShortOrderInfo, ShortSpotOrder},
-    inj_mock_env, HandlesMarketIdQuery, HandlesPythPriceQuery, InjectiveQueryWrapper,
-    InjectiveRoute, MarketId, MarketStatus, OrderType, PythPriceResponse, SpotMarket,
-    SpotMarketResponse, SubaccountId,
+    create_deposit_msg,
+    exchange::spot::{ShortOrderInfo, ShortSpotOrder, TrimmedSpotLimitOrder},
+    get_default_subaccount_id_for_checked_address, inj_mock_env, Deposit,
+    HandlesAccountAddressQuery, HandlesMarketAndSubaccountQuery, HandlesMarketIdQuery,
+    HandlesPythPriceQuery, HandlesSubaccountAndDenomQuery, InjectiveQueryWrapper, InjectiveRoute,
+    MarketId, MarketMidPriceAndTOBResponse, MarketStatus, MarketVolume, OrderData, OrderType,
+    PythPriceResponse, QueryAggregateVolumeResponse, SpotMarket, SpotMarketResponse,
+    SubaccountDepositResponse, SubaccountId, TraderSpotOrdersResponse, VolumeByType,
 };
 use injective_math::FPDecimal;
+use injective_protobuf::proto::tx;
 use protobuf::Message;
-use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::asset::{Asset, AssetInfo};
-use crate::contract::{execute, instantiate, reply, ORDER_REPLY_ID};
+use crate::asset::{format_lp_token_name, format_lp_token_symbol, Asset, AssetInfo};
+use crate::contract::{
+    execute, instantiate, migrate, query, reply, CANCEL_REPLY_ID, ORDER_REPLY_ID,
+};
 use crate::error::ContractError;
-use crate::helpers::{get_message_data, i32_to_dec};
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg};
+use crate::helpers::{cancel_order_msg, get_message_data, i32_to_dec, MarketType, ORDER_MASK_ANY};
+use crate::msg::{
+    BalanceReconciliation, CanWithdrawResponse, Cw20HookMsg, EffectiveFeeRateResponse,
+    EffectiveHardcapResponse, ExecuteMsg, FeeStateResponse, InstantiateMsg, InventorySkewResponse,
+    MigrateMsg, OpenOrder, PriceSource, QueryMsg, RiskParamsResponse, SimulateDepositResponse,
+    SpotOrderParams, ValidateDepositResponse,
+};
 use crate::response::MsgInstantiateContractResponse;
-use crate::state::CONTRACT_INFO;
+use crate::state::{
+    ContractInfo, OrderMeta, BASE_FEE_COLLECTED, CONTRACT_INFO, LAST_SWAP_ORACLE_PRICE,
+    OPEN_ORDERS, USER_MINTED_SHARES,
+};
 use crate::test::mock_querier::{mock_dependencies, WasmMockQuerier};
 
 const TEST_CONTRACT_ADDR: &str = "inj14hj2tavq8fpesdwxxcu44rty3hh90vhujaxlnz";
 
 const TEST_MARKET_ID: &str = "0x78c2d3af98c517b164070a739681d4bd4d293101e7ffc3a30968945329b47ec6";
 
+const TEST_ATOM_MARKET_ID: &str =
+    "0x0511ddc4e6586f3bfe1acb2dd905f8b8a82c97e1edaef654b12ca7e6031ca0fa";
+
 fn test_deps<'a>() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier, InjectiveQueryWrapper> {
     mock_dependencies(&[], |querier| {
         querier.pyth_price_response_handler = Some(Box::new(create_pyth_price_handler()));
@@ -39,6 +57,16 @@ fn test_deps<'a>() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier, Injective
     })
 }
 
+fn cancel_reply() -> Reply {
+    Reply {
+        id: CANCEL_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    }
+}
+
 fn store_liquidity_token(deps: DepsMut<InjectiveQueryWrapper>, msg_id: u64, contract_addr: String) {
     let data = MsgInstantiateContractResponse {
         contract_address: contract_addr,
@@ -60,6 +88,42 @@ fn store_liquidity_token(deps: DepsMut<InjectiveQueryWrapper>, msg_id: u64, cont
     let _res = reply(deps, inj_mock_env(), reply_msg.clone()).expect("failed to reply");
 }
 
+fn test_instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        base_decimal: 18,
+        quote_decimal: 6,
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "USDT_PRICE_ID".to_string(),
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
+    }
+}
+
+/// Instantiates the vault as `addr0000` with `overrides` applied and stores its LP token.
+fn setup_vault(
+    mut deps: DepsMut<InjectiveQueryWrapper>,
+    overrides: impl FnOnce(&mut InstantiateMsg),
+) {
+    let mut msg = test_instantiate_msg();
+    overrides(&mut msg);
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        instantiate(deps.branch(), inj_mock_env(), info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps, 1, "liquidity0000".to_string());
+}
+
 #[test]
 fn proper_initialization() {
     let mut deps = test_deps();
@@ -88,17 +152,86 @@ fn proper_initialization() {
         quote_price_id: "USDT_PRICE_ID".to_string(),
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
     };
 
     let env = inj_mock_env();
     let info = mock_info("addr0000", &[]);
     let res = instantiate(deps.as_mut(), env, info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::MarketNotFound {
+            market_id: msg.market_id.as_str().to_string(),
+        }
+    );
+
+    // Fail to initialize when price ids are the same
+    let msg = InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        base_decimal: 18,
+        quote_decimal: 6,
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "INJ_PRICE_ID".to_string(),
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Base and quote price ids must differ: INJ_PRICE_ID".to_string(),
+        }
+    );
+
+    // Fail to initialize when a price id doesn't resolve
+    let msg = InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        base_decimal: 18,
+        quote_decimal: 6,
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "UNKNOWN_PRICE_ID".to_string(),
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
     assert_eq!(
         res,
         ContractError::CustomError {
-            val: format!("Market with id: {} not found", msg.market_id.as_str()),
+            val: "Price id does not resolve: UNKNOWN_PRICE_ID".to_string(),
         }
     );
+    assert!(CONTRACT_INFO
+        .may_load(&deps.storage)
+        .expect("failed to load contract info")
+        .is_none());
 
     // Initialize
     let msg = InstantiateMsg {
@@ -110,6 +243,13 @@ fn proper_initialization() {
         quote_price_id: "USDT_PRICE_ID".to_string(),
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
     };
 
     let env = inj_mock_env();
@@ -122,7 +262,7 @@ fn proper_initialization() {
                 code_id: 10u64,
                 msg: to_binary(&TokenInstantiateMsg {
                     name: "INJ-USDT-LP".to_string(),
-                    symbol: "uLP".to_string(),
+                    symbol: "IULP".to_string(),
                     decimals: 12,
                     initial_balances: vec![],
                     mint: Some(MinterResponse {
@@ -184,6 +324,13 @@ fn deposit() {
         quote_price_id: "USDT_PRICE_ID".to_string(),
         hardcap: Uint128::new(5000_000000000000u128),
         token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
     };
 
     let env = inj_mock_env();
@@ -197,25 +344,56 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(10_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDC".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("assets must contain one or two elements").into()
+    );
+
+    // Fail to deposit when a denom is malformed
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "IN J".to_string(),
+                },
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -223,26 +401,30 @@ fn deposit() {
     let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
     assert_eq!(
         res,
-        StdError::generic_err("assets must contain exactly two elements").into()
+        ContractError::InvalidDenom {
+            denom: "IN J".to_string()
+        }
     );
 
     // Fail to deposit when wrong assets provided
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(10_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDC".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -257,19 +439,21 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(10_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(120_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -299,19 +483,21 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(10_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -343,19 +529,21 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(10_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -411,22 +599,35 @@ fn deposit() {
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::zero(),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(100_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(100_000000u128),
+        }],
+    );
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::InvalidZeroAmount {});
+
+    // Fail to deposit with a zero amount coin attached
     let info = mock_info(
         "addr0001",
         &[
@@ -441,25 +642,30 @@ fn deposit() {
         ],
     );
     let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::InvalidZeroAmount {});
+    assert_eq!(
+        res,
+        StdError::generic_err("Supplied coins contain INJ with zero amount").into()
+    );
 
     // Fail to deposit more than hardcap
     let msg = ExecuteMsg::Deposit {
         assets: vec![
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "INJ".to_string(),
                 },
                 amount: Uint128::from(300_000000000000000000u128),
             },
             Asset {
-                info: AssetInfo {
+                info: AssetInfo::NativeToken {
                     denom: "USDT".to_string(),
                 },
                 amount: Uint128::from(2700_000000u128),
             },
         ],
         receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
     let env = inj_mock_env();
@@ -481,115 +687,101 @@ fn deposit() {
 }
 
 #[test]
-fn withdraw_n_fee() {
+fn single_sided_deposit() {
     let mut deps = test_deps();
 
     deps.querier.with_token_balances(&[(
         &"liquidity0000".to_string(),
-        &[(
-            &String::from("addr0001"),
-            &Uint128::new(180_000000000000u128),
-        )],
-    )]);
-    deps.querier.with_balance(&[(
-        &String::from(TEST_CONTRACT_ADDR),
-        &[
-            Coin {
-                denom: "INJ".to_string(),
-                amount: Uint128::from(10_000000000000000000u128),
-            },
-            Coin {
-                denom: "USDT".to_string(),
-                amount: Uint128::from(90_000000u128),
-            },
-        ],
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
     )]);
 
-    let msg = InstantiateMsg {
-        owner: "addr0000".to_string(),
-        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
-        base_decimal: 18,
-        quote_decimal: 6,
-        base_price_id: "INJ_PRICE_ID".to_string(),
-        quote_price_id: "USDT_PRICE_ID".to_string(),
-        hardcap: Uint128::new(5000_000000000000u128),
-        token_code_id: 10u64,
-    };
-
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
-
-    // Store liquidity token
-    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
-
-    // Fail to withdraw when wrong liquidity is provided
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: String::from("addr0001"),
-        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
-        amount: Uint128::new(90_000000000000u128),
-    });
-
-    let env = inj_mock_env();
-    let info = mock_info("liquidity0001", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::Unauthorized {});
+    setup_vault(deps.as_mut(), |_| {});
 
-    // Fail to set fee as non owner
-    let msg = ExecuteMsg::AddFee {
-        base_fee: Uint128::from(1_000000000000000000u128),
-        quote_fee: Uint128::from(9_000000u128),
+    let single_deposit = |denom: &str, amount: u128| ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: denom.to_string(),
+            },
+            amount: Uint128::from(amount),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
     };
 
-    let env = inj_mock_env();
+    // Fail to deposit an asset not in the pool
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDC")]);
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info,
+        single_deposit("USDC", 90_000000u128),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Asset USDC is not in the pool").into()
+    );
+
+    // Fail to deposit a zero amount
     let info = mock_info("addr0001", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(res, ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info,
+        single_deposit("USDT", 0),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::InvalidZeroAmount {});
 
-    // Set fee as owner
-    let msg = ExecuteMsg::AddFee {
-        base_fee: Uint128::from(1_000000000000000000u128),
-        quote_fee: Uint128::from(9_000000u128),
+    // Fail to deposit when the share is below the minimum
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: Some(Uint128::from(90_000000000001u128)),
+        max_share_price: None,
     };
-
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
-
-    // Withdraw
-    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
-        sender: String::from("addr0001"),
-        msg: to_binary(&Cw20HookMsg::Withdraw {}).expect("failed to convert to binary"),
-        amount: Uint128::new(90_000000000000u128),
-    });
-
-    let env = inj_mock_env();
-    let info = mock_info("liquidity0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
-    let log_withdrawn_share = res.attributes.get(2).expect("no log");
-    let log_refund_assets = res.attributes.get(3).expect("no log");
-    let msg_burn_liquidity = res.messages.get(0).expect("no message");
-    let msg_refund_0 = res.messages.get(1).expect("no message");
-    let msg_refund_1 = res.messages.get(2).expect("no message");
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
     assert_eq!(
-        msg_refund_0,
-        &SubMsg {
-            msg: BankMsg::Send {
-                to_address: String::from("addr0001"),
-                amount: vec![Coin::new(4_500000000000000000u128, "INJ",)],
-            }
-            .into(),
-            id: 0,
-            gas_limit: None,
-            reply_on: ReplyOn::Never,
+        res,
+        ContractError::SlippageExceeded {
+            share: Uint128::from(90_000000000000u128),
+            min_lp_out: Uint128::from(90_000000000001u128),
         }
     );
+
+    // 90 USDT is worth 90 at the USDT price of 1
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: Some(Uint128::from(90_000000000000u128)),
+        max_share_price: None,
+    };
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(res.messages.len(), 1);
     assert_eq!(
-        msg_refund_1,
-        &SubMsg {
-            msg: BankMsg::Send {
-                to_address: String::from("addr0001"),
-                amount: vec![Coin::new(40_500000u128, "USDT",)],
+        res.messages[0],
+        SubMsg {
+            msg: WasmMsg::Execute {
+                contract_addr: String::from("liquidity0000"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("addr0001"),
+                    amount: Uint128::from(90_000000000000u128),
+                })
+                .expect("failed to convert to binary"),
+                funds: vec![],
             }
             .into(),
             id: 0,
@@ -597,13 +789,55 @@ fn withdraw_n_fee() {
             reply_on: ReplyOn::Never,
         }
     );
+}
+
+#[test]
+fn deposit_tick_rounding() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // 1.0005 INJ is rounded down to the 0.001 INJ quantity tick
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1_000500000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0001",
+        &[
+            Coin::new(1_000500000000000000u128, "INJ"),
+            Coin::new(100_000000u128, "USDT"),
+        ],
+    );
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
     assert_eq!(
-        msg_burn_liquidity,
-        &SubMsg {
+        res.messages[0],
+        SubMsg {
             msg: WasmMsg::Execute {
                 contract_addr: String::from("liquidity0000"),
-                msg: to_binary(&Cw20ExecuteMsg::Burn {
-                    amount: Uint128::from(90_000000000000u128),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("addr0001"),
+                    amount: Uint128::from(18_000000000000u128),
                 })
                 .expect("failed to convert to binary"),
                 funds: vec![],
@@ -614,226 +848,5014 @@ fn withdraw_n_fee() {
             reply_on: ReplyOn::Never,
         }
     );
-
     assert_eq!(
-        log_withdrawn_share,
+        res.messages[1],
+        SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0001"),
+                amount: vec![
+                    Coin::new(500000000000000u128, "INJ"),
+                    Coin::new(91_000000u128, "USDT"),
+                ],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }
+    );
+}
+
+#[test]
+fn max_share_price() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(90_000000u128, "USDT"),
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let deposit = |max_share_price: Option<Uint128>| ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(9_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price,
+    };
+    let info = mock_info(
+        "addr0002",
+        &[
+            Coin::new(1_000000000000000000u128, "INJ"),
+            Coin::new(9_000000u128, "USDT"),
+        ],
+    );
+
+    // Vault holds 180 USD for 180 LP, so one LP token is worth 1 USD
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info.clone(),
+        deposit(Some(Uint128::from(99_999999u128))),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::SharePriceExceeded {
+            share_price: Uint128::from(1_00000000u128),
+            max_share_price: Uint128::from(99_999999u128),
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        info.clone(),
+        deposit(Some(Uint128::from(1_00000000u128))),
+    )
+    .expect("failed to deposit");
+
+    execute(deps.as_mut(), inj_mock_env(), info, deposit(None)).expect("failed to deposit");
+}
+
+#[test]
+fn per_user_cap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to update the cap as non owner
+    let msg = ExecuteMsg::UpdatePerUserCap {
+        per_user_cap: Some(Uint128::from(150_000000000000u128)),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update cap");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_per_user_cap"),
+            attr("per_user_cap", "150000000000000"),
+        ]
+    );
+
+    let deposit = |receiver: Option<String>| ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    // 90 USDT mints 90 LP, under the cap
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let _res = execute(deps.as_mut(), env.clone(), info, deposit(None)).expect("failed to deposit");
+
+    // Another 90 LP would take addr0001 to 180 LP, over the cap
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), env.clone(), info, deposit(None)).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::ExceedPerUserCap {
+            cap: Uint128::from(150_000000000000u128)
+        }
+    );
+
+    // The cap applies to the receiver of the LP
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        deposit(Some("addr0002".to_string())),
+    )
+    .expect("failed to deposit");
+
+    // Removing the cap lets the deposit through
+    let msg = ExecuteMsg::UpdatePerUserCap { per_user_cap: None };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update cap");
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let _res = execute(deps.as_mut(), env, info, deposit(None)).expect("failed to deposit");
+}
+
+#[test]
+fn treasury() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to update the treasury as non owner
+    let msg = ExecuteMsg::UpdateTreasury {
+        treasury: Some("treasury0000".to_string()),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update treasury");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_treasury"),
+            attr("treasury", "treasury0000"),
+        ]
+    );
+
+    let deposit = |receiver: Option<String>| ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    // Fail to mint LP to the treasury
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        deposit(Some("treasury0000".to_string())),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::TreasuryDeposit {});
+
+    // Fail to deposit from the treasury
+    let info = mock_info("treasury0000", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), env.clone(), info, deposit(None)).unwrap_err();
+    assert_eq!(res, ContractError::TreasuryDeposit {});
+
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let _res = execute(deps.as_mut(), env.clone(), info, deposit(None)).expect("failed to deposit");
+
+    // Withdrawn fees go to the treasury
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::zero(),
+        quote_fee: Uint128::from(1_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::WithdrawFee {
+        base_fee: Uint128::zero(),
+        quote_fee: Uint128::from(1_000000u128),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to withdraw fee");
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "treasury0000".to_string(),
+            amount: vec![Coin::new(1_000000u128, "USDT")],
+        })
+    );
+}
+
+#[test]
+fn deposit_into_empty_vault_with_supply() {
+    let mut deps = test_deps();
+
+    // LP supply is outstanding but the vault holds no balance
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(90_000000000000u128),
+        )],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info("addr0002", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Vault value is zero with outstanding LP supply: 90").into()
+    );
+}
+
+#[test]
+fn tiny_deposit() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // 1 wei of INJ is worth far less than the smallest USDT unit
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(1u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0001",
+        &[Coin::new(1u128, "INJ"), Coin::new(1u128, "USDT")],
+    );
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: String::from("Deposit too small: balanced amounts round to zero")
+        }
+    );
+}
+
+#[test]
+fn deposit_start_time() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let now = inj_mock_env().block.time.seconds();
+    setup_vault(deps.as_mut(), |msg| msg.deposit_start_time = Some(now));
+
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    // Fail to deposit before the start time
+    let mut env = inj_mock_env();
+    env.block.time = Timestamp::from_seconds(now - 10);
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), env, info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "deposits not open yet".to_string()
+        }
+    );
+
+    // Deposit once the start time is reached
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn simulate_deposit() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let assets = |inj_amount: u128, usdt_amount: u128| {
+        vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(inj_amount),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(usdt_amount),
+            },
+        ]
+    };
+
+    // Fail to simulate a deposit above hardcap
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::SimulateDeposit {
+            assets: assets(300_000000000000000000u128, 2700_000000u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err(ContractError::ExceedHardcap {}.to_string())
+    );
+
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::SimulateDeposit {
+            assets: assets(10_000000000000000000u128, 100_000000u128),
+        },
+    )
+    .expect("failed to simulate deposit");
+    let simulation: SimulateDepositResponse =
+        from_binary(&res).expect("failed to parse simulation");
+    assert_eq!(
+        simulation,
+        SimulateDepositResponse {
+            share: Uint128::new(180_000000000000u128),
+            actual_deposits: [
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "INJ".to_string(),
+                    },
+                    amount: Uint128::from(10_000000000000000000u128),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "USDT".to_string(),
+                    },
+                    amount: Uint128::from(90_000000u128),
+                },
+            ],
+            refund: [
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "INJ".to_string(),
+                    },
+                    amount: Uint128::zero(),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "USDT".to_string(),
+                    },
+                    amount: Uint128::from(10_000000u128),
+                },
+            ],
+        }
+    );
+
+    // The real deposit mints the simulated share
+    let info = mock_info(
+        "addr0001",
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(100_000000u128, "USDT"),
+        ],
+    );
+    let msg = ExecuteMsg::Deposit {
+        assets: assets(10_000000000000000000u128, 100_000000u128),
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to deposit");
+    assert!(res
+        .attributes
+        .contains(&attr("share", simulation.share.to_string())));
+}
+
+#[test]
+fn initial_seed() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let seed_msg = ExecuteMsg::InitialSeed {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    };
+    let funds = [
+        Coin::new(10_000000000000000000u128, "INJ"),
+        Coin::new(90_000000u128, "USDT"),
+    ];
+
+    // Fail to seed before the liquidity token is set
+    let info = mock_info("addr0000", &funds);
+    let res = execute(deps.as_mut(), env.clone(), info, seed_msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Cannot seed before the liquidity token is set".to_string()
+        }
+    );
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    // Fail to seed as non owner
+    let info = mock_info("addr0001", &funds);
+    let res = execute(deps.as_mut(), env.clone(), info, seed_msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // The seed value bootstraps one share per unit of value
+    let info = mock_info("addr0000", &funds);
+    let res = execute(deps.as_mut(), env.clone(), info, seed_msg.clone()).expect("failed to seed");
+    assert_eq!(
+        res.messages[0],
+        SubMsg {
+            msg: WasmMsg::Execute {
+                contract_addr: String::from("liquidity0000"),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: String::from("addr0000"),
+                    amount: Uint128::from(180_000000000000u128),
+                })
+                .expect("failed to convert to binary"),
+                funds: vec![],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }
+    );
+    assert!(res.attributes.contains(&attr("initial_seed", "true")));
+
+    // Fail to seed twice
+    let info = mock_info("addr0000", &funds);
+    let res = execute(deps.as_mut(), env, info, seed_msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Vault already seeded".to_string()
+        }
+    );
+}
+
+#[test]
+fn deposit_asset_hardcaps() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+    // Funds are credited to the contract before execution
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let deposit_msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let deposit_info = mock_info(
+        "addr0001",
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+    );
+
+    // Fail to update asset hardcaps as non owner
+    let msg = ExecuteMsg::UpdateAssetHardcaps {
+        base_hardcap: Some(Uint128::from(5_000000000000000000u128)),
+        quote_hardcap: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to deposit more than base hardcap
+    let msg = ExecuteMsg::UpdateAssetHardcaps {
+        base_hardcap: Some(Uint128::from(5_000000000000000000u128)),
+        quote_hardcap: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update asset hardcaps");
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        deposit_info.clone(),
+        deposit_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::ExceedAssetHardcap {
+            denom: "INJ".to_string()
+        }
+    );
+
+    // Fail to deposit more than quote hardcap, the refunded amount is not counted
+    let msg = ExecuteMsg::UpdateAssetHardcaps {
+        base_hardcap: None,
+        quote_hardcap: Some(Uint128::from(89_000000u128)),
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update asset hardcaps");
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        deposit_info.clone(),
+        deposit_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::ExceedAssetHardcap {
+            denom: "USDT".to_string()
+        }
+    );
+
+    // Deposit within both hardcaps
+    let msg = ExecuteMsg::UpdateAssetHardcaps {
+        base_hardcap: Some(Uint128::from(10_000000000000000000u128)),
+        quote_hardcap: Some(Uint128::from(90_000000u128)),
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update asset hardcaps");
+
+    let _res = execute(deps.as_mut(), env, deposit_info, deposit_msg).expect("failed to deposit");
+}
+
+#[test]
+fn locked_liquidity() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[
+            (&String::from(TEST_CONTRACT_ADDR), &Uint128::new(1_000u128)),
+            (
+                &String::from("addr0001"),
+                &Uint128::new(180_000000000000u128),
+            ),
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::LockedLiquidity {})
+        .expect("failed to query locked liquidity");
+    let locked: Uint128 = from_binary(&res).expect("failed to parse locked liquidity");
+    assert_eq!(locked, Uint128::new(1_000u128));
+}
+
+#[test]
+fn deposit_overpayment_policy() {
+    for credit_overpayment in [false, true] {
+        let mut deps = test_deps();
+
+        deps.querier.with_token_balances(&[(
+            &"liquidity0000".to_string(),
+            &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+        )]);
+
+        setup_vault(deps.as_mut(), |msg| {
+            msg.credit_overpayment = credit_overpayment
+        });
+
+        // Deposit with 10 USDT overpaid
+        let msg = ExecuteMsg::Deposit {
+            assets: vec![
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "INJ".to_string(),
+                    },
+                    amount: Uint128::from(10_000000000000000000u128),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "USDT".to_string(),
+                    },
+                    amount: Uint128::from(100_000000u128),
+                },
+            ],
+            receiver: None,
+            min_lp_out: None,
+            max_share_price: None,
+        };
+
+        let env = inj_mock_env();
+        let info = mock_info(
+            "addr0001",
+            &[
+                Coin {
+                    denom: "INJ".to_string(),
+                    amount: Uint128::from(10_000000000000000000u128),
+                },
+                Coin {
+                    denom: "USDT".to_string(),
+                    amount: Uint128::from(100_000000u128),
+                },
+            ],
+        );
+        let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to deposit");
+
+        let res_credit = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Credit {
+                user: "addr0001".to_string(),
+            },
+        )
+        .expect("failed to query credit");
+        let credit: [Uint128; 2] = from_binary(&res_credit).expect("failed to parse credit");
+
+        let refund_msg = SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(10_000000u128, "USDT")],
+        });
+
+        if !credit_overpayment {
+            assert_eq!(res.messages.len(), 2);
+            assert_eq!(res.messages[1], refund_msg);
+            assert_eq!(credit, [Uint128::zero(), Uint128::zero()]);
+            continue;
+        }
+
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(credit, [Uint128::zero(), Uint128::from(10_000000u128)]);
+
+        // Claim credit
+        let info = mock_info("addr0001", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ClaimCredit {})
+            .expect("failed to claim credit");
+        assert_eq!(res.messages, vec![refund_msg]);
+
+        // Fail to claim credit twice
+        let info = mock_info("addr0001", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::ClaimCredit {}).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::CustomError {
+                val: String::from("No credit to claim")
+            }
+        );
+    }
+}
+
+#[test]
+fn check_prices() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    // Check fresh prices as anyone
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::CheckPrices {})
+        .expect("failed to check prices");
+    assert_eq!(res.attributes[0], attr("action", "check_prices"));
+    assert_eq!(res.attributes[1], attr("base_price", "9"));
+    assert_eq!(res.attributes[3], attr("quote_price", "1"));
+
+    // INJ is worth 9 USDT
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::SpotPriceFromOracle {},
+    )
+    .expect("failed to query spot price");
+    let spot_price: Uint128 = from_binary(&res).expect("failed to parse spot price");
+    assert_eq!(spot_price, Uint128::new(9_00000000u128));
+
+    // Fail to check stale prices
+    let now = inj_mock_env().block.time.seconds();
+    let mut env = inj_mock_env();
+    env.block.time = Timestamp::from_seconds(now + 60);
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CheckPrices {}).unwrap_err();
+    assert_eq!(res, ContractError::PriceTooOld {});
+    let res = query(deps.as_ref(), env, QueryMsg::SpotPriceFromOracle {}).unwrap_err();
+    assert_eq!(res, StdError::generic_err("Price too old"));
+
+    // Fail to check prices published after the block time
+    let mut env = inj_mock_env();
+    env.block.time = Timestamp::from_seconds(now - 60);
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::CheckPrices {}).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Price published in the future").into()
+    );
+}
+
+#[test]
+fn market_price_fallback() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        base_price_id: "STALE_INJ_PRICE_ID".to_string(),
+        market_price_fallback: true,
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail when the market has no mid price either
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::Prices {}).unwrap_err();
+    assert_eq!(res, StdError::generic_err("Price too old"));
+
+    // The stale INJ price is derived from the USDT price and the 9.5 mid price
+    deps.querier.spot_market_mid_price_and_tob_handler =
+        Some(Box::new(create_spot_market_mid_price_handler()));
+    let res =
+        query(deps.as_ref(), env.clone(), QueryMsg::Prices {}).expect("failed to query prices");
+    let prices: [Uint128; 2] = from_binary(&res).expect("failed to parse prices");
+    assert_eq!(
+        prices,
+        [Uint128::new(9_50000000u128), Uint128::new(1_00000000u128)]
+    );
+
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::PriceSource {})
+        .expect("failed to query price source");
+    let price_source: PriceSource = from_binary(&res).expect("failed to parse price source");
+    assert_eq!(price_source, PriceSource::MarketMid);
+
+    // The strict Pyth check still fails
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::CheckPrices {}).unwrap_err();
+    assert_eq!(res, ContractError::PriceTooOld {});
+}
+
+#[test]
+fn price_confidence() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    // The wide feed has a 10% confidence interval
+    setup_vault(deps.as_mut(), |msg| {
+        msg.base_price_id = "WIDE_INJ_PRICE_ID".to_string()
+    });
+
+    let deposit_msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+
+    // Fail to deposit against a price with a wide confidence interval
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, deposit_msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Price confidence too wide: WIDE_INJ_PRICE_ID").into()
+    );
+
+    // Fail to update the ratio as non owner
+    let msg = ExecuteMsg::UpdateMaxConfRatio {
+        max_conf_ratio: FPDecimal::from_str("0.2").expect("failed to parse string"),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to update the ratio to zero
+    let msg = ExecuteMsg::UpdateMaxConfRatio {
+        max_conf_ratio: FPDecimal::zero(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+
+    // Switch to the tight feed with a 1% confidence interval
+    let msg = ExecuteMsg::UpdatePriceIds {
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "USDT_PRICE_ID".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to update price ids");
+
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, deposit_msg).expect("failed to deposit");
+    assert_eq!(res.messages.len(), 1);
+
+    // A ratio of 0.5% rejects the tight feed as well
+    let msg = ExecuteMsg::UpdateMaxConfRatio {
+        max_conf_ratio: FPDecimal::from_str("0.005").expect("failed to parse string"),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to update max conf ratio");
+    let res = execute(
+        deps.as_mut(),
+        inj_mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::CheckPrices {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("Price confidence too wide: INJ_PRICE_ID").into()
+    );
+}
+
+#[test]
+fn effective_hardcap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::EffectiveHardcap {})
+        .expect("failed to query effective hardcap");
+    let effective_hardcap: EffectiveHardcapResponse =
+        from_binary(&res).expect("failed to parse effective hardcap");
+    assert_eq!(
+        effective_hardcap,
+        EffectiveHardcapResponse {
+            binding_constraint: "global".to_string(),
+            remaining_shares: Uint128::new(4820_000000000000u128),
+        }
+    );
+
+    // 2 INJ of base headroom allows a deposit worth 36 USD
+    let msg = ExecuteMsg::UpdateAssetHardcaps {
+        base_hardcap: Some(Uint128::from(12_000000000000000000u128)),
+        quote_hardcap: Some(Uint128::from(1000_000000u128)),
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to update asset hardcaps");
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::EffectiveHardcap {})
+        .expect("failed to query effective hardcap");
+    let effective_hardcap: EffectiveHardcapResponse =
+        from_binary(&res).expect("failed to parse effective hardcap");
+    assert_eq!(
+        effective_hardcap,
+        EffectiveHardcapResponse {
+            binding_constraint: "INJ".to_string(),
+            remaining_shares: Uint128::new(36_000000000000u128),
+        }
+    );
+}
+
+#[test]
+fn deposit_and_rebalance() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    setup_vault(deps.as_mut(), |_| {});
+
+    let msg = ExecuteMsg::DepositAndRebalance {
+        asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "INJ".to_string(),
+            },
+            amount: Uint128::from(10_000000000000000000u128),
+        },
+        max_slippage_bps: 100,
+        receiver: None,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info(
+        "addr0001",
+        &[Coin {
+            denom: "INJ".to_string(),
+            amount: Uint128::from(10_000000000000000000u128),
+        }],
+    );
+    let res =
+        execute(deps.as_mut(), env.clone(), info, msg).expect("failed to deposit and rebalance");
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: String::from("addr0001"),
+                amount: Uint128::from(90_000000000000u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    // Sell half the INJ no lower than 1% below the oracle price of 9 USDT
+    let order_message = get_message_data(&res.messages, 1);
+    assert_eq!(
+        InjectiveRoute::Exchange,
+        order_message.route,
+        "route was incorrect"
+    );
+    match &order_message.msg_data {
+        InjectiveMsg::CreateSpotMarketOrder { sender, order } => {
+            assert_eq!(sender, &env.contract.address);
+            assert_eq!(order.market_id, market_id);
+            assert_eq!(order.order_type, OrderType::Sell);
+            assert_eq!(
+                order.order_info.price,
+                FPDecimal::from_str("0.00000000000891").expect("failed to parse string")
+            );
+            assert_eq!(
+                order.order_info.quantity,
+                FPDecimal::from(5_000000000000000000u128)
+            );
+        }
+        _ => panic!("No spot market order found"),
+    }
+
+    // The buy price is rounded down and the quantity down to the market ticks
+    let usdt_deposit = ExecuteMsg::DepositAndRebalance {
+        asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(100_000000u128),
+        },
+        max_slippage_bps: 33,
+        receiver: None,
+    };
+    let info = mock_info("addr0001", &[Coin::new(100_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), env.clone(), info, usdt_deposit.clone())
+        .expect("failed to deposit and rebalance");
+    match &get_message_data(&res.messages, 1).msg_data {
+        InjectiveMsg::CreateSpotMarketOrder { order, .. } => {
+            assert_eq!(order.order_type, OrderType::Buy);
+            assert_eq!(
+                order.order_info.price,
+                FPDecimal::from_str("0.000000000009029").expect("failed to parse string")
+            );
+            assert_eq!(
+                order.order_info.quantity,
+                FPDecimal::from(5_537000000000000000u128)
+            );
+        }
+        _ => panic!("No spot market order found"),
+    }
+
+    // The rebalance order goes through the same checks as a swap
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::UpdateMinOrderNotional {
+        min_order_notional: FPDecimal::from(100_000000u128),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).expect("failed to update min order notional");
+    let info = mock_info("addr0001", &[Coin::new(100_000000u128, "USDT")]);
+    let res = execute(deps.as_mut(), env, info, usdt_deposit).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: notional 49993573 below min_order_notional: 100000000".to_string()
+        }
+    );
+}
+
+#[test]
+fn validate_deposit() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |msg| {
+        msg.hardcap = Uint128::new(10_000000000000u128)
+    });
+
+    let validate_msg_for =
+        |inj_amount: u128, usdt_amount: u128, receiver: Option<&str>| QueryMsg::ValidateDeposit {
+            assets: vec![
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "INJ".to_string(),
+                    },
+                    amount: Uint128::from(inj_amount),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "USDT".to_string(),
+                    },
+                    amount: Uint128::from(usdt_amount),
+                },
+            ],
+            receiver: receiver.map(str::to_string),
+        };
+    let validate_msg =
+        |inj_amount: u128, usdt_amount: u128| validate_msg_for(inj_amount, usdt_amount, None);
+
+    // Deposit worth 18 shares exceeds the hardcap of 10 shares
+    let res: ValidateDepositResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            validate_msg(1_000000000000000000u128, 9_000000u128),
+        )
+        .expect("failed to query"),
+    )
+    .expect("failed to parse response");
+    assert_eq!(
+        res,
+        ValidateDepositResponse {
+            within_hardcap: false,
+            within_user_cap: true,
+            allowlisted: true,
+            not_paused: true,
+            deposits_open: true,
+            prices_fresh: true,
+            shares: Uint128::new(18_000000000000u128),
+        }
+    );
+
+    let res: ValidateDepositResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            validate_msg(500000000000000000u128, 4_500000u128),
+        )
+        .expect("failed to query"),
+    )
+    .expect("failed to parse response");
+    assert!(res.within_hardcap);
+    assert_eq!(res.shares, Uint128::new(9_000000000000u128));
+
+    // Deposits are reported closed before the start time, as `deposit` rejects them
+    let mut contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    contract_info.deposit_start_time = Some(env.block.time.seconds() + 1);
+    CONTRACT_INFO
+        .save(deps.as_mut().storage, &contract_info)
+        .expect("failed to save contract info");
+    let res: ValidateDepositResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            validate_msg(500000000000000000u128, 4_500000u128),
+        )
+        .expect("failed to query"),
+    )
+    .expect("failed to parse response");
+    assert!(!res.deposits_open);
+
+    // The per-user cap counts the LP already minted to the receiver
+    let info = mock_info("addr0000", &[]);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::UpdatePerUserCap {
+            per_user_cap: Some(Uint128::new(10_000000000000u128)),
+        },
+    )
+    .expect("failed to update per user cap");
+    USER_MINTED_SHARES
+        .save(
+            deps.as_mut().storage,
+            &Addr::unchecked("addr0001"),
+            &Uint128::new(2_000000000000u128),
+        )
+        .expect("failed to save minted shares");
+    let res: ValidateDepositResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            validate_msg(500000000000000000u128, 4_500000u128),
+        )
+        .expect("failed to query"),
+    )
+    .expect("failed to parse response");
+    assert!(res.within_user_cap);
+    let msg = validate_msg_for(500000000000000000u128, 4_500000u128, Some("addr0001"));
+    let res: ValidateDepositResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), msg).expect("failed to query"))
+            .expect("failed to parse response");
+    assert!(!res.within_user_cap);
+
+    // Pausing the vault is reported as well
+    let info = mock_info("addr0000", &[]);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: true },
+    )
+    .expect("failed to pause");
+    let res: ValidateDepositResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            validate_msg(500000000000000000u128, 4_500000u128),
+        )
+        .expect("failed to query"),
+    )
+    .expect("failed to parse response");
+    assert!(!res.not_paused);
+}
+
+#[test]
+fn maker_volume() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    // Zero when the volume query is unavailable
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::MakerVolume {})
+        .expect("failed to query maker volume");
+    let maker_volume: FPDecimal = from_binary(&res).expect("failed to parse maker volume");
+    assert_eq!(maker_volume, FPDecimal::zero());
+
+    // Only the vault market volume counts
+    deps.querier.aggregate_account_volume_handler =
+        Some(Box::new(create_aggregate_account_volume_handler()));
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::MakerVolume {})
+        .expect("failed to query maker volume");
+    let maker_volume: FPDecimal = from_binary(&res).expect("failed to parse maker volume");
+    assert_eq!(maker_volume, i32_to_dec(1500));
+}
+
+#[test]
+fn pending_order_notional() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    // Zero without resting orders
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::PendingOrderNotional {},
+    )
+    .expect("failed to query pending order notional");
+    let notional: FPDecimal = from_binary(&res).expect("failed to parse pending order notional");
+    assert_eq!(notional, FPDecimal::zero());
+
+    // Only the unfilled part of each order is committed
+    deps.querier.trader_spot_orders_response_handler =
+        Some(Box::new(create_trader_spot_orders_handler()));
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::PendingOrderNotional {},
+    )
+    .expect("failed to query pending order notional");
+    let notional: FPDecimal = from_binary(&res).expect("failed to parse pending order notional");
+    assert_eq!(notional, i32_to_dec(7200));
+}
+
+#[test]
+fn zero_decimal() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        quote_decimal: 0,
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Base and quote decimals must be positive".to_string()
+        }
+    );
+
+    let msg = InstantiateMsg {
+        base_decimal: 0,
+        quote_decimal: 6,
+        ..msg
+    };
+    let res = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Base and quote decimals must be positive".to_string()
+        }
+    );
+}
+
+#[test]
+fn lp_token_metadata() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        lp_token_name: Some("Vault One LP".to_string()),
+        lp_token_symbol: Some("VONE".to_string()),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { msg, .. }) => {
+            let token_msg: TokenInstantiateMsg =
+                from_binary(msg).expect("failed to parse token instantiate msg");
+            assert_eq!(token_msg.name, "Vault One LP");
+            assert_eq!(token_msg.symbol, "VONE");
+            assert_eq!(token_msg.decimals, 12);
+        }
+        _ => panic!("No token instantiate message found"),
+    }
+}
+
+#[test]
+fn lp_decimal() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    // Fail to initialize with more than 18 LP decimals
+    let msg = InstantiateMsg {
+        hardcap: Uint128::new(5000_000000000000000000u128),
+        lp_decimal: Some(19),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
+    match res {
+        Err(ContractError::CustomError { val }) => {
+            assert_eq!(val, "LP decimal must not exceed 18")
+        }
+        _ => panic!("Must return custom error"),
+    }
+
+    let msg = InstantiateMsg {
+        lp_decimal: Some(18),
+        ..msg
+    };
+    let res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { msg, .. }) => {
+            let token_msg: TokenInstantiateMsg =
+                from_binary(msg).expect("failed to parse token instantiate msg");
+            assert_eq!(token_msg.decimals, 18);
+        }
+        _ => panic!("No token instantiate message found"),
+    }
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    // First deposit of 180 USDT in value mints 180 LP with 18 decimals
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0001",
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    );
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: String::from("addr0001"),
+                amount: Uint128::from(180_000000000000000000u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    // A tenth of the vault value mints a tenth of the supply
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(9_000000u128),
+            },
+        ],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info(
+        "addr0002",
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(9_000000u128),
+            },
+        ],
+    );
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to deposit");
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: String::from("addr0002"),
+                amount: Uint128::from(18_000000000000000000u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    // The minted shares redeem the deposited assets back
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::TokensForShares {
+            share: Uint128::from(18_000000000000000000u128),
+        },
+    )
+    .expect("failed to query tokens for shares");
+    let tokens: [Uint128; 2] = from_binary(&res).expect("failed to parse tokens");
+    assert_eq!(
+        tokens,
+        [
+            Uint128::from(1_000000000000000000u128),
+            Uint128::from(9_000000u128)
+        ]
+    );
+}
+
+#[test]
+fn owner_fee_claimable() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(5_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::from(10_000000000000000000u128),
+        quote_fee: Uint128::from(50_000000u128),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    // The base fee counter exceeds the balance, only the balance is claimable
+    let res = query(deps.as_ref(), env, QueryMsg::OwnerFeeClaimable {})
+        .expect("failed to query owner fee claimable");
+    let claimable: [Asset; 2] = from_binary(&res).expect("failed to parse claimable fee");
+    assert_eq!(
+        claimable,
+        [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(5_000000000000000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(50_000000u128),
+            },
+        ]
+    );
+}
+
+#[test]
+fn migration() {
+    let mut deps = test_deps();
+
+    // Contract info as stored by vaults deployed at the baseline, before any of the later fields
+    let baseline_vault = |liquidity_token: &str| {
+        format!(
+            r#"{{"market_id":"{TEST_MARKET_ID}","base_denom":"INJ","quote_denom":"USDT","base_decimal":18,"quote_decimal":6,"base_price_id":"INJ_PRICE_ID","quote_price_id":"USDT_PRICE_ID","hardcap":"5000000000000000","liquidity_token":"{liquidity_token}","contract_subaccount_id":"0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000"}}"#
+        )
+    };
+
+    // Fail to migrate before the liquidity token is set
+    deps.storage.set(b"vault", baseline_vault("").as_bytes());
+    let env = inj_mock_env();
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Cannot migrate before the liquidity token is set".to_string()
+        }
+    );
+
+    deps.storage
+        .set(b"vault", baseline_vault("liquidity0000").as_bytes());
+    let res = migrate(deps.as_mut(), env, MigrateMsg {}).expect("failed to migrate");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "migrate"),
+            attr("previous_version", "0"),
+            attr("contract_version", "1"),
+        ]
+    );
+
+    // Fields added after the baseline load with their defaults
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.contract_version, 1);
+    assert_eq!(contract_info.lp_decimal, 12);
+    assert_eq!(contract_info.min_order_notional, FPDecimal::zero());
+    assert!(!contract_info.credit_overpayment);
+    assert_eq!(contract_info.base_hardcap, None);
+    assert_eq!(contract_info.quote_hardcap, None);
+    assert_eq!(contract_info.max_slippage_bps, None);
+    assert_eq!(contract_info.max_spread_bps, None);
+    assert_eq!(contract_info.max_price_jump_bps, None);
+    assert!(!contract_info.paused);
+}
+
+#[test]
+fn withdraw_to_subaccount() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let subaccount_id = SubaccountId::new(
+        "0xaf79152ac5df276d9a8e1e2e22822f9713474902000000000000000000000001".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: Some(subaccount_id.clone()),
+            max_imbalance_bps: None,
+            target_denom: None,
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+
+    let env = inj_mock_env();
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(create_deposit_msg(
+            env.contract.address.clone(),
+            subaccount_id.clone(),
+            Coin::new(5_000000000000000000u128, "INJ"),
+        ))
+    );
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(create_deposit_msg(
+            env.contract.address,
+            subaccount_id,
+            Coin::new(45_000000u128, "USDT"),
+        ))
+    );
+}
+
+#[test]
+fn withdrawable_assets() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[
+            (
+                &String::from("addr0001"),
+                &Uint128::new(90_000000000000u128),
+            ),
+            (
+                &String::from("addr0002"),
+                &Uint128::new(90_000000000000u128),
+            ),
+        ],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "ATOM".to_string(),
+                amount: Uint128::from(10_000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(2_000000000000000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |msg| {
+        msg.market_id =
+            MarketId::new(TEST_ATOM_MARKET_ID.to_string()).expect("failed to create market_id");
+        msg.base_decimal = 6;
+        msg.base_price_id = "ATOM_PRICE_ID".to_string();
+    });
+
+    // Half of the supply gets half of each balance, including the INJ residue
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::WithdrawableAssets {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query withdrawable assets");
+    let assets: Vec<Asset> = from_binary(&res).expect("failed to parse withdrawable assets");
+    assert_eq!(
+        assets,
+        vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "ATOM".to_string(),
+                },
+                amount: Uint128::from(5_000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(45_000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+        ]
+    );
+
+    // Nothing for a user without shares
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::WithdrawableAssets {
+            user: "addr0003".to_string(),
+        },
+    )
+    .expect("failed to query withdrawable assets");
+    let assets: Vec<Asset> = from_binary(&res).expect("failed to parse withdrawable assets");
+    assert!(assets.is_empty());
+}
+
+#[test]
+fn collect_residue() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[
+            (
+                &String::from("addr0001"),
+                &Uint128::new(90_000000000000u128),
+            ),
+            (
+                &String::from("addr0002"),
+                &Uint128::new(90_000000000000u128),
+            ),
+        ],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "ATOM".to_string(),
+                amount: Uint128::from(10_000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(2_000000000000000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |msg| {
+        msg.market_id =
+            MarketId::new(TEST_ATOM_MARKET_ID.to_string()).expect("failed to create market_id");
+        msg.base_decimal = 6;
+        msg.base_price_id = "ATOM_PRICE_ID".to_string();
+    });
+
+    // Fail to collect as non owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CollectResidue {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CollectResidue {},
+    )
+    .expect("failed to collect residue");
+    assert_eq!(
+        res.attributes[1],
+        attr("residue_collected", "2000000000000000000")
+    );
+
+    // Nothing left to collect
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CollectResidue {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: String::from("No residue to collect")
+        }
+    );
+
+    // The collected residue is no longer refunded to withdrawers
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::WithdrawableAssets {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query withdrawable assets");
+    let assets: Vec<Asset> = from_binary(&res).expect("failed to parse withdrawable assets");
+    assert_eq!(
+        assets,
+        vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "ATOM".to_string(),
+                },
+                amount: Uint128::from(5_000000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(45_000000u128),
+            },
+        ]
+    );
+
+    // It is forwarded with the fees
+    let msg = ExecuteMsg::ForwardFees {
+        fee_vault: "feevault0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to forward fees");
+    assert_eq!(
+        res.messages,
+        vec![SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("feevault0000"),
+                amount: vec![Coin::new(2_000000000000000000u128, "INJ")],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }]
+    );
+    assert_eq!(
+        res.attributes[3],
+        attr("residue_forwarded", "2000000000000000000")
+    );
+}
+
+#[test]
+fn withdraw_curve() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "ATOM".to_string(),
+                amount: Uint128::from(10_000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(2_000000000000000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |msg| {
+        msg.market_id =
+            MarketId::new(TEST_ATOM_MARKET_ID.to_string()).expect("failed to create market_id");
+        msg.base_decimal = 6;
+        msg.base_price_id = "ATOM_PRICE_ID".to_string();
+    });
+
+    // Fail to query too many steps
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::WithdrawCurve {
+            min: Uint128::zero(),
+            max: Uint128::from(180_000000000000u128),
+            steps: 1000,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("steps must be between 1 and 100")
+    );
+
+    // Fail to query past the total supply
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::WithdrawCurve {
+            min: Uint128::zero(),
+            max: Uint128::from(200_000000000000u128),
+            steps: 4,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        StdError::generic_err("max must not exceed total supply 180000000000000")
+    );
+
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::WithdrawCurve {
+            min: Uint128::zero(),
+            max: Uint128::from(180_000000000000u128),
+            steps: 4,
+        },
+    )
+    .expect("failed to query withdraw curve");
+    let curve: Vec<(Uint128, Vec<Asset>)> =
+        from_binary(&res).expect("failed to parse withdraw curve");
+    assert_eq!(curve.len(), 5);
+    assert_eq!(curve[2].0, Uint128::from(90_000000000000u128));
+    assert_eq!(curve[2].1[0].amount, Uint128::from(5_000000u128));
+    assert_eq!(curve[2].1[1].amount, Uint128::from(45_000000u128));
+    assert_eq!(
+        curve[2].1[2].amount,
+        Uint128::from(1_000000000000000000u128)
+    );
+    for points in curve.windows(2) {
+        assert!(points[0].0 < points[1].0);
+        for (asset0, asset1) in points[0].1.iter().zip(points[1].1.iter()) {
+            assert!(asset0.amount < asset1.amount);
+        }
+    }
+}
+
+#[test]
+fn sweep_token() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+            Coin {
+                denom: "AIRDROP".to_string(),
+                amount: Uint128::from(500_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail to sweep as non owner
+    let msg = ExecuteMsg::SweepToken {
+        denom: "AIRDROP".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to sweep a denom the vault doesn't hold
+    let msg = ExecuteMsg::SweepToken {
+        denom: "ATOM".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: String::from("No ATOM balance to sweep")
+        }
+    );
+
+    let msg = ExecuteMsg::SweepToken {
+        denom: "AIRDROP".to_string(),
+        recipient: "addr0002".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to sweep token");
+    assert_eq!(
+        res.messages,
+        vec![SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0002"),
+                amount: vec![Coin::new(500_000000u128, "AIRDROP")],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }]
+    );
+    assert_eq!(res.attributes[2], attr("swept", "500000000AIRDROP"));
+}
+
+#[test]
+fn sweep_vault_denom() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "USDT".to_string(),
+            amount: Uint128::from(90_000000u128),
+        }],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    for denom in ["USDT", "INJ"] {
+        let msg = ExecuteMsg::SweepToken {
+            denom: denom.to_string(),
+            recipient: "addr0002".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::CustomError {
+                val: format!("Can't sweep vault denom {denom}")
+            }
+        );
+    }
+}
+
+#[test]
+fn update_hardcap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to update hardcap as non owner
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdateHardcap {
+        hardcap: Uint128::new(200_000000000000u128),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to lower hardcap below current supply
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::UpdateHardcap {
+            hardcap: Uint128::new(100_000000000000u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Hardcap 100000000000000 below current supply: 180000000000000".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to update hardcap");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_hardcap"),
+            attr("hardcap", "200000000000000"),
+        ]
+    );
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.hardcap, Uint128::new(200_000000000000u128));
+}
+
+#[test]
+fn update_price_ids() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Fail to update price ids as non owner
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdatePriceIds {
+        base_price_id: "INJ_PRICE_ID_V2".to_string(),
+        quote_price_id: "USDT_PRICE_ID".to_string(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to update to a price id without price state, keeping the old ids
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::UpdatePriceIds {
+            base_price_id: "UNKNOWN_PRICE_ID".to_string(),
+            quote_price_id: "USDT_PRICE_ID".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Price id does not resolve: UNKNOWN_PRICE_ID".to_string()
+        }
+    );
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.base_price_id, "INJ_PRICE_ID");
+    assert_eq!(contract_info.quote_price_id, "USDT_PRICE_ID");
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to update price ids");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_price_ids"),
+            attr("old_base_price_id", "INJ_PRICE_ID"),
+            attr("base_price_id", "INJ_PRICE_ID_V2"),
+            attr("old_quote_price_id", "USDT_PRICE_ID"),
+            attr("quote_price_id", "USDT_PRICE_ID"),
+        ]
+    );
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.base_price_id, "INJ_PRICE_ID_V2");
+}
+
+#[test]
+fn existing_lp_token() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        existing_lp_token: Some("LPToken0000".to_string()),
+        ..test_instantiate_msg()
+    };
+
+    // Fail to instantiate with an invalid token address
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg.clone()).unwrap_err();
+
+    let msg = InstantiateMsg {
+        existing_lp_token: Some("lptoken0000".to_string()),
+        deposit_start_time: None,
+        ..msg
+    };
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("method", "instantiate"),
+            attr("liquidity_token_addr", "lptoken0000")
+        ]
+    );
+
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(
+        contract_info.liquidity_token,
+        Addr::unchecked("lptoken0000")
+    );
+}
+
+#[test]
+fn config() {
+    let mut deps = test_deps();
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let res =
+        query(deps.as_ref(), env.clone(), QueryMsg::Config {}).expect("failed to query config");
+    let config: ContractInfo = from_binary(&res).expect("failed to parse config");
+    assert_eq!(
+        config,
+        ContractInfo {
+            market_id,
+            base_denom: "INJ".to_string(),
+            quote_denom: "USDT".to_string(),
+            base_decimal: 18,
+            quote_decimal: 6,
+            base_price_id: "INJ_PRICE_ID".to_string(),
+            quote_price_id: "USDT_PRICE_ID".to_string(),
+            hardcap: Uint128::new(5000_000000000000u128),
+            base_hardcap: None,
+            quote_hardcap: None,
+            credit_overpayment: false,
+            min_order_notional: FPDecimal::zero(),
+            max_slippage_bps: None,
+            max_spread_bps: None,
+            max_price_jump_bps: None,
+            max_conf_ratio: FPDecimal::from_str("0.02").expect("failed to parse string"),
+            deposit_start_time: None,
+            max_open_orders: None,
+            paused: false,
+            per_user_cap: None,
+            market_price_fallback: false,
+            lp_decimal: 12,
+            treasury: None,
+            liquidity_token: Addr::unchecked("liquidity0000"),
+            contract_subaccount_id: get_default_subaccount_id_for_checked_address(
+                &env.contract.address
+            ),
+            contract_version: 1,
+        }
+    );
+}
+
+#[test]
+fn instantiate_params() {
+    let mut deps = test_deps();
+
+    let msg = InstantiateMsg {
+        credit_overpayment: true,
+        lp_decimal: Some(8),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res =
+        instantiate(deps.as_mut(), env.clone(), info, msg.clone()).expect("failed to instantiate");
+
+    let res = query(deps.as_ref(), env, QueryMsg::InstantiateParams {})
+        .expect("failed to query instantiate params");
+    let params: InstantiateMsg = from_binary(&res).expect("failed to parse instantiate params");
+    assert_eq!(params, msg);
+}
+
+#[test]
+fn withdraw_imbalance_guard() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    // The vault holds 90 of value in INJ and only 10 in USDT
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(10_000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let withdraw_msg = |max_imbalance_bps: Option<u64>| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("addr0001"),
+            msg: to_binary(&Cw20HookMsg::Withdraw {
+                to_subaccount: None,
+                max_imbalance_bps,
+                target_denom: None,
+            })
+            .expect("failed to convert to binary"),
+            amount: Uint128::new(90_000000000000u128),
+        })
+    };
+
+    // Refunds of 45 in INJ and 5 in USDT are 8000 bps apart
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, withdraw_msg(Some(5000))).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Withdraw: refund imbalance 8000 bps exceeds 5000 bps".to_string()
+        }
+    );
+
+    let info = mock_info("liquidity0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, withdraw_msg(Some(8000)))
+        .expect("failed to withdraw");
+
+    let info = mock_info("liquidity0000", &[]);
+    let _res = execute(deps.as_mut(), env, info, withdraw_msg(None)).expect("failed to withdraw");
+}
+
+#[test]
+fn forward_fees() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Nothing to forward yet
+    let msg = ExecuteMsg::ForwardFees {
+        fee_vault: "feevault0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: String::from("No fees to forward")
+        }
+    );
+
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    // Fail to forward fees as non owner
+    let msg = ExecuteMsg::ForwardFees {
+        fee_vault: "feevault0000".to_string(),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to forward fees to an invalid address
+    let msg = ExecuteMsg::ForwardFees {
+        fee_vault: "FeeVault0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+
+    // Forward fees to the fee vault
+    let msg = ExecuteMsg::ForwardFees {
+        fee_vault: "feevault0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to forward fees");
+    assert_eq!(
+        res.messages,
+        vec![SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("feevault0000"),
+                amount: vec![
+                    Coin::new(1_000000000000000000u128, "INJ",),
+                    Coin::new(9_000000u128, "USDT",)
+                ],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }]
+    );
+    assert_eq!(
+        res.attributes[2],
+        attr("fee_forwarded", "1000000000000000000INJ, 9000000USDT")
+    );
+
+    // Counters are zeroed
+    let res = query(deps.as_ref(), env, QueryMsg::FeeState {}).expect("failed to query fee state");
+    let fee_state: FeeStateResponse = from_binary(&res).expect("failed to parse fee state");
+    assert_eq!(fee_state.base_fee_collected.amount, Uint128::zero());
+    assert_eq!(fee_state.quote_fee_collected.amount, Uint128::zero());
+}
+
+#[test]
+fn balance_reconciliation() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(0))],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let info = mock_info("addr0001", &[Coin::new(90_000000u128, "USDT")]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to deposit");
+
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::zero(),
+        quote_fee: Uint128::from(5_000000u128),
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    // 1 INJ and 10 USDT are donated on top of the deposit
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(100_000000u128),
+            },
+        ],
+    )]);
+
+    let res = query(deps.as_ref(), env, QueryMsg::BalanceReconciliation {})
+        .expect("failed to query balance reconciliation");
+    let reconciliation: [BalanceReconciliation; 2] =
+        from_binary(&res).expect("failed to parse balance reconciliation");
+    assert_eq!(
+        reconciliation,
+        [
+            BalanceReconciliation {
+                denom: "INJ".to_string(),
+                balance: Uint128::from(1_000000000000000000u128),
+                accounted: Uint128::zero(),
+                surplus: Uint128::from(1_000000000000000000u128),
+                deficit: Uint128::zero(),
+            },
+            BalanceReconciliation {
+                denom: "USDT".to_string(),
+                balance: Uint128::from(100_000000u128),
+                accounted: Uint128::from(90_000000u128),
+                surplus: Uint128::from(10_000000u128),
+                deficit: Uint128::zero(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn fees_above_balance() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(100_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(10_000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // The base fee counter exceeds the base balance
+    BASE_FEE_COLLECTED
+        .save(
+            deps.as_mut().storage,
+            &Uint128::from(2_000000000000000000u128),
+        )
+        .expect("failed to save base fee");
+
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::TotalLiquidity {})
+        .expect("failed to query total liquidity");
+    let liquidity: [Uint128; 2] = from_binary(&res).expect("failed to parse total liquidity");
+    assert_eq!(liquidity, [Uint128::zero(), Uint128::from(10_000000u128)]);
+
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::UserLiquidity {
+            user: "addr0001".to_string(),
+        },
+    )
+    .expect("failed to query user liquidity");
+    let liquidity: [Asset; 2] = from_binary(&res).expect("failed to parse user liquidity");
+    assert_eq!(liquidity[0].amount, Uint128::zero());
+    assert_eq!(liquidity[1].amount, Uint128::from(10_000000u128));
+}
+
+#[test]
+fn can_withdraw() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(100_000000000000u128),
+        )],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let can_withdraw =
+        |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, InjectiveQueryWrapper>,
+         amount: u128|
+         -> CanWithdrawResponse {
+            let res = query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CanWithdraw {
+                    user: "addr0001".to_string(),
+                    amount: Uint128::new(amount),
+                },
+            )
+            .expect("failed to query can withdraw");
+            from_binary(&res).expect("failed to parse can withdraw")
+        };
+
+    // The vault holds no bank balance
+    assert_eq!(
+        can_withdraw(&deps, 10_000000000000u128),
+        CanWithdrawResponse {
+            can_withdraw: false,
+            reason: Some("Insufficient vault liquidity".to_string()),
+        }
+    );
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(5_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(50_000000u128),
+            },
+        ],
+    )]);
+
+    assert_eq!(
+        can_withdraw(&deps, 10_000000000000u128),
+        CanWithdrawResponse {
+            can_withdraw: true,
+            reason: None,
+        }
+    );
+    assert_eq!(
+        can_withdraw(&deps, 0),
+        CanWithdrawResponse {
+            can_withdraw: false,
+            reason: Some("Can't withdraw zero amount".to_string()),
+        }
+    );
+    assert_eq!(
+        can_withdraw(&deps, 200_000000000000u128),
+        CanWithdrawResponse {
+            can_withdraw: false,
+            reason: Some("Insufficient LP balance: 100000000000000".to_string()),
+        }
+    );
+}
+
+#[test]
+fn burn_contract_lp() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[
+            (&String::from(TEST_CONTRACT_ADDR), &Uint128::new(1000u128)),
+            (
+                &String::from("addr0001"),
+                &Uint128::new(10_000000000000u128),
+            ),
+        ],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(1_000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(10_000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to burn as non owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::BurnContractLp {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to burn while another holder remains
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::BurnContractLp {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "LP holders remain: 10000000000000".to_string()
+        }
+    );
+
+    // The last holder exited, only the locked LP is left
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&String::from(TEST_CONTRACT_ADDR), &Uint128::new(1000u128))],
+    )]);
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::BurnContractLp {})
+        .expect("failed to burn contract lp");
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg {
+                msg: WasmMsg::Execute {
+                    contract_addr: String::from("liquidity0000"),
+                    msg: to_binary(&Cw20ExecuteMsg::Burn {
+                        amount: Uint128::from(1000u128),
+                    })
+                    .expect("failed to convert to binary"),
+                    funds: vec![],
+                }
+                .into(),
+                id: 0,
+                gas_limit: None,
+                reply_on: ReplyOn::Never,
+            },
+            SubMsg {
+                msg: BankMsg::Send {
+                    to_address: String::from("addr0000"),
+                    amount: vec![
+                        Coin::new(1_000000000000000u128, "INJ"),
+                        Coin::new(10_000u128, "USDT"),
+                    ],
+                }
+                .into(),
+                id: 0,
+                gas_limit: None,
+                reply_on: ReplyOn::Never,
+            },
+        ]
+    );
+}
+
+#[test]
+fn partial_withdrawal() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Resting orders lock 2 INJ units on the sell side and 5000 USDT units on the buy side
+    deps.querier.trader_spot_orders_response_handler =
+        Some(Box::new(create_trader_spot_orders_handler()));
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: None,
+            max_imbalance_bps: None,
+            target_denom: None,
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(180_000000000000u128),
+    });
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).expect("failed to withdraw");
+
+    // USDT is the short side, so only the shares worth the free 89.995 USDT are burned
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: Uint128::new(179_990000000000u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(9_999444444444444444u128, "INJ")],
+        })
+    );
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(89_995000u128, "USDT")],
+        })
+    );
+    assert_eq!(
+        res.messages[3],
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: String::from("liquidity0000"),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: String::from("addr0001"),
+                amount: Uint128::new(10000000000u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+    assert!(res.attributes.contains(&attr("partial_withdrawal", "true")));
+    assert!(res
+        .attributes
+        .contains(&attr("returned_share", "10000000000")));
+}
+
+#[test]
+fn cw20_asset_msgs() {
+    let asset = Asset {
+        info: AssetInfo::Token {
+            contract_addr: Addr::unchecked("token0000"),
+        },
+        amount: Uint128::new(100u128),
+    };
+    assert!(!asset.info.is_native_token());
+    assert!(!asset.info.equal(&AssetInfo::NativeToken {
+        denom: "token0000".to_string(),
+    }));
+    asset
+        .as_coin()
+        .expect_err("CW20 assets can't be converted to coins");
+
+    let msg: CosmosMsg = asset
+        .clone()
+        .into_msg("addr0001")
+        .expect("failed to build refund message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::new(100u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    let msg: CosmosMsg = asset
+        .into_transfer_from_msg("addr0001", TEST_CONTRACT_ADDR)
+        .expect("failed to build transfer from message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: "addr0001".to_string(),
+                recipient: TEST_CONTRACT_ADDR.to_string(),
+                amount: Uint128::new(100u128),
+            })
+            .expect("failed to convert to binary"),
+            funds: vec![],
+        })
+    );
+
+    // Native refunds still go through the bank module
+    let msg: CosmosMsg = Asset {
+        info: AssetInfo::NativeToken {
+            denom: "USDT".to_string(),
+        },
+        amount: Uint128::new(100u128),
+    }
+    .into_msg("addr0001")
+    .expect("failed to build refund message");
+    assert_eq!(
+        msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(100u128, "USDT")],
+        })
+    );
+}
+
+#[test]
+fn withdraw_to_target_denom() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    setup_vault(deps.as_mut(), |_| {});
+
+    let withdraw_msg = |target_denom: &str| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("addr0001"),
+            msg: to_binary(&Cw20HookMsg::Withdraw {
+                to_subaccount: None,
+                max_imbalance_bps: None,
+                target_denom: Some(target_denom.to_string()),
+            })
+            .expect("failed to convert to binary"),
+            amount: Uint128::new(18_000000000000u128),
+        })
+    };
+
+    // Fail to withdraw into a denom outside the pool
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, withdraw_msg("ATOM")).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Withdraw: target denom ATOM is not a pool denom".to_string()
+        }
+    );
+
+    // Fail to consolidate without a slippage bound
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, withdraw_msg("USDT")).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Withdraw: consolidation requires max_slippage_bps".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let update_msg = ExecuteMsg::UpdateRiskParams {
+        max_slippage_bps: Some(100),
+        max_spread_bps: None,
+        max_price_jump_bps: None,
+    };
+    let _res = execute(deps.as_mut(), inj_mock_env(), info, update_msg)
+        .expect("failed to update risk params");
+
+    // Fail to convert more than 10% of the free USDT balance, 5 INJ are worth 44.55 USDT
+    let info = mock_info("liquidity0000", &[]);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: None,
+            max_imbalance_bps: None,
+            target_denom: Some("USDT".to_string()),
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+    let res = execute(deps.as_mut(), inj_mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Withdraw: converted amount 44550000 exceeds 1000 bps of the free USDT balance"
+                .to_string()
+        }
+    );
+
+    // 10% of the vault is 1 INJ and 9 USDT, the INJ is worth 9 USDT at the oracle price and
+    // 8.91 USDT after the 1% slippage
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), inj_mock_env(), info, withdraw_msg("USDT"))
+        .expect("failed to withdraw");
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin::new(17_910000u128, "USDT")],
+        })
+    );
+    assert!(res
+        .attributes
+        .contains(&attr("consolidated_amount", "17910000")));
+
+    // The remaining 162 shares hold 10 INJ and 72.09 USDT, worth 162.09 USDT, so their value
+    // per share is not reduced
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(162_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(72_090000u128),
+            },
+        ],
+    )]);
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::TotalLiquidity {})
+        .expect("failed to query total liquidity");
+    let [base, quote]: [Uint128; 2] = from_binary(&res).expect("failed to parse liquidity");
+    let value = base.u128() / 1_000000000000 * 9 + quote.u128();
+    assert_eq!(value, 162_090000);
+    assert!(value * 180 >= 180_000000 * 162);
+}
+
+#[test]
+fn withdraw_n_fee() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id"),
+        base_decimal: 18,
+        quote_decimal: 6,
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "USDT_PRICE_ID".to_string(),
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    // Fail to withdraw when wrong liquidity is provided
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: None,
+            max_imbalance_bps: None,
+            target_denom: None,
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+
+    let env = inj_mock_env();
+    let info = mock_info("liquidity0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to set fee as non owner
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Set fee as owner
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::FeeState {})
+        .expect("failed to query fee state");
+    let fee_state: FeeStateResponse = from_binary(&res).expect("failed to parse fee state");
+    assert_eq!(
+        fee_state,
+        FeeStateResponse {
+            base_fee_collected: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "INJ".to_string(),
+                },
+                amount: Uint128::from(1_000000000000000000u128),
+            },
+            quote_fee_collected: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "USDT".to_string(),
+                },
+                amount: Uint128::from(9_000000u128),
+            },
+        }
+    );
+
+    let res = query(
+        deps.as_ref(),
+        inj_mock_env(),
+        QueryMsg::SimulateWithdraw {
+            share: Uint128::new(90_000000000000u128),
+        },
+    )
+    .expect("failed to simulate withdraw");
+    let simulated: Vec<Asset> = from_binary(&res).expect("failed to parse simulation");
+    // INJ is the base asset, so there is no separate residue
+    assert_eq!(simulated.len(), 2);
+
+    // Withdraw
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: None,
+            max_imbalance_bps: None,
+            target_denom: None,
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(90_000000000000u128),
+    });
+
+    let env = inj_mock_env();
+    let info = mock_info("liquidity0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
+    let log_withdrawn_share = res.attributes.get(2).expect("no log");
+    let log_refund_assets = res.attributes.get(3).expect("no log");
+    // Burn and the base and quote refunds, INJ is not sent again as residue
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(
+        log_refund_assets,
+        &attr(
+            "refund_assets",
+            format!("{}, {}", simulated[0], simulated[1])
+        )
+    );
+    let msg_burn_liquidity = res.messages.get(0).expect("no message");
+    let msg_refund_0 = res.messages.get(1).expect("no message");
+    let msg_refund_1 = res.messages.get(2).expect("no message");
+    assert_eq!(
+        msg_refund_0,
+        &SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0001"),
+                amount: vec![Coin::new(4_500000000000000000u128, "INJ",)],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }
+    );
+    assert_eq!(
+        msg_refund_1,
+        &SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0001"),
+                amount: vec![Coin::new(40_500000u128, "USDT",)],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }
+    );
+    assert_eq!(
+        msg_burn_liquidity,
+        &SubMsg {
+            msg: WasmMsg::Execute {
+                contract_addr: String::from("liquidity0000"),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(90_000000000000u128),
+                })
+                .expect("failed to convert to binary"),
+                funds: vec![],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }
+    );
+
+    assert_eq!(
+        log_withdrawn_share,
         &attr("withdrawn_share", 90_000000000000u128.to_string())
     );
     assert_eq!(
-        log_refund_assets,
-        &attr("refund_assets", "4500000000000000000INJ, 40500000USDT")
+        log_refund_assets,
+        &attr("refund_assets", "4500000000000000000INJ, 40500000USDT")
+    );
+
+    // Fail to withdraw fee as non owner
+    let msg = ExecuteMsg::WithdrawFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to withdraw fee more than collected
+    let msg = ExecuteMsg::WithdrawFee {
+        base_fee: Uint128::from(2_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::InsufficientFeeAccrued {});
+
+    // Fail to withdraw fee more than collected
+    let msg = ExecuteMsg::WithdrawFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(10_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::InsufficientFeeAccrued {});
+
+    // Withdraw fee
+    let msg = ExecuteMsg::WithdrawFee {
+        base_fee: Uint128::from(1_000000000000000000u128),
+        quote_fee: Uint128::from(9_000000u128),
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw fee");
+    let messages = res.messages;
+    assert_eq!(
+        messages,
+        vec![SubMsg {
+            msg: BankMsg::Send {
+                to_address: String::from("addr0000"),
+                amount: vec![
+                    Coin::new(1_000000000000000000u128, "INJ",),
+                    Coin::new(9_000000u128, "USDT",)
+                ],
+            }
+            .into(),
+            id: 0,
+            gas_limit: None,
+            reply_on: ReplyOn::Never,
+        }]
+    );
+    let attributes = res.attributes;
+    assert_eq!(attributes.len(), 1);
+    assert_eq!(
+        attributes[0],
+        &attr("fee_withdrawn", "1000000000000000000INJ, 9000000USDT")
+    );
+}
+
+#[test]
+fn test_swap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let msg = InstantiateMsg {
+        owner: "addr0000".to_string(),
+        market_id: market_id.clone(),
+        base_decimal: 18,
+        quote_decimal: 6,
+        base_price_id: "INJ_PRICE_ID".to_string(),
+        quote_price_id: "USDT_PRICE_ID".to_string(),
+        hardcap: Uint128::new(5000_000000000000u128),
+        token_code_id: 10u64,
+        credit_overpayment: false,
+        lp_decimal: None,
+        lp_token_name: None,
+        lp_token_symbol: None,
+        existing_lp_token: None,
+        deposit_start_time: None,
+        market_price_fallback: false,
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // Store liquidity token
+    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+
+    let sender_addr = "inj1x2ck0ql2ngyxqtw8jteyc0tchwnwxv7npaungt";
+    let env = inj_mock_env();
+    let info = mock_info(sender_addr, &[]);
+    let msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(8_000000000000000u128),
+        price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // Fail to place order below min order notional
+    let info = mock_info("addr0000", &[]);
+    let msg_min_notional = ExecuteMsg::UpdateMinOrderNotional {
+        min_order_notional: FPDecimal::from(10_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg_min_notional)
+        .expect("failed to update min order notional");
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: notional 8000000 below min_order_notional: 10000000".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let msg_min_notional = ExecuteMsg::UpdateMinOrderNotional {
+        min_order_notional: FPDecimal::from(8_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg_min_notional)
+        .expect("failed to update min order notional");
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone())
+        .expect("failed to place limit order");
+
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+
+    let expected_atomic_order_message = BatchUpdateOrders {
+        sender: env.contract.address.to_owned(),
+        subaccount_id: None,
+        spot_market_ids_to_cancel_all: vec![],
+        derivative_market_ids_to_cancel_all: vec![],
+        spot_orders_to_cancel: vec![],
+        derivative_orders_to_cancel: vec![],
+        spot_orders_to_create: vec![ShortSpotOrder {
+            market_id,
+            order_info: ShortOrderInfo {
+                subaccount_id: subaccount_id.into(),
+                fee_recipient: Some(env.contract.address),
+                price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+                quantity: FPDecimal::from(8_000000000000000u128),
+            },
+            order_type: OrderType::Buy,
+            trigger_price: None,
+        }],
+        derivative_orders_to_create: vec![],
+    };
+
+    let order_message = get_message_data(&res.messages, 0);
+    assert_eq!(
+        InjectiveRoute::Exchange,
+        order_message.route,
+        "route was incorrect"
+    );
+    assert_eq!(
+        expected_atomic_order_message, order_message.msg_data,
+        "spot create order had incorrect content"
+    );
+
+    let binary_response = Binary::from_base64("CAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEaDDB4MTIzNDU2Nzg5MA==").expect("failed to decode message");
+    let reply_msg = Reply {
+        id: ORDER_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(binary_response),
+        }),
+    };
+
+    let transfers_response =
+        reply(deps.as_mut(), inj_mock_env(), reply_msg).expect("failed to reply");
+    let messages = transfers_response.messages;
+    assert_eq!(messages.len(), 0);
+    let attributes = transfers_response.attributes;
+    assert_eq!(attributes.len(), 1);
+    assert_eq!(
+        attributes[0],
+        &attr("order_hash", "0x1234567890".to_string())
+    );
+
+    // The placed order is recorded as open
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::OpenOrders {})
+        .expect("failed to query open orders");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert_eq!(
+        open_orders,
+        vec![OpenOrder {
+            order_hash: "0x1234567890".to_string(),
+            buying: true,
+            price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+            quantity: FPDecimal::from(8_000000000000000u128),
+        }]
+    );
+
+    // A tracked order that already filled leaves no exposure
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::HasExposure {})
+        .expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(!has_exposure);
+
+    // The order rests on the exchange
+    deps.querier.trader_spot_orders_response_handler =
+        Some(Box::new(create_trader_spot_orders_handler()));
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::HasExposure {})
+        .expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(has_exposure);
+
+    // The order stays tracked until the cancel is confirmed
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::CancelOrder {
+        order_hash: "0x1234567890".to_string(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to cancel order");
+    assert_eq!(res.messages[0].id, CANCEL_REPLY_ID);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::OpenOrders {})
+        .expect("failed to query open orders");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert_eq!(open_orders.len(), 1);
+
+    // Confirming the cancel clears it
+    let res = reply(deps.as_mut(), env.clone(), cancel_reply()).expect("failed to reply");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "cancel_order_confirmed"),
+            attr("order_hash", "0x1234567890"),
+        ]
+    );
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::OpenOrders {})
+        .expect("failed to query open orders");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert!(open_orders.is_empty());
+
+    deps.querier.trader_spot_orders_response_handler = None;
+    let res =
+        query(deps.as_ref(), env, QueryMsg::HasExposure {}).expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(!has_exposure);
+}
+
+#[test]
+fn order_reply_without_hash() {
+    let mut deps = test_deps();
+
+    let order_response = tx::MsgBatchUpdateOrdersResponse::default();
+    let reply_msg = Reply {
+        id: ORDER_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(
+                order_response
+                    .write_to_bytes()
+                    .expect("failed to encode order response"),
+            )),
+        }),
+    };
+
+    let res = reply(deps.as_mut(), inj_mock_env(), reply_msg).expect("failed to reply");
+    assert_eq!(res.messages.len(), 0);
+    assert_eq!(res.attributes, vec![attr("order_hash", "none")]);
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::OpenOrders {})
+        .expect("failed to query open orders");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert!(open_orders.is_empty());
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::HasExposure {})
+        .expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(!has_exposure);
+}
+
+#[test]
+fn max_open_orders() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to update max open orders as non owner
+    let update_msg = ExecuteMsg::UpdateMaxOpenOrders {
+        max_open_orders: Some(2),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, update_msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, update_msg)
+        .expect("failed to update max open orders");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_max_open_orders"),
+            attr("max_open_orders", "2"),
+        ]
+    );
+
+    let swap_msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    for order_hash in ["0x01", "0x02"] {
+        let info = mock_info("addr0000", &[]);
+        let _res =
+            execute(deps.as_mut(), env.clone(), info, swap_msg.clone()).expect("failed to swap");
+
+        let mut order_response = tx::MsgBatchUpdateOrdersResponse::default();
+        order_response
+            .spot_order_hashes
+            .push(order_hash.to_string());
+        let reply_msg = Reply {
+            id: ORDER_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(
+                    order_response
+                        .write_to_bytes()
+                        .expect("failed to encode order response"),
+                )),
+            }),
+        };
+        let _res = reply(deps.as_mut(), env.clone(), reply_msg).expect("failed to reply");
+    }
+
+    // Fail to place a third order while two orders rest on the exchange
+    deps.querier.trader_spot_orders_response_handler =
+        Some(Box::new(create_trader_spot_orders_handler()));
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap_msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: 2 open orders reached max_open_orders".to_string()
+        }
+    );
+
+    // Once the orders fill they leave the book and free their slots, even though the vault
+    // still tracks them as open
+    deps.querier.trader_spot_orders_response_handler = None;
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::OpenOrders {})
+        .expect("failed to query open orders");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert_eq!(open_orders.len(), 2);
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env, info, swap_msg).expect("failed to swap");
+}
+
+#[test]
+fn swap_fund_subaccount() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+
+    // The USDT balance spent by the buy order is deposited ahead of the order
+    let msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(8_000000000000000u128),
+        price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+        fund_subaccount: true,
+        post_only: false,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to swap");
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(create_deposit_msg(
+            env.contract.address.clone(),
+            subaccount_id,
+            Coin::new(90_000000u128, "USDT"),
+        ))
+    );
+    assert_eq!(res.messages[1].id, ORDER_REPLY_ID);
+    assert_eq!(
+        res.attributes,
+        vec![attr("subaccount_deposit", "90000000USDT")]
+    );
+
+    // Only the order without the flag
+    let msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(8_000000000000000u128),
+        price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to swap");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, ORDER_REPLY_ID);
+}
+
+#[test]
+fn deposit_after_funded_swap() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let assets = vec![
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "INJ".to_string(),
+            },
+            amount: Uint128::from(10_000000000000000000u128),
+        },
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(90_000000u128),
+        },
+    ];
+    let simulate_share = |deps: Deps<InjectiveQueryWrapper>| {
+        let res = query(
+            deps,
+            inj_mock_env(),
+            QueryMsg::SimulateDeposit {
+                assets: assets.clone(),
+            },
+        )
+        .expect("failed to simulate deposit");
+        let simulation: SimulateDepositResponse =
+            from_binary(&res).expect("failed to parse simulation");
+        simulation.share
+    };
+    let share = simulate_share(deps.as_ref());
+    assert_eq!(share, Uint128::new(180_000000000000u128));
+
+    let msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(8_000000000000000u128),
+        price: FPDecimal::from_str("0.000000001").expect("failed to parse string"),
+        fund_subaccount: true,
+        post_only: false,
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to swap");
+
+    // The USDT moved onto the subaccount still counts towards the vault value
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[Coin {
+            denom: "INJ".to_string(),
+            amount: Uint128::from(10_000000000000000000u128),
+        }],
+    )]);
+    deps.querier.subaccount_deposit_response_handler =
+        Some(Box::new(create_subaccount_deposit_handler()));
+    assert_eq!(simulate_share(deps.as_ref()), share);
+
+    let info = mock_info(
+        "addr0002",
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(90_000000u128, "USDT"),
+        ],
+    );
+    let msg = ExecuteMsg::Deposit {
+        assets,
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to deposit");
+    assert!(res.attributes.contains(&attr("share", share.to_string())));
+}
+
+#[test]
+fn swap_tick_sizes() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let swap = |quantity: FPDecimal, price: &str| ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity,
+        price: FPDecimal::from_str(price).expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let price_tick = FPDecimal::from_str("0.000000000000001").expect("failed to parse string");
+    let quantity_tick = FPDecimal::from(1000000000000000u128);
+
+    // Price off the market price tick
+    let price = FPDecimal::from_str("0.0000000000090005").expect("failed to parse string");
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap(
+            FPDecimal::from(1_000000000000000000u128),
+            "0.0000000000090005",
+        ),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: format!(
+                "Swap: price {price} is not a multiple of min_price_tick_size: {price_tick}"
+            ),
+        }
+    );
+
+    // Quantity off the market quantity tick
+    let quantity = FPDecimal::from(1_000500000000000000u128 + 1);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap(quantity, "0.000000000009"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: format!(
+                "Swap: quantity {quantity} is not a multiple of min_quantity_tick_size: {quantity_tick}"
+            ),
+        }
+    );
+
+    // Aligned price and quantity are placed
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        swap(
+            FPDecimal::from(1_001000000000000000u128),
+            "0.000000000009001",
+        ),
+    )
+    .expect("failed to place spot order");
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn swap_market_not_active() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(90_000000u128, "USDT"),
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let swap_msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), env.clone(), info, swap_msg.clone()).expect("failed to swap");
+
+    // Fail to swap once the market is paused
+    deps.querier.spot_market_response_handler = Some(Box::new(create_paused_spot_market_handler()));
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, swap_msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::MarketNotActive {
+            market_id: TEST_MARKET_ID.to_string(),
+        }
+    );
+}
+
+#[test]
+fn swap_spot_batch() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(90_000000u128, "USDT"),
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let order = |buying: bool, quantity: u128, price: &str| SpotOrderParams {
+        buying,
+        quantity: FPDecimal::from(quantity),
+        price: FPDecimal::from_str(price).expect("failed to parse string"),
+    };
+
+    // Fail when the buys together need more than the 90 USDT held
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapSpotBatch {
+            orders: vec![
+                order(true, 6_000000000000000000u128, "0.000000000009"),
+                order(true, 6_000000000000000000u128, "0.000000000008"),
+            ],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "SwapBatch: 90000000 below combined notional: 102000000".to_string()
+        }
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SwapSpotBatch {
+            orders: vec![
+                order(true, 1_000000000000000000u128, "0.000000000009"),
+                order(false, 1_000000000000000000u128, "0.00000000001"),
+            ],
+        },
+    )
+    .expect("failed to swap");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, ORDER_REPLY_ID);
+    match &get_message_data(&res.messages, 0).msg_data {
+        BatchUpdateOrders {
+            spot_orders_to_create,
+            ..
+        } => {
+            assert_eq!(spot_orders_to_create.len(), 2);
+            assert_eq!(spot_orders_to_create[0].order_type, OrderType::Buy);
+            assert_eq!(
+                spot_orders_to_create[0].order_info.price,
+                FPDecimal::from_str("0.000000000009").expect("failed to parse string")
+            );
+            assert_eq!(spot_orders_to_create[1].order_type, OrderType::Sell);
+            assert_eq!(
+                spot_orders_to_create[1].order_info.price,
+                FPDecimal::from_str("0.00000000001").expect("failed to parse string")
+            );
+        }
+        _ => panic!("expected a batch update orders message"),
+    }
+
+    // Both returned hashes are tracked
+    let mut order_response = tx::MsgBatchUpdateOrdersResponse::default();
+    order_response.spot_order_hashes.push("0x01".to_string());
+    order_response.spot_order_hashes.push("0x02".to_string());
+    let reply_msg = Reply {
+        id: ORDER_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(
+                order_response
+                    .write_to_bytes()
+                    .expect("failed to encode order response"),
+            )),
+        }),
+    };
+    let res = reply(deps.as_mut(), env.clone(), reply_msg).expect("failed to reply");
+    assert_eq!(
+        res.attributes,
+        vec![attr("order_hash", "0x01"), attr("order_hash", "0x02")]
+    );
+
+    let res = query(deps.as_ref(), env, QueryMsg::OpenOrders {}).expect("failed to query");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert_eq!(open_orders.len(), 2);
+    assert!(open_orders[0].buying);
+    assert!(!open_orders[1].buying);
+}
+
+#[test]
+fn swap_post_only() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let swap = |buying: bool, price: &str| ExecuteMsg::SwapSpot {
+        buying,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str(price).expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: true,
+    };
+
+    // Without the top of book the order is placed as is
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap(true, "0.00000000001"),
+    )
+    .expect("failed to swap");
+    match &get_message_data(&res.messages, 0).msg_data {
+        BatchUpdateOrders {
+            spot_orders_to_create,
+            ..
+        } => assert_eq!(spot_orders_to_create[0].order_type, OrderType::BuyPo),
+        _ => panic!("expected a batch update orders message"),
+    }
+
+    // Best bid 0.000000000009 and best ask 0.00000000001
+    deps.querier.spot_market_mid_price_and_tob_handler =
+        Some(Box::new(create_spot_market_mid_price_handler()));
+
+    // Fail to buy at the best ask
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap(true, "0.00000000001"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: post-only buy price 0.00000000001 crosses best ask 0.00000000001"
+                .to_string()
+        }
+    );
+
+    // Fail to sell at the best bid
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        swap(false, "0.000000000009"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: post-only sell price 0.000000000009 crosses best bid 0.000000000009"
+                .to_string()
+        }
     );
 
-    // Fail to withdraw fee as non owner
-    let msg = ExecuteMsg::WithdrawFee {
-        base_fee: Uint128::from(1_000000000000000000u128),
-        quote_fee: Uint128::from(9_000000u128),
+    let info = mock_info("addr0000", &[]);
+    let res =
+        execute(deps.as_mut(), env, info, swap(false, "0.0000000000095")).expect("failed to swap");
+    match &get_message_data(&res.messages, 0).msg_data {
+        BatchUpdateOrders {
+            spot_orders_to_create,
+            ..
+        } => assert_eq!(spot_orders_to_create[0].order_type, OrderType::SellPo),
+        _ => panic!("expected a batch update orders message"),
+    }
+}
+
+#[test]
+fn swap_at_oracle() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let swap_msg = |buying: bool, buffer_bps: u64| ExecuteMsg::SwapSpotAtOracle {
+        buying,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        buffer_bps,
     };
 
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap_msg(true, 100)).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap_msg(false, 10_000)).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "buffer_bps must be below 10000".to_string()
+        }
+    );
+
+    // Oracle price is 0.000000000009 USDT per INJ in chain units
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap_msg(true, 100))
+        .expect("failed to swap at oracle");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "swap_at_oracle"),
+            attr(
+                "price",
+                FPDecimal::from_str("0.00000000000909")
+                    .expect("failed to parse string")
+                    .to_string()
+            ),
+        ]
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let res =
+        execute(deps.as_mut(), env, info, swap_msg(false, 100)).expect("failed to swap at oracle");
+    assert_eq!(
+        res.attributes[1],
+        attr(
+            "price",
+            FPDecimal::from_str("0.00000000000891")
+                .expect("failed to parse string")
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn pause() {
+    let mut deps = test_deps();
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(
+            &String::from("addr0001"),
+            &Uint128::new(180_000000000000u128),
+        )],
+    )]);
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
     let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    // Fail to pause as non owner
     let info = mock_info("addr0001", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: true },
+    )
+    .unwrap_err();
     assert_eq!(res, ContractError::Unauthorized {});
 
-    // Fail to withdraw fee more than collected
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: true },
+    )
+    .expect("failed to pause");
+    assert_eq!(
+        res.attributes,
+        vec![attr("action", "set_paused"), attr("paused", "true")]
+    );
+
+    // Fail to deposit while paused
+    let info = mock_info("addr0001", &[Coin::new(9_000000u128, "USDT")]);
+    let msg = ExecuteMsg::Deposit {
+        assets: vec![Asset {
+            info: AssetInfo::NativeToken {
+                denom: "USDT".to_string(),
+            },
+            amount: Uint128::from(9_000000u128),
+        }],
+        receiver: None,
+        min_lp_out: None,
+        max_share_price: None,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(res, ContractError::Paused {});
+
+    // Fail to swap while paused
+    let swap_msg = ExecuteMsg::SwapSpot {
+        buying: true,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, swap_msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Paused {});
+
+    // Withdrawals still go through
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: String::from("addr0001"),
+        msg: to_binary(&Cw20HookMsg::Withdraw {
+            to_subaccount: None,
+            max_imbalance_bps: None,
+            target_denom: None,
+        })
+        .expect("failed to convert to binary"),
+        amount: Uint128::new(18_000000000000u128),
+    });
+    let info = mock_info("liquidity0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw");
+
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::AddFee {
+        base_fee: Uint128::zero(),
+        quote_fee: Uint128::from(1_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to add fee");
+    let info = mock_info("addr0000", &[]);
     let msg = ExecuteMsg::WithdrawFee {
-        base_fee: Uint128::from(2_000000000000000000u128),
-        quote_fee: Uint128::from(9_000000u128),
+        base_fee: Uint128::zero(),
+        quote_fee: Uint128::from(1_000000u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw fee");
+
+    // Swaps resume once unpaused
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused { paused: false },
+    )
+    .expect("failed to unpause");
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env, info, swap_msg).expect("failed to swap");
+}
+
+#[test]
+fn inventory_skew() {
+    let mut deps = test_deps();
+
+    // 90 USD of INJ and 30 USD of USDT
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(30_000000u128, "USDT"),
+        ],
+    )]);
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::InventorySkew {})
+        .expect("failed to query inventory skew");
+    let skew: InventorySkewResponse = from_binary(&res).expect("failed to parse inventory skew");
+    assert_eq!(
+        skew,
+        InventorySkewResponse {
+            base_value: i32_to_dec(90),
+            quote_value: i32_to_dec(30),
+            skew_bps: 2500,
+        }
+    );
+
+    // 90 USD of INJ and 270 USD of USDT
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin::new(10_000000000000000000u128, "INJ"),
+            Coin::new(270_000000u128, "USDT"),
+        ],
+    )]);
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::InventorySkew {})
+        .expect("failed to query inventory skew");
+    let skew: InventorySkewResponse = from_binary(&res).expect("failed to parse inventory skew");
+    assert_eq!(skew.skew_bps, -2500);
+}
+
+#[test]
+fn market_info() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env, info, msg).expect("failed to instantiate");
+
+    let res = query(deps.as_ref(), inj_mock_env(), QueryMsg::MarketInfo {})
+        .expect("failed to query market info");
+    let market: SpotMarket = from_binary(&res).expect("failed to parse market info");
+    assert_eq!(market.ticker, "INJ/USDT");
+    assert_eq!(market.status, MarketStatus::Active);
+    assert_eq!(
+        market.min_price_tick_size,
+        FPDecimal::from_str("0.000000000000001").expect("failed to parse string")
+    );
+    assert_eq!(
+        market.min_quantity_tick_size,
+        FPDecimal::from_str("1000000000000000").expect("failed to parse string")
+    );
+}
+
+#[test]
+fn effective_fee_rate() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    // 40% of the 0.01 maker and 0.1 taker fees come back to the vault
+    let res = query(deps.as_ref(), env, QueryMsg::EffectiveFeeRate {})
+        .expect("failed to query effective fee rate");
+    let fee_rate: EffectiveFeeRateResponse =
+        from_binary(&res).expect("failed to parse effective fee rate");
+    assert_eq!(
+        fee_rate,
+        EffectiveFeeRateResponse {
+            maker_fee_rate: FPDecimal::from_str("0.006").expect("failed to parse string"),
+            taker_fee_rate: FPDecimal::from_str("0.06").expect("failed to parse string"),
+        }
+    );
+}
+
+#[test]
+fn risk_params() {
+    let mut deps = test_deps();
+
+    deps.querier.with_balance(&[(
+        &String::from(TEST_CONTRACT_ADDR),
+        &[
+            Coin {
+                denom: "INJ".to_string(),
+                amount: Uint128::from(10_000000000000000000u128),
+            },
+            Coin {
+                denom: "USDT".to_string(),
+                amount: Uint128::from(90_000000u128),
+            },
+        ],
+    )]);
+
+    let env = inj_mock_env();
+    setup_vault(deps.as_mut(), |_| {});
+
+    let res: RiskParamsResponse = from_binary(
+        &query(deps.as_ref(), env.clone(), QueryMsg::RiskParams {})
+            .expect("failed to query risk params"),
+    )
+    .expect("failed to parse risk params");
+    assert_eq!(
+        res,
+        RiskParamsResponse {
+            min_order_notional: FPDecimal::zero(),
+            max_slippage_bps: None,
+            max_spread_bps: None,
+            max_price_jump_bps: None,
+            max_conf_ratio: FPDecimal::from_str("0.02").expect("failed to parse string"),
+        }
+    );
+
+    let update_msg = ExecuteMsg::UpdateRiskParams {
+        max_slippage_bps: Some(100),
+        max_spread_bps: None,
+        max_price_jump_bps: Some(500),
+    };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, update_msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, update_msg)
+        .expect("failed to update risk params");
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_risk_params"),
+            attr("max_slippage_bps", "100"),
+            attr("max_price_jump_bps", "500"),
+        ]
+    );
+
+    let contract_info = CONTRACT_INFO
+        .load(deps.as_ref().storage)
+        .expect("failed to load contract info");
+    assert_eq!(contract_info.max_slippage_bps, Some(100));
+    assert_eq!(contract_info.max_spread_bps, None);
+    assert_eq!(contract_info.max_price_jump_bps, Some(500));
+
+    let res: RiskParamsResponse = from_binary(
+        &query(deps.as_ref(), env.clone(), QueryMsg::RiskParams {})
+            .expect("failed to query risk params"),
+    )
+    .expect("failed to parse risk params");
+    assert_eq!(res.max_slippage_bps, Some(100));
+    assert_eq!(res.max_spread_bps, None);
+    assert_eq!(res.max_price_jump_bps, Some(500));
+
+    // Oracle price is 0.000000000009 USDT per INJ in chain units
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::SwapSpot {
+        buying: false,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str("0.00000000001").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: price deviates from oracle price by more than 100 bps".to_string()
+        }
+    );
+
+    let msg = ExecuteMsg::SwapSpot {
+        buying: false,
+        quantity: FPDecimal::from(1_000000000000000000u128),
+        price: FPDecimal::from_str("0.00000000000895").expect("failed to parse string"),
+        fund_subaccount: false,
+        post_only: false,
     };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, msg.clone())
+        .expect("failed to place limit order");
+    assert_eq!(
+        LAST_SWAP_ORACLE_PRICE
+            .load(deps.as_ref().storage)
+            .expect("failed to load last swap oracle price"),
+        FPDecimal::from_str("0.000000000009").expect("failed to parse string")
+    );
+
+    // Oracle price moved 1000 bps since the last swap
+    LAST_SWAP_ORACLE_PRICE
+        .save(
+            deps.as_mut().storage,
+            &FPDecimal::from_str("0.00000000001").expect("failed to parse string"),
+        )
+        .expect("failed to save last swap oracle price");
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            val: "Swap: oracle price moved more than 500 bps since the last swap".to_string()
+        }
+    );
+}
+
+#[test]
+fn batch_cancel_by_hashes() {
+    let mut deps = test_deps();
+
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let msg = InstantiateMsg {
+        market_id: market_id.clone(),
+        ..test_instantiate_msg()
+    };
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let hashes = vec!["0x01".to_string(), "0x02".to_string(), "0x03".to_string()];
+    for order_hash in hashes.iter() {
+        OPEN_ORDERS
+            .save(
+                deps.as_mut().storage,
+                order_hash,
+                &OrderMeta {
+                    buying: true,
+                    price: FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+                    quantity: FPDecimal::from(1_000000000000000000u128),
+                },
+            )
+            .expect("failed to save open order");
+    }
+
+    // Fail to cancel from non-owner
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::BatchCancelByHashes {
+        hashes: hashes.clone(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg);
+    match res {
+        Err(ContractError::Unauthorized {}) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
 
-    let env = inj_mock_env();
+    // Fail to cancel more hashes than the cap
     let info = mock_info("addr0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-    assert_eq!(
-        res,
-        ContractError::CustomError {
-            val: String::from("Insufficient fee accrued")
+    let msg = ExecuteMsg::BatchCancelByHashes {
+        hashes: (0..21).map(|i| format!("0x{i:02}")).collect(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg);
+    match res {
+        Err(ContractError::CustomError { val }) => {
+            assert_eq!(val, "Number of hashes must be between 1 and 20")
         }
-    );
+        _ => panic!("Must return custom error"),
+    }
 
-    // Fail to withdraw fee more than collected
-    let msg = ExecuteMsg::WithdrawFee {
-        base_fee: Uint128::from(1_000000000000000000u128),
-        quote_fee: Uint128::from(10_000000u128),
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::BatchCancelByHashes {
+        hashes: hashes.clone(),
     };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to cancel orders");
+    assert_eq!(res.messages.len(), 1);
 
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+    let cancel_message = get_message_data(&res.messages, 0);
     assert_eq!(
-        res,
-        ContractError::CustomError {
-            val: String::from("Insufficient fee accrued")
+        cancel_message.msg_data,
+        BatchUpdateOrders {
+            sender: env.contract.address,
+            subaccount_id: None,
+            spot_market_ids_to_cancel_all: vec![],
+            derivative_market_ids_to_cancel_all: vec![],
+            spot_orders_to_cancel: hashes
+                .iter()
+                .map(|order_hash| OrderData {
+                    market_id: market_id.clone(),
+                    subaccount_id: subaccount_id.clone(),
+                    order_hash: order_hash.clone(),
+                    order_mask: ORDER_MASK_ANY,
+                })
+                .collect(),
+            derivative_orders_to_cancel: vec![],
+            spot_orders_to_create: vec![],
+            derivative_orders_to_create: vec![],
         }
     );
+    assert_eq!(res.messages[0].id, CANCEL_REPLY_ID);
 
-    // Withdraw fee
-    let msg = ExecuteMsg::WithdrawFee {
-        base_fee: Uint128::from(1_000000000000000000u128),
-        quote_fee: Uint128::from(9_000000u128),
-    };
+    // The orders rest on the exchange until the cancels go through
+    deps.querier.trader_spot_orders_response_handler =
+        Some(Box::new(create_trader_spot_orders_handler()));
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::HasExposure {})
+        .expect("failed to query exposure");
+    let has_exposure: bool = from_binary(&res).expect("failed to parse exposure");
+    assert!(has_exposure);
 
-    let env = inj_mock_env();
-    let info = mock_info("addr0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg).expect("failed to withdraw fee");
-    let messages = res.messages;
+    let res = reply(deps.as_mut(), env.clone(), cancel_reply()).expect("failed to reply");
     assert_eq!(
-        messages,
-        vec![SubMsg {
-            msg: BankMsg::Send {
-                to_address: String::from("addr0000"),
-                amount: vec![
-                    Coin::new(1_000000000000000000u128, "INJ",),
-                    Coin::new(9_000000u128, "USDT",)
-                ],
-            }
-            .into(),
-            id: 0,
-            gas_limit: None,
-            reply_on: ReplyOn::Never,
-        }]
-    );
-    let attributes = res.attributes;
-    assert_eq!(attributes.len(), 1);
-    assert_eq!(
-        attributes[0],
-        &attr("fee_withdrawn", "1000000000000000000INJ, 9000000USDT")
+        res.attributes,
+        vec![
+            attr("action", "cancel_order_confirmed"),
+            attr("order_hash", "0x01"),
+            attr("order_hash", "0x02"),
+            attr("order_hash", "0x03"),
+        ]
     );
+    let res = query(deps.as_ref(), env, QueryMsg::OpenOrders {}).expect("failed to query");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert!(open_orders.is_empty());
 }
 
 #[test]
-fn test_swap() {
+fn cancel_all_orders() {
     let mut deps = test_deps();
 
-    deps.querier.with_token_balances(&[(
-        &"liquidity0000".to_string(),
-        &[(
-            &String::from("addr0001"),
-            &Uint128::new(180_000000000000u128),
-        )],
-    )]);
-    deps.querier.with_balance(&[(
-        &String::from(TEST_CONTRACT_ADDR),
-        &[
-            Coin {
-                denom: "INJ".to_string(),
-                amount: Uint128::from(10_000000000000000000u128),
-            },
-            Coin {
-                denom: "USDT".to_string(),
-                amount: Uint128::from(90_000000u128),
-            },
-        ],
-    )]);
-
     let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
     let msg = InstantiateMsg {
-        owner: "addr0000".to_string(),
         market_id: market_id.clone(),
-        base_decimal: 18,
-        quote_decimal: 6,
-        base_price_id: "INJ_PRICE_ID".to_string(),
-        quote_price_id: "USDT_PRICE_ID".to_string(),
-        hardcap: Uint128::new(5000_000000000000u128),
-        token_code_id: 10u64,
+        ..test_instantiate_msg()
     };
 
     let env = inj_mock_env();
     let info = mock_info("addr0000", &[]);
     let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
 
-    // Store liquidity token
-    store_liquidity_token(deps.as_mut(), 1, "liquidity0000".to_string());
+    OPEN_ORDERS
+        .save(
+            deps.as_mut().storage,
+            "0x01",
+            &OrderMeta {
+                buying: true,
+                price: FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+                quantity: FPDecimal::from(1_000000000000000000u128),
+            },
+        )
+        .expect("failed to save open order");
 
-    let sender_addr = "inj1x2ck0ql2ngyxqtw8jteyc0tchwnwxv7npaungt";
-    let env = inj_mock_env();
-    let info = mock_info(sender_addr, &[]);
-    let msg = ExecuteMsg::SwapSpot {
-        buying: true,
-        quantity: i32_to_dec(8),
-        price: i32_to_dec(1000),
-    };
-    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    // Fail to cancel from non-owner
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CancelAllOrders {},
+    )
+    .unwrap_err();
     assert_eq!(res, ContractError::Unauthorized {});
 
     let info = mock_info("addr0000", &[]);
-    let res = execute(deps.as_mut(), env.clone(), info, msg.clone())
-        .expect("failed to place limit order");
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::CancelAllOrders {},
+    )
+    .expect("failed to cancel all orders");
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "cancel_all_orders"),
+            attr("market_id", TEST_MARKET_ID),
+        ]
+    );
 
     let subaccount_id = SubaccountId::new(
         "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
     )
     .expect("failed to create subaccount_id");
+    let cancel_message = get_message_data(&res.messages, 0);
+    assert_eq!(
+        cancel_message.msg_data,
+        BatchUpdateOrders {
+            sender: env.contract.address,
+            subaccount_id: Some(subaccount_id),
+            spot_market_ids_to_cancel_all: vec![market_id],
+            derivative_market_ids_to_cancel_all: vec![],
+            spot_orders_to_cancel: vec![],
+            derivative_orders_to_cancel: vec![],
+            spot_orders_to_create: vec![],
+            derivative_orders_to_create: vec![],
+        }
+    );
+    assert_eq!(res.messages[0].id, CANCEL_REPLY_ID);
 
-    let expected_atomic_order_message = BatchUpdateOrders {
-        sender: env.contract.address.to_owned(),
-        subaccount_id: None,
-        spot_market_ids_to_cancel_all: vec![],
-        derivative_market_ids_to_cancel_all: vec![],
-        spot_orders_to_cancel: vec![],
-        derivative_orders_to_cancel: vec![],
-        spot_orders_to_create: vec![ShortSpotOrder {
-            market_id,
-            order_info: ShortOrderInfo {
-                subaccount_id: subaccount_id.into(),
-                fee_recipient: Some(env.contract.address),
-                price: i32_to_dec(1000),
-                quantity: i32_to_dec(8),
-            },
-            order_type: OrderType::Buy,
-            trigger_price: None,
-        }],
-        derivative_orders_to_create: vec![],
-    };
+    // The orders stay tracked until the exchange confirms the cancels
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::OpenOrders {}).expect("failed to query");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert_eq!(open_orders.len(), 1);
 
-    let order_message = get_message_data(&res.messages, 0);
+    reply(deps.as_mut(), env.clone(), cancel_reply()).expect("failed to reply");
+    let res = query(deps.as_ref(), env, QueryMsg::OpenOrders {}).expect("failed to query");
+    let open_orders: Vec<OpenOrder> = from_binary(&res).expect("failed to parse open orders");
+    assert!(open_orders.is_empty());
+}
+
+#[test]
+fn lp_token_name() {
+    let name = |denom0: &str, denom1: &str| {
+        format_lp_token_name(&denom0.to_string(), &denom1.to_string())
+            .expect("failed to format lp token name")
+    };
+    assert_eq!(name("INJ", "USDT"), "INJ-USDT-LP");
+    // Factory denoms sharing a creator prefix used to collide
     assert_eq!(
-        InjectiveRoute::Exchange,
-        order_message.route,
-        "route was incorrect"
+        name(
+            "factory/inj1kz7vxlmrnxkrzh7zymwfhvxxkasnsl2w4s8ujs/usdx",
+            "USDT"
+        ),
+        "USDX-USDT-LP"
     );
     assert_eq!(
-        expected_atomic_order_message, order_message.msg_data,
-        "spot create order had incorrect content"
+        name(
+            "factory/inj1kz7vxlmrnxkrzh7zymwfhvxxkasnsl2w4s8ujs/usdy",
+            "USDT"
+        ),
+        "USDY-USDT-LP"
+    );
+    assert_eq!(
+        name("peggy0x87aB3B4C8661e07D6372361211B96ed4Dc36B1B5", "INJ"),
+        "PEGGY0X8-INJ-LP"
+    );
+    // Identical short denoms get an index
+    assert_eq!(
+        name(
+            "factory/inj1kz7vxlmrnxkrzh7zymwfhvxxkasnsl2w4s8ujs/usd",
+            "factory/inj1qg5ega6dykkxc307y25pecuufrjkxkaggkkxh7/usd"
+        ),
+        "USD-USD2-LP"
     );
+}
 
-    let binary_response = Binary::from_base64("CAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBCAEIAQgBEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEQARABEAEaDDB4MTIzNDU2Nzg5MA==").expect("failed to decode message");
-    let reply_msg = Reply {
-        id: ORDER_REPLY_ID,
-        result: SubMsgResult::Ok(SubMsgResponse {
-            events: vec![],
-            data: Some(binary_response),
-        }),
+#[test]
+fn lp_token_symbol() {
+    assert_eq!(format_lp_token_symbol("INJ/USDT"), "IULP");
+    assert_eq!(format_lp_token_symbol("weth/usdt"), "WULP");
+    // Falls back when the ticker doesn't have two sides
+    assert_eq!(format_lp_token_symbol("INJUSDT"), "uLP");
+    assert_eq!(format_lp_token_symbol("1/2"), "uLP");
+}
+
+#[test]
+fn cancel_order_attributes() {
+    let mut deps = test_deps();
+
+    let msg = test_instantiate_msg();
+
+    let env = inj_mock_env();
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).expect("failed to instantiate");
+
+    let msg = ExecuteMsg::CancelOrder {
+        order_hash: "0x1234567890".to_string(),
     };
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
 
-    let transfers_response =
-        reply(deps.as_mut(), inj_mock_env(), reply_msg).expect("failed to reply");
-    let messages = transfers_response.messages;
-    assert_eq!(messages.len(), 0);
-    let attributes = transfers_response.attributes;
-    assert_eq!(attributes.len(), 1);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, msg).expect("failed to cancel order");
+    assert_eq!(res.messages.len(), 1);
     assert_eq!(
-        attributes[0],
-        &attr("order_hash", "0x1234567890".to_string())
+        res.attributes,
+        vec![
+            attr("action", "cancel_order"),
+            attr("order_hash", "0x1234567890"),
+            attr("market_id", TEST_MARKET_ID),
+        ]
+    );
+}
+
+#[test]
+fn cancel_order_msg_shapes() {
+    let sender = Addr::unchecked(TEST_CONTRACT_ADDR);
+    let market_id = MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id");
+    let subaccount_id = SubaccountId::new(
+        "0xade4a5f5803a439835c636395a8d648dee57b2fc000000000000000000000000".to_string(),
+    )
+    .expect("failed to create subaccount_id");
+
+    let msg = cancel_order_msg(
+        MarketType::Spot,
+        sender.clone(),
+        market_id.clone(),
+        subaccount_id.clone(),
+        "0x1234567890".to_string(),
+    );
+    match msg {
+        CosmosMsg::Custom(msg) => assert_eq!(
+            msg.msg_data,
+            InjectiveMsg::CancelSpotOrder {
+                sender: sender.clone(),
+                market_id: market_id.clone(),
+                subaccount_id: subaccount_id.clone(),
+                order_hash: "0x1234567890".to_string(),
+            }
+        ),
+        _ => panic!("No wrapped message found"),
+    }
+
+    let msg = cancel_order_msg(
+        MarketType::Derivative,
+        sender.clone(),
+        market_id.clone(),
+        subaccount_id.clone(),
+        "0x1234567890".to_string(),
     );
+    match msg {
+        CosmosMsg::Custom(msg) => assert_eq!(
+            msg.msg_data,
+            InjectiveMsg::CancelDerivativeOrder {
+                sender,
+                market_id,
+                subaccount_id,
+                order_hash: "0x1234567890".to_string(),
+                order_mask: ORDER_MASK_ANY,
+            }
+        ),
+        _ => panic!("No wrapped message found"),
+    }
 }
 
 fn create_pyth_price_handler() -> impl HandlesPythPriceQuery {
     struct Temp();
     impl HandlesPythPriceQuery for Temp {
         fn handle(&self, price_id: String) -> QuerierResult {
-            let start = SystemTime::now();
-            let since_the_epoch = start
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards");
-            let timestamp = (since_the_epoch.as_secs() as i64) - 30;
+            // Published shortly before the mock block time
+            let timestamp = (inj_mock_env().block.time.seconds() as i64) - 30;
             let response = match price_id.as_str() {
                 "INJ_PRICE_ID" => PythPriceResponse {
                     price_state: Some(PythPriceState {
                         price_id: "INJ_PRICE_ID".to_string(),
                         ema_price: FPDecimal::from_str("9").expect("failed to parse string"),
-                        ema_conf: FPDecimal::from_str("9").expect("failed to parse string"),
-                        conf: FPDecimal::from_str("9").expect("failed to parse string"),
+                        ema_conf: FPDecimal::from_str("0.09").expect("failed to parse string"),
+                        conf: FPDecimal::from_str("0.09").expect("failed to parse string"),
                         publish_time: timestamp,
                         price_state: PriceState {
                             price: FPDecimal::from_str("9").expect("failed to parse string"),
@@ -847,8 +5869,8 @@ fn create_pyth_price_handler() -> impl HandlesPythPriceQuery {
                     price_state: Some(PythPriceState {
                         price_id: "USDT_PRICE_ID".to_string(),
                         ema_price: FPDecimal::one(),
-                        ema_conf: FPDecimal::one(),
-                        conf: FPDecimal::one(),
+                        ema_conf: FPDecimal::from_str("0.01").expect("failed to parse string"),
+                        conf: FPDecimal::from_str("0.01").expect("failed to parse string"),
                         publish_time: timestamp,
                         price_state: PriceState {
                             price: FPDecimal::one(),
@@ -857,14 +5879,45 @@ fn create_pyth_price_handler() -> impl HandlesPythPriceQuery {
                         },
                     }),
                 },
+                "WIDE_INJ_PRICE_ID" => PythPriceResponse {
+                    price_state: Some(PythPriceState {
+                        price_id: "WIDE_INJ_PRICE_ID".to_string(),
+                        ema_price: FPDecimal::from_str("9").expect("failed to parse string"),
+                        ema_conf: FPDecimal::from_str("0.9").expect("failed to parse string"),
+                        conf: FPDecimal::from_str("0.9").expect("failed to parse string"),
+                        publish_time: timestamp,
+                        price_state: PriceState {
+                            price: FPDecimal::from_str("9").expect("failed to parse string"),
+                            cumulative_price: FPDecimal::from_str("9")
+                                .expect("failed to parse string"),
+                            timestamp,
+                        },
+                    }),
+                },
+                "STALE_INJ_PRICE_ID" => PythPriceResponse {
+                    price_state: Some(PythPriceState {
+                        price_id: "STALE_INJ_PRICE_ID".to_string(),
+                        ema_price: FPDecimal::from_str("9").expect("failed to parse string"),
+                        ema_conf: FPDecimal::from_str("0.09").expect("failed to parse string"),
+                        conf: FPDecimal::from_str("0.09").expect("failed to parse string"),
+                        publish_time: timestamp - 120,
+                        price_state: PriceState {
+                            price: FPDecimal::from_str("9").expect("failed to parse string"),
+                            cumulative_price: FPDecimal::from_str("9")
+                                .expect("failed to parse string"),
+                            timestamp: timestamp - 120,
+                        },
+                    }),
+                },
+                "UNKNOWN_PRICE_ID" => PythPriceResponse { price_state: None },
                 _ => PythPriceResponse {
                     price_state: Some(PythPriceState {
                         price_id:
                             "0xff0ec26442c57d7456695b843694e7379b15cf1b250b27e0e47e657f1955aaff"
                                 .to_string(),
                         ema_price: FPDecimal::one(),
-                        ema_conf: FPDecimal::one(),
-                        conf: FPDecimal::one(),
+                        ema_conf: FPDecimal::from_str("0.01").expect("failed to parse string"),
+                        conf: FPDecimal::from_str("0.01").expect("failed to parse string"),
                         publish_time: timestamp,
                         price_state: PriceState {
                             price: FPDecimal::one(),
@@ -880,18 +5933,151 @@ fn create_pyth_price_handler() -> impl HandlesPythPriceQuery {
     Temp()
 }
 
+fn create_spot_market_mid_price_handler() -> impl HandlesMarketIdQuery {
+    struct Temp();
+    impl HandlesMarketIdQuery for Temp {
+        fn handle(&self, _market_id: MarketId) -> QuerierResult {
+            let response = MarketMidPriceAndTOBResponse {
+                mid_price: Some(
+                    FPDecimal::from_str("0.0000000000095").expect("failed to parse string"),
+                ),
+                best_buy_price: Some(
+                    FPDecimal::from_str("0.000000000009").expect("failed to parse string"),
+                ),
+                best_sell_price: Some(
+                    FPDecimal::from_str("0.00000000001").expect("failed to parse string"),
+                ),
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp()
+}
+
+fn create_aggregate_account_volume_handler() -> impl HandlesAccountAddressQuery {
+    struct Temp();
+    impl HandlesAccountAddressQuery for Temp {
+        fn handle(&self, _account: String) -> QuerierResult {
+            let response = QueryAggregateVolumeResponse {
+                aggregate_volumes: Some(vec![
+                    MarketVolume {
+                        market_id: MarketId::new(TEST_MARKET_ID.to_string())
+                            .expect("failed to create market_id"),
+                        volume: VolumeByType {
+                            maker_volume: i32_to_dec(1500),
+                            taker_volume: i32_to_dec(200),
+                        },
+                    },
+                    MarketVolume {
+                        market_id: MarketId::new(
+                            "0x0000000000000000000000000000000000000000000000000000000000000000"
+                                .to_string(),
+                        )
+                        .expect("failed to create market_id"),
+                        volume: VolumeByType {
+                            maker_volume: i32_to_dec(700),
+                            taker_volume: i32_to_dec(100),
+                        },
+                    },
+                ]),
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp()
+}
+
+fn create_trader_spot_orders_handler() -> impl HandlesMarketAndSubaccountQuery {
+    struct Temp();
+    impl HandlesMarketAndSubaccountQuery for Temp {
+        fn handle(&self, _market_id: MarketId, _subaccount_id: SubaccountId) -> QuerierResult {
+            let response = TraderSpotOrdersResponse {
+                orders: Some(vec![
+                    TrimmedSpotLimitOrder {
+                        price: i32_to_dec(1000),
+                        quantity: i32_to_dec(8),
+                        fillable: i32_to_dec(5),
+                        isBuy: true,
+                        order_hash: "0x1234567890".to_string(),
+                    },
+                    TrimmedSpotLimitOrder {
+                        price: i32_to_dec(1100),
+                        quantity: i32_to_dec(2),
+                        fillable: i32_to_dec(2),
+                        isBuy: false,
+                        order_hash: "0x0987654321".to_string(),
+                    },
+                ]),
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp()
+}
+
+fn create_subaccount_deposit_handler() -> impl HandlesSubaccountAndDenomQuery {
+    struct Temp();
+    impl HandlesSubaccountAndDenomQuery for Temp {
+        fn handle(&self, _subaccount_id: SubaccountId, denom: String) -> QuerierResult {
+            let total_balance = if denom == "USDT" {
+                FPDecimal::from(90_000000u128)
+            } else {
+                FPDecimal::zero()
+            };
+            let response = SubaccountDepositResponse {
+                deposits: Deposit {
+                    available_balance: total_balance,
+                    total_balance,
+                },
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp()
+}
+
+fn create_paused_spot_market_handler() -> impl HandlesMarketIdQuery {
+    struct Temp();
+    impl HandlesMarketIdQuery for Temp {
+        fn handle(&self, market_id: MarketId) -> QuerierResult {
+            let response = SpotMarketResponse {
+                market: Some(SpotMarket {
+                    ticker: "INJ/USDT".to_string(),
+                    base_denom: "INJ".to_string(),
+                    quote_denom: "USDT".to_string(),
+                    maker_fee_rate: FPDecimal::from_str("0.01").expect("failed to parse string"),
+                    taker_fee_rate: FPDecimal::from_str("0.1").expect("failed to parse string"),
+                    relayer_fee_share_rate: FPDecimal::from_str("0.4")
+                        .expect("failed to parse string"),
+                    market_id,
+                    status: MarketStatus::Paused,
+                    min_price_tick_size: FPDecimal::from_str("0.000000000000001")
+                        .expect("failed to parse string"),
+                    min_quantity_tick_size: FPDecimal::from_str("1000000000000000")
+                        .expect("failed to parse string"),
+                }),
+            };
+            SystemResult::Ok(ContractResult::from(to_binary(&response)))
+        }
+    }
+    Temp()
+}
+
 fn create_spot_market_handler() -> impl HandlesMarketIdQuery {
     struct Temp();
     impl HandlesMarketIdQuery for Temp {
         fn handle(&self, market_id: MarketId) -> QuerierResult {
-            if market_id
-                == MarketId::new(TEST_MARKET_ID.to_string()).expect("failed to create market_id")
-            {
+            let denoms = match market_id.as_str() {
+                TEST_MARKET_ID => Some(("INJ", "USDT")),
+                TEST_ATOM_MARKET_ID => Some(("ATOM", "USDT")),
+                _ => None,
+            };
+            if let Some((base_denom, quote_denom)) = denoms {
                 let response = SpotMarketResponse {
                     market: Some(SpotMarket {
-                        ticker: "INJ/USDT".to_string(),
-                        base_denom: "INJ".to_string(),
-                        quote_denom: "USDT".to_string(),
+                        ticker: format!("{base_denom}/{quote_denom}"),
+                        base_denom: base_denom.to_string(),
+                        quote_denom: quote_denom.to_string(),
                         maker_fee_rate: FPDecimal::from_str("0.01")
                             .expect("failed to parse string"),
                         taker_fee_rate: FPDecimal::from_str("0.1").expect("failed to parse string"),